@@ -0,0 +1,577 @@
+//! configサブコマンド
+
+use crate::output::{self, Report};
+use crate::{Cli, config_path, load_config, project_config_path};
+use anyhow::{Context, Result};
+use clap::Subcommand;
+use rp_core::{Config, ConfigFormat, Diagnostic};
+use serde::Serialize;
+use similar::TextDiff;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+#[derive(Subcommand)]
+pub enum ConfigCommand {
+    /// 読み込んだ設定（プロジェクトの設定ファイルと共有プリセットを重ねた結果）を表示
+    Show {
+        /// 設定の読み込み元のファイルを表示
+        #[arg(long)]
+        sources: bool,
+    },
+
+    /// 設定ファイルと共有プリセットを検査し、問題があれば終了コード1で終了
+    Check {
+        /// 設定ファイルのパス（指定しない場合は -c またはデフォルトパス）
+        path: Option<PathBuf>,
+    },
+
+    /// 設定ファイルを現在の形式に移行（元のファイルは日時付きのバックアップを作成）
+    Migrate {
+        /// 設定ファイルのパス（指定しない場合は -c またはデフォルトパス）
+        path: Option<PathBuf>,
+
+        /// 書き込まずに変更内容の差分を表示
+        #[arg(long)]
+        dry_run: bool,
+    },
+
+    /// 設定ファイルの JSON Schema を出力（エディタの補完と検証用）
+    Schema {
+        /// 出力先のファイル（指定しない場合は標準出力）
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+
+    /// 設定ファイルを別の形式に変換（形式は拡張子から判定: .toml, .json, .yaml, .yml）
+    ///
+    /// 古い形式の場合は現在の形式に移行してから変換する
+    Convert {
+        /// 変換元のファイル
+        input: PathBuf,
+
+        /// 変換先のファイル
+        output: PathBuf,
+
+        /// 変換先のファイルが存在する場合に上書きする
+        #[arg(short, long)]
+        force: bool,
+    },
+}
+
+/// configサブコマンドの実行
+pub fn run(cli: &Cli, command: &ConfigCommand) -> Result<()> {
+    match command {
+        ConfigCommand::Show { sources } => cmd_show(cli, *sources),
+        ConfigCommand::Check { path } => cmd_check(cli, path.as_deref()),
+        ConfigCommand::Migrate { path, dry_run } => cmd_migrate(cli, path.as_deref(), *dry_run),
+        ConfigCommand::Schema { output } => cmd_schema(cli, output.as_deref()),
+        ConfigCommand::Convert {
+            input,
+            output,
+            force,
+        } => cmd_convert(cli, input, output, *force),
+    }
+}
+
+/// config showの結果
+#[derive(Serialize)]
+struct ShowReport {
+    /// 読み込んだ設定（展開した値は元の参照に戻す）
+    config: toml::Table,
+    /// TOML 形式の内容
+    #[serde(skip)]
+    toml: String,
+}
+
+impl Report for ShowReport {
+    fn print_text(&self) {
+        print!("{}", self.toml);
+    }
+}
+
+/// config show --sources の結果
+#[derive(Serialize)]
+struct SourcesReport {
+    /// 設定ファイル（後のものが優先）
+    files: Vec<SourceFile>,
+    /// 共有プリセットのファイル（後のものが優先、設定ファイルのプリセットが最優先）
+    preset_files: Vec<PathBuf>,
+    /// 読み込んだ .env（先のものが優先、既存の環境変数が最優先）
+    env_files: Vec<PathBuf>,
+}
+
+/// 設定の読み込み元のファイル
+#[derive(Serialize)]
+struct SourceFile {
+    path: PathBuf,
+    /// プロジェクトの設定ファイルか
+    project: bool,
+}
+
+impl Report for SourcesReport {
+    fn print_text(&self) {
+        println!("{}", t!("config.sources.files"));
+        if self.files.is_empty() {
+            println!("  {}", t!("config.sources.no_files"));
+        }
+        for file in &self.files {
+            if file.project {
+                println!(
+                    "  {}",
+                    t!("config.sources.project", path = file.path.display())
+                );
+            } else {
+                println!("  {}", file.path.display());
+            }
+        }
+
+        if !self.preset_files.is_empty() {
+            println!();
+            println!("{}", t!("config.sources.preset_files"));
+            for path in &self.preset_files {
+                println!("  {}", path.display());
+            }
+        }
+
+        if !self.env_files.is_empty() {
+            println!();
+            println!("{}", t!("config.sources.env_files"));
+            for path in &self.env_files {
+                println!("  {}", path.display());
+            }
+        }
+    }
+}
+
+/// config showの実行
+fn cmd_show(cli: &Cli, sources: bool) -> Result<()> {
+    let config = load_config(cli)?;
+    if !sources {
+        let toml = config.to_toml_string()?;
+        let config = toml.parse().context(t!("config.serialize_failed"))?;
+        return output::emit(cli, &ShowReport { config, toml });
+    }
+
+    let project = project_config_path(cli);
+    let files = config
+        .sources()
+        .iter()
+        .map(|path| SourceFile {
+            path: path.clone(),
+            project: project.as_deref() == Some(path.as_path()),
+        })
+        .collect();
+
+    let base_dir = config_path(cli.config.as_deref())?
+        .parent()
+        .map(Path::to_path_buf)
+        .unwrap_or_default();
+
+    output::emit(
+        cli,
+        &SourcesReport {
+            files,
+            preset_files: config.preset_files(&base_dir)?,
+            env_files: cli.env_files.clone(),
+        },
+    )
+}
+
+/// config checkの結果
+#[derive(Serialize)]
+struct CheckReport {
+    /// 検査した設定ファイル
+    path: PathBuf,
+    /// 見つかった問題（ファイルと位置の順）
+    diagnostics: Vec<DiagnosticReport>,
+}
+
+/// 見つかった問題
+#[derive(Serialize)]
+struct DiagnosticReport {
+    /// 問題のあるファイル（環境変数など、ファイルに由来しない場合は `None`）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    file: Option<PathBuf>,
+    /// 1始まりの行番号
+    #[serde(skip_serializing_if = "Option::is_none")]
+    line: Option<usize>,
+    /// 1始まりの列番号（文字単位）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    column: Option<usize>,
+    /// 問題のあるキーのパス（例: `presets.work.app`）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    key: Option<String>,
+    /// 問題の内容
+    message: String,
+    /// 問題のある行の内容と、下線を引く文字数
+    #[serde(skip)]
+    snippet: Option<(String, usize)>,
+}
+
+impl Report for CheckReport {
+    fn print_text(&self) {
+        if self.diagnostics.is_empty() {
+            println!("✓ {}", t!("config.check.ok", path = self.path.display()));
+            return;
+        }
+
+        for diagnostic in &self.diagnostics {
+            let location = match (&diagnostic.file, diagnostic.line, diagnostic.column) {
+                (Some(file), Some(line), Some(column)) => {
+                    format!("{}:{}:{}: ", file.display(), line, column)
+                }
+                (Some(file), _, _) => format!("{}: ", file.display()),
+                (None, _, _) => String::new(),
+            };
+            let key = diagnostic
+                .key
+                .as_ref()
+                .map(|key| format!("{}: ", key))
+                .unwrap_or_default();
+            println!("{}{}{}", location, key, diagnostic.message);
+
+            if let (Some((text, width)), Some(line), Some(column)) =
+                (&diagnostic.snippet, diagnostic.line, diagnostic.column)
+            {
+                print_snippet(text, *width, line, column);
+            }
+            println!();
+        }
+    }
+}
+
+/// config checkの実行
+fn cmd_check(cli: &Cli, path: Option<&Path>) -> Result<()> {
+    let path = config_path(path.or(cli.config.as_deref()))?;
+    let source = std::fs::read_to_string(&path)
+        .with_context(|| t!("config.read_failed", path = path.display()))?;
+
+    let base_dir = path.parent().map(Path::to_path_buf).unwrap_or_default();
+    let (config, diagnostics) =
+        Config::check_source_as(&source, ConfigFormat::from_path(&path), &base_dir);
+    let mut diagnostics = diagnostics
+        .into_iter()
+        .map(|diagnostic| diagnostic.file(&path))
+        .collect::<Vec<_>>();
+
+    if let Some(mut config) = config {
+        diagnostics.extend(config.check_preset_dirs(&base_dir));
+
+        // プリセットが参照するApplication IDは環境変数で登録されている場合もある
+        if let Err(e) = config.load_from_env() {
+            diagnostics.push(Diagnostic::new(Vec::<String>::new(), e.full_message()));
+        }
+
+        diagnostics.extend(config.validate().into_iter().map(|mut diagnostic| {
+            diagnostic.file.get_or_insert_with(|| path.clone());
+            diagnostic
+        }));
+    }
+
+    let report = CheckReport {
+        path,
+        diagnostics: locate_diagnostics(diagnostics),
+    };
+    if report.diagnostics.is_empty() {
+        return output::emit(cli, &report);
+    }
+
+    let error = anyhow::anyhow!(t!("config.check.found", count = report.diagnostics.len()));
+    Err(output::emit_failure(cli, &report, error))
+}
+
+/// config migrateの結果
+#[derive(Serialize)]
+struct MigrateReport {
+    /// 移行した設定ファイル
+    path: PathBuf,
+    /// 適用した移行（最新の形式の場合は空）
+    steps: Vec<String>,
+    /// 書き込まずに差分を表示したか
+    dry_run: bool,
+    /// 変更内容の差分（--dry-run の場合のみ）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    diff: Option<String>,
+    /// 元のファイルのバックアップ
+    #[serde(skip_serializing_if = "Option::is_none")]
+    backup: Option<PathBuf>,
+}
+
+impl Report for MigrateReport {
+    fn print_text(&self) {
+        if self.steps.is_empty() {
+            println!(
+                "✓ {}",
+                t!("config.migrate.up_to_date", path = self.path.display())
+            );
+            return;
+        }
+
+        println!("{}", t!("config.migrate.steps"));
+        for step in &self.steps {
+            println!("  - {}", step);
+        }
+        println!();
+
+        if let Some(ref diff) = self.diff {
+            print!("{}", diff);
+            println!();
+            println!("{}", t!("config.migrate.dry_run"));
+        }
+        if let Some(ref backup) = self.backup {
+            println!("{}", t!("config.migrate.backup", path = backup.display()));
+            println!(
+                "✓ {}",
+                t!("config.migrate.done", path = self.path.display())
+            );
+        }
+    }
+}
+
+/// config migrateの実行
+fn cmd_migrate(cli: &Cli, path: Option<&Path>, dry_run: bool) -> Result<()> {
+    let path = config_path(path.or(cli.config.as_deref()))?;
+    let source = std::fs::read_to_string(&path)
+        .with_context(|| t!("config.read_failed", path = path.display()))?;
+
+    // TOML はコメントなどの記述を残すため、変更する部分のみを書き換える
+    let format = ConfigFormat::from_path(&path);
+    let (migrated, steps) = match format {
+        ConfigFormat::Toml => Config::migrate_toml(&source)
+            .with_context(|| t!("config.read_failed", path = path.display()))?,
+        _ => {
+            let mut table = format
+                .parse(&source)
+                .with_context(|| t!("config.read_failed", path = path.display()))?;
+            let steps = Config::migrate(&mut table)?;
+            (format.to_string(&table)?, steps)
+        }
+    };
+    let mut report = MigrateReport {
+        path,
+        steps,
+        dry_run,
+        diff: None,
+        backup: None,
+    };
+    if report.steps.is_empty() {
+        return output::emit(cli, &report);
+    }
+    let path = &report.path;
+
+    // 移行後の内容が読み込めることを書き込む前に確認する
+    let base_dir = path.parent().map(Path::to_path_buf).unwrap_or_default();
+    Config::from_table(format.parse(&migrated)?, &base_dir)
+        .context(t!("config.migrate.invalid_result"))?;
+
+    if dry_run {
+        let diff = TextDiff::from_lines(&source, &migrated);
+        report.diff = Some(
+            diff.unified_diff()
+                .header(
+                    &path.display().to_string(),
+                    &t!("config.migrate.diff_header"),
+                )
+                .to_string(),
+        );
+        return output::emit(cli, &report);
+    }
+
+    let backup = Config::backup(path)?;
+    std::fs::write(path, migrated)
+        .with_context(|| t!("config.write_failed", path = path.display()))?;
+
+    report.backup = Some(backup);
+    output::emit(cli, &report)
+}
+
+/// config schemaの結果
+#[derive(Serialize)]
+struct SchemaReport {
+    /// 書き込んだファイル（標準出力に出力する場合は `None`）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    path: Option<PathBuf>,
+    /// JSON Schema（ファイルに書き込んだ場合は `None`）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    schema: Option<serde_json::Value>,
+}
+
+impl Report for SchemaReport {
+    fn print_text(&self) {
+        if let Some(ref path) = self.path {
+            println!("✓ {}", t!("config.schema.written", path = path.display()));
+        }
+        if let Some(ref schema) = self.schema {
+            println!("{:#}", schema);
+        }
+    }
+}
+
+/// config schemaの実行
+fn cmd_schema(cli: &Cli, output: Option<&Path>) -> Result<()> {
+    let schema = Config::json_schema();
+    let Some(output) = output else {
+        let schema = serde_json::from_str(&schema).context(t!("config.schema.failed"))?;
+        return output::emit(
+            cli,
+            &SchemaReport {
+                path: None,
+                schema: Some(schema),
+            },
+        );
+    };
+
+    std::fs::write(output, schema + "\n")
+        .with_context(|| t!("config.schema.write_failed", path = output.display()))?;
+    output::emit(
+        cli,
+        &SchemaReport {
+            path: Some(output.to_path_buf()),
+            schema: None,
+        },
+    )
+}
+
+/// config convertの結果
+#[derive(Serialize)]
+struct ConvertReport<'a> {
+    /// 変換元のファイル
+    input: &'a Path,
+    /// 変換先のファイル
+    output: &'a Path,
+    /// 変換元の形式
+    from: &'static str,
+    /// 変換先の形式
+    to: &'static str,
+    /// 変換前に適用した移行
+    migrations: Vec<String>,
+}
+
+impl Report for ConvertReport<'_> {
+    fn print_text(&self) {
+        for step in &self.migrations {
+            println!("{}", t!("config.convert.migration", step = step));
+        }
+        println!(
+            "✓ {}",
+            t!(
+                "config.convert.done",
+                from = self.from,
+                to = self.to,
+                path = self.output.display()
+            )
+        );
+    }
+}
+
+/// config convertの実行
+fn cmd_convert(cli: &Cli, input: &Path, output: &Path, force: bool) -> Result<()> {
+    if output.exists() && !force {
+        anyhow::bail!(t!("config.convert.exists", path = output.display()));
+    }
+
+    let from = ConfigFormat::from_path(input);
+    let to = ConfigFormat::from_path(output);
+    let source = std::fs::read_to_string(input)
+        .with_context(|| t!("config.read_failed", path = input.display()))?;
+    let mut table = from
+        .parse(&source)
+        .with_context(|| t!("config.read_failed", path = input.display()))?;
+
+    let migrations = Config::migrate(&mut table)?;
+
+    // 変換後の内容が読み込めることを書き込む前に確認する（参照は展開せずに書き込む）
+    let base_dir = input.parent().map(Path::to_path_buf).unwrap_or_default();
+    Config::from_table(table.clone(), &base_dir)
+        .with_context(|| t!("config.read_failed", path = input.display()))?;
+    let converted = to.to_string(&table)?;
+
+    if let Some(parent) = output.parent().filter(|p| !p.as_os_str().is_empty()) {
+        std::fs::create_dir_all(parent)
+            .with_context(|| t!("config.create_dir_failed", path = parent.display()))?;
+    }
+    std::fs::write(output, converted)
+        .with_context(|| t!("config.write_failed", path = output.display()))?;
+
+    output::emit(
+        cli,
+        &ConvertReport {
+            input,
+            output,
+            from: from.name(),
+            to: to.name(),
+            migrations,
+        },
+    )
+}
+
+/// 診断の行と列を求め、ファイルと位置の順に並べる
+fn locate_diagnostics(mut diagnostics: Vec<Diagnostic>) -> Vec<DiagnosticReport> {
+    let mut sources: BTreeMap<PathBuf, String> = BTreeMap::new();
+    for file in diagnostics.iter().filter_map(|d| d.file.clone()) {
+        if !sources.contains_key(&file)
+            && let Ok(source) = std::fs::read_to_string(&file)
+        {
+            sources.insert(file, source);
+        }
+    }
+
+    // キーの位置は TOML のファイルからのみ探せる
+    for diagnostic in diagnostics.iter_mut() {
+        if let Some(source) = diagnostic
+            .file
+            .as_ref()
+            .filter(|f| ConfigFormat::from_path(f) == ConfigFormat::Toml)
+            .and_then(|f| sources.get(f))
+        {
+            diagnostic.locate(source);
+        }
+    }
+    diagnostics.sort_by_key(|d| (d.file.clone(), d.span.as_ref().map(|span| span.start)));
+
+    diagnostics
+        .into_iter()
+        .map(|diagnostic| {
+            let source = diagnostic.file.as_ref().and_then(|f| sources.get(f));
+            let position = source.and_then(|source| Some((source, diagnostic.line_col(source)?)));
+            let snippet = position.and_then(|(source, (line, column))| {
+                let text = source.lines().nth(line - 1)?;
+                Some((text.to_string(), underline_width(&diagnostic, text, column)))
+            });
+
+            DiagnosticReport {
+                line: position.map(|(_, (line, _))| line),
+                column: position.map(|(_, (_, column))| column),
+                key: (!diagnostic.key.is_empty()).then(|| diagnostic.key_path()),
+                message: diagnostic.message,
+                file: diagnostic.file,
+                snippet,
+            }
+        })
+        .collect()
+}
+
+/// 問題のある範囲に下線を引く文字数
+fn underline_width(diagnostic: &Diagnostic, text: &str, column: usize) -> usize {
+    diagnostic.span.as_ref().map_or(1, |span| {
+        let start = text
+            .char_indices()
+            .nth(column - 1)
+            .map_or(text.len(), |(i, _)| i);
+        let end = (start + span.len()).min(text.len());
+        text.get(start..end).map_or(1, |s| s.chars().count().max(1))
+    })
+}
+
+/// 問題のある行と、その範囲を示す下線を表示
+fn print_snippet(text: &str, width: usize, line: usize, column: usize) {
+    let gutter = " ".repeat(line.to_string().len());
+    println!("{} |", gutter);
+    println!("{} | {}", line, text);
+    println!(
+        "{} | {}{}",
+        gutter,
+        " ".repeat(column - 1),
+        "^".repeat(width)
+    );
+}
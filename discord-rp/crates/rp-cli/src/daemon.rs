@@ -0,0 +1,524 @@
+//! daemonコマンド（Discord との接続を保持し、Unix ソケットで操作を受け付ける）とデーモンへのリクエスト
+
+use crate::Cli;
+use crate::output::{self, Report};
+use anyhow::Result;
+use rp_core::{Request, Response};
+use serde::Serialize;
+use std::path::PathBuf;
+
+/// 起動中のデーモンにリクエストを送信する
+///
+/// `--no-daemon` を指定した場合やデーモンが起動していない場合は `None` を返し、
+/// 呼び出し側は直接 Discord に接続する
+pub fn request(cli: &Cli, request: &Request) -> Result<Option<Response>> {
+    if cli.no_daemon {
+        return Ok(None);
+    }
+    unix::request(request)
+}
+
+/// デーモンを起動したときの結果
+#[derive(Serialize)]
+struct StartReport {
+    /// 待ち受けるソケットのパス
+    socket: PathBuf,
+    /// デーモンのプロセスID
+    pid: u32,
+}
+
+impl Report for StartReport {
+    fn print_text(&self) {
+        println!("{}", t!("daemon.started", socket = self.socket.display()));
+    }
+}
+
+/// daemon --stop の結果
+#[derive(Serialize)]
+struct StopReport {
+    /// 起動中のデーモンを終了したか（起動していなかった場合は `false`）
+    stopped: bool,
+}
+
+impl Report for StopReport {
+    fn print_text(&self) {
+        if self.stopped {
+            println!("{}", t!("daemon.stopped"));
+        } else {
+            println!("{}", t!("daemon.not_running"));
+        }
+    }
+}
+
+/// daemonコマンドの実行
+pub fn run(cli: &Cli, stop: bool) -> Result<()> {
+    if !stop {
+        return unix::serve(cli);
+    }
+
+    let stopped = request(cli, &Request::Shutdown)?.is_some();
+    output::emit(cli, &StopReport { stopped })
+}
+
+#[cfg(unix)]
+mod unix {
+    use super::StartReport;
+    use crate::output::{self, OutputFormat};
+    use crate::{Cli, REFRESH_INTERVAL, load_config};
+    use anyhow::{Context, Result};
+    use rp_core::{
+        ActivityTemplate, ApplicationId, DaemonClient, DaemonStatus, Error, PresenceSession,
+        ReconnectStatus, Request, Response, RichPresenceClient, SessionStatus, daemon_socket_path,
+        decode_message, encode_message,
+    };
+    use std::collections::BTreeMap;
+    use std::collections::btree_map::Entry;
+    use std::fs::DirBuilder;
+    use std::io::{BufRead, BufReader, ErrorKind, Write};
+    use std::os::unix::fs::{DirBuilderExt, FileTypeExt, MetadataExt};
+    use std::os::unix::net::{UnixListener, UnixStream};
+    use std::path::Path;
+    use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+    use std::time::{Duration, Instant};
+    use tracing::{debug, info, warn};
+
+    /// リクエストを読み込むまで待つ時間
+    const READ_TIMEOUT: Duration = Duration::from_secs(10);
+
+    /// 起動中のデーモンにリクエストを送信（起動していない場合は `None`）
+    pub fn request(request: &Request) -> Result<Option<Response>> {
+        let client = match DaemonClient::connect(&daemon_socket_path()) {
+            Ok(client) => client,
+            Err(Error::DaemonNotRunning) => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        debug!(
+            "{}",
+            t!("daemon.sending", request = format!("{:?}", request))
+        );
+        Ok(Some(client.request(request)?))
+    }
+
+    /// メインループへのイベント
+    enum Event {
+        /// クライアントからのリクエスト（レスポンスは接続に書き込む）
+        Request(Request, UnixStream),
+        /// Ctrl+C
+        Interrupt,
+    }
+
+    /// ソケットで待ち受け、終了するまでリクエストを処理する
+    pub fn serve(cli: &Cli) -> Result<()> {
+        let config = load_config(cli)?;
+        let path = daemon_socket_path();
+        prepare_socket(&path)?;
+        let listener = bind(&path)?;
+
+        let (tx, rx) = mpsc::channel();
+        let interrupt = tx.clone();
+        ctrlc::set_handler(move || {
+            let _ = interrupt.send(Event::Interrupt);
+        })
+        .with_context(|| t!("cli.ctrlc_failed"))?;
+        std::thread::spawn(move || accept(listener, tx));
+
+        info!("{}", t!("daemon.started_log", path = path.display()));
+        output::emit(
+            cli,
+            &StartReport {
+                socket: path.clone(),
+                pid: std::process::id(),
+            },
+        )?;
+
+        let mut daemon = Daemon {
+            sessions: BTreeMap::new(),
+            auto_reconnect: config.auto_reconnect,
+            reconnect_interval: Duration::from_secs(config.reconnect_interval),
+        };
+        loop {
+            match rx.recv_timeout(daemon.timeout()) {
+                Ok(Event::Request(request, stream)) => {
+                    let shutdown = request == Request::Shutdown;
+                    respond(stream, &daemon.handle(request));
+                    if shutdown {
+                        info!("{}", t!("daemon.shutdown_requested"));
+                        break;
+                    }
+                }
+                Ok(Event::Interrupt) | Err(RecvTimeoutError::Disconnected) => break,
+                Err(RecvTimeoutError::Timeout) => {}
+            }
+            daemon.tick();
+        }
+
+        // セッションを破棄すると切断され、表示中のアクティビティは消える
+        drop(daemon);
+        let _ = std::fs::remove_file(&path);
+        if cli.output == OutputFormat::Text {
+            println!("{}", t!("daemon.stopped"));
+        }
+        Ok(())
+    }
+
+    /// ソケットを作成できる状態にする
+    ///
+    /// ソケットのディレクトリがない場合は、所有者のみが使用できる権限で作成する。
+    /// 前回異常終了したときのソケットが残っている場合は、現在のユーザーが所有するソケットで、
+    /// 接続できない（デーモンが終了している）ことを確認してから削除する
+    fn prepare_socket(path: &Path) -> Result<()> {
+        // SAFETY: getuid は常に成功し、副作用もない
+        let uid = unsafe { libc::getuid() };
+
+        if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            if !dir.exists() {
+                DirBuilder::new()
+                    .recursive(true)
+                    .mode(0o700)
+                    .create(dir)
+                    .with_context(|| t!("daemon.create_dir_failed", path = dir.display()))?;
+            }
+            // 他のユーザーのディレクトリでは、ソケットを差し替えられるおそれがある
+            let owner = std::fs::metadata(dir)
+                .with_context(|| t!("daemon.create_dir_failed", path = dir.display()))?
+                .uid();
+            if owner != uid && owner != 0 {
+                anyhow::bail!(t!("daemon.untrusted_dir", path = dir.display()));
+            }
+        }
+
+        let metadata = match std::fs::symlink_metadata(path) {
+            Ok(metadata) => metadata,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(()),
+            Err(e) => {
+                return Err(e)
+                    .with_context(|| t!("daemon.remove_socket_failed", path = path.display()));
+            }
+        };
+        if !metadata.file_type().is_socket() || metadata.uid() != uid {
+            anyhow::bail!(t!("daemon.not_own_socket", path = path.display()));
+        }
+        if !matches!(DaemonClient::connect(path), Err(Error::DaemonNotRunning)) {
+            anyhow::bail!(t!("daemon.already_running", path = path.display()));
+        }
+
+        // 前回異常終了したときのソケットが残っている
+        std::fs::remove_file(path)
+            .with_context(|| t!("daemon.remove_socket_failed", path = path.display()))
+    }
+
+    /// 所有者のみが接続できる権限でソケットを作成する
+    ///
+    /// 作成した時点から権限を制限するため、umask で設定する（他のスレッドを起動する前に呼ぶ）
+    fn bind(path: &Path) -> Result<UnixListener> {
+        // SAFETY: umask は常に成功する。元の値はすぐに戻す
+        let mask = unsafe { libc::umask(0o177) };
+        let listener = UnixListener::bind(path);
+        unsafe { libc::umask(mask) };
+        listener.with_context(|| t!("daemon.bind_failed", path = path.display()))
+    }
+
+    /// 接続を受け付け、リクエストをメインループに渡す
+    ///
+    /// リクエストを送らないクライアントが他の接続を妨げないように、接続ごとのスレッドで読み込む
+    fn accept(listener: UnixListener, tx: Sender<Event>) {
+        for stream in listener.incoming() {
+            let stream = match stream {
+                Ok(stream) => stream,
+                Err(e) => {
+                    warn!("{}", t!("daemon.accept_failed", error = e));
+                    continue;
+                }
+            };
+            let tx = tx.clone();
+            std::thread::spawn(move || match read_request(&stream) {
+                // メインループが終了している場合は応答しない
+                Ok(request) => {
+                    let _ = tx.send(Event::Request(request, stream));
+                }
+                Err(message) => respond(stream, &Response::Error { message }),
+            });
+        }
+    }
+
+    /// 接続からリクエストを1つ読み込む
+    fn read_request(stream: &UnixStream) -> std::result::Result<Request, String> {
+        let mut line = String::new();
+        stream
+            .set_read_timeout(Some(READ_TIMEOUT))
+            .and_then(|()| BufReader::new(stream).read_line(&mut line))
+            .map_err(|e| t!("daemon.read_failed", error = e))?;
+
+        decode_message(&line).map_err(|e| e.full_message())
+    }
+
+    /// レスポンスを書き込む（クライアントが既に切断している場合は無視）
+    fn respond(mut stream: UnixStream, response: &Response) {
+        let result = encode_message(response)
+            .map_err(anyhow::Error::from)
+            .and_then(|line| Ok(stream.write_all(line.as_bytes())?));
+        if let Err(e) = result {
+            debug!(
+                "{}",
+                t!("daemon.respond_failed", error = format!("{:#}", e))
+            );
+        }
+    }
+
+    /// デーモンが表示中のアクティビティ
+    struct Session {
+        /// Application IDのインデックス
+        index: u32,
+        /// アプリケーションの表示用の名前
+        name: Option<String>,
+        presence: PresenceSession,
+        /// 表示を終了する時刻
+        deadline: Option<Instant>,
+        /// 最後の更新または再接続のエラー（成功すると `None` に戻す）
+        ///
+        /// 接続中は更新のエラー、切断中は再接続の状態として返す
+        last_error: Option<String>,
+        /// 続けて失敗した再接続の回数
+        reconnect_attempts: u32,
+        /// 最後に再接続を試みた時刻
+        last_reconnect: Option<Instant>,
+    }
+
+    /// デーモンの状態（Application IDごとのセッション）
+    struct Daemon {
+        sessions: BTreeMap<ApplicationId, Session>,
+        auto_reconnect: bool,
+        reconnect_interval: Duration,
+    }
+
+    impl Daemon {
+        /// リクエストを処理
+        fn handle(&mut self, request: Request) -> Response {
+            let result = match request {
+                Request::Set {
+                    index,
+                    application_id,
+                    name,
+                    template,
+                    duration,
+                } => self.set(index, application_id, name, *template, duration),
+                Request::Clear { application_id } => {
+                    self.clear(application_id);
+                    Ok(())
+                }
+                Request::Status => return Response::Status(self.status()),
+                Request::Shutdown => Ok(()),
+            };
+
+            match result {
+                Ok(()) => Response::Ok,
+                Err(e) => Response::Error {
+                    message: e.full_message(),
+                },
+            }
+        }
+
+        /// アクティビティを表示（同じApplication IDのセッションはテンプレートを置き換える）
+        fn set(
+            &mut self,
+            index: u32,
+            application_id: ApplicationId,
+            name: Option<String>,
+            template: ActivityTemplate,
+            duration: Option<u64>,
+        ) -> rp_core::Result<()> {
+            template.to_ipc_payloads()?;
+
+            // 表示中のセッションは接続と再接続の状態、最後に送信した内容を引き継ぐ
+            let session = match self.sessions.entry(application_id) {
+                Entry::Occupied(entry) => {
+                    let session = entry.into_mut();
+                    session.index = index;
+                    session.name = name;
+                    // 更新間隔の制限中は送信できるようになってから表示時間を数える
+                    let wait = session.presence.client().next_update_in();
+                    session.deadline =
+                        duration.map(|secs| Instant::now() + wait + Duration::from_secs(secs));
+                    session.presence.replace_template(template);
+                    session
+                }
+                Entry::Vacant(entry) => {
+                    let mut client = RichPresenceClient::new(application_id)?;
+                    client.connect()?;
+                    entry.insert(Session {
+                        index,
+                        name,
+                        presence: PresenceSession::new(client, template),
+                        deadline: duration.map(|secs| Instant::now() + Duration::from_secs(secs)),
+                        last_error: None,
+                        reconnect_attempts: 0,
+                        last_reconnect: None,
+                    })
+                }
+            };
+            if session.presence.client().is_connected()
+                && let Err(e) = session.presence.refresh()
+            {
+                warn!("{}", t!("daemon.update_failed", error = e.full_message()));
+                session.last_error = Some(e.full_message());
+            }
+            info!("{}", t!("daemon.set", index = index));
+            Ok(())
+        }
+
+        /// アクティビティをクリアしてセッションを終了
+        fn clear(&mut self, application_id: ApplicationId) {
+            let Some(mut session) = self.sessions.remove(&application_id) else {
+                debug!(
+                    "{}",
+                    t!("daemon.no_session", application_id = application_id)
+                );
+                return;
+            };
+            if let Err(e) = session.presence.client_mut().clear_activity() {
+                warn!("{}", t!("daemon.clear_failed", error = e.full_message()));
+            }
+            info!("{}", t!("daemon.cleared", index = session.index));
+        }
+
+        /// デーモンの状態
+        fn status(&self) -> DaemonStatus {
+            let now = Instant::now();
+            DaemonStatus {
+                pid: std::process::id(),
+                sessions: self
+                    .sessions
+                    .iter()
+                    .map(|(&application_id, session)| {
+                        let client = session.presence.client();
+                        let connected = client.is_connected();
+                        SessionStatus {
+                            index: session.index,
+                            application_id,
+                            name: session.name.clone(),
+                            connected,
+                            user: client.user().cloned(),
+                            activity: session.presence.last_sent().cloned(),
+                            pending: session.presence.is_pending(),
+                            next_update_secs: client.next_update_in().as_secs_f64().ceil() as u64,
+                            remaining_secs: session
+                                .deadline
+                                .map(|deadline| deadline.saturating_duration_since(now).as_secs()),
+                            last_error: session.last_error.clone().filter(|_| connected),
+                            reconnect: self.reconnect_status(session).filter(|_| !connected),
+                        }
+                    })
+                    .collect(),
+            }
+        }
+
+        /// セッションの再接続の状態（失敗していない場合は `None`）
+        fn reconnect_status(&self, session: &Session) -> Option<ReconnectStatus> {
+            let last_error = session.last_error.clone()?;
+            let next_attempt = session.last_reconnect.map_or(Duration::ZERO, |last| {
+                self.reconnect_interval.saturating_sub(last.elapsed())
+            });
+            Some(ReconnectStatus {
+                attempts: session.reconnect_attempts,
+                next_attempt_secs: self.auto_reconnect.then_some(next_attempt.as_secs()),
+                last_error,
+            })
+        }
+
+        /// 次に [`tick`](Self::tick) を呼ぶまでの時間
+        fn timeout(&self) -> Duration {
+            let now = Instant::now();
+            self.sessions
+                .values()
+                .filter_map(|session| session.deadline)
+                .map(|deadline| deadline.saturating_duration_since(now))
+                .fold(REFRESH_INTERVAL, Duration::min)
+        }
+
+        /// 表示時間の過ぎたセッションを終了し、残りのテンプレートを再評価する
+        ///
+        /// 接続に関するエラーで更新に失敗した場合や切断している場合は、自動再接続が有効なら再接続の間隔ごとに再接続を試みる
+        fn tick(&mut self) {
+            let now = Instant::now();
+            let expired = self
+                .sessions
+                .iter()
+                .filter(|(_, session)| session.deadline.is_some_and(|deadline| deadline <= now))
+                .map(|(&application_id, _)| application_id)
+                .collect::<Vec<_>>();
+            for application_id in expired {
+                self.clear(application_id);
+            }
+
+            for session in self.sessions.values_mut() {
+                if session.presence.client().is_connected() {
+                    match session.presence.refresh() {
+                        Ok(_) => {
+                            session.last_error = None;
+                            continue;
+                        }
+                        Err(e) => {
+                            warn!(
+                                "{}",
+                                t!(
+                                    "daemon.update_failed_for",
+                                    index = session.index,
+                                    error = e.full_message()
+                                )
+                            );
+                            session.last_error = Some(e.full_message());
+                            // テンプレートの展開の失敗などは再接続しても解決しないため、接続を維持する
+                            if !e.is_connection_error() {
+                                continue;
+                            }
+                        }
+                    }
+                }
+
+                let due = session
+                    .last_reconnect
+                    .is_none_or(|last| last.elapsed() >= self.reconnect_interval);
+                if !self.auto_reconnect || !due {
+                    continue;
+                }
+                session.last_reconnect = Some(Instant::now());
+                match session.presence.reconnect() {
+                    Ok(()) => {
+                        info!("{}", t!("daemon.reconnected", index = session.index));
+                        session.reconnect_attempts = 0;
+                        session.last_error =
+                            session.presence.refresh().err().map(|e| e.full_message());
+                    }
+                    Err(e) => {
+                        warn!(
+                            "{}",
+                            t!(
+                                "daemon.reconnect_failed",
+                                seconds = self.reconnect_interval.as_secs(),
+                                error = e.full_message()
+                            )
+                        );
+                        session.reconnect_attempts += 1;
+                        session.last_error = Some(e.full_message());
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(not(unix))]
+mod unix {
+    use crate::Cli;
+    use anyhow::Result;
+    use rp_core::{Request, Response};
+
+    /// デーモンは Unix 系 OS でのみ使用できるため、常に直接接続する
+    pub fn request(_request: &Request) -> Result<Option<Response>> {
+        Ok(None)
+    }
+
+    pub fn serve(_cli: &Cli) -> Result<()> {
+        anyhow::bail!(t!("daemon.unsupported"))
+    }
+}
@@ -0,0 +1,80 @@
+//! 終了コード（エラーの種類ごとに固定。変更しない）
+//!
+//! [`rp_core::Error`] のバリアントとの対応は [`from_error`] で決める:
+//!
+//! | 終了コード | バリアント |
+//! |-----------|-----------|
+//! | [`CONFIG`] (3) | `ConfigLoadFailed`, `ConfigSaveFailed` |
+//! | [`INVALID_APPLICATION_ID`] (4) | `InvalidApplicationId` |
+//! | [`INVALID_INPUT`] (5) | `PresetNotFound`, `InvalidPreset`, `MissingVariable`, `InvalidTemplate`, `InvalidActivity`, `InvalidSchedule`, `InvalidPomodoro` |
+//! | [`DISCORD_NOT_RUNNING`] (6) | `DiscordNotRunning` |
+//! | [`CONNECTION`] (7) | `ConnectionFailed`, `Disconnected` |
+//! | [`RATE_LIMITED`] (8) | `RateLimited` |
+//! | [`ACTIVITY_UPDATE`] (9) | `ActivityUpdateFailed` |
+//! | [`DAEMON`] (10) | `DaemonNotRunning`, `DaemonConnectionFailed`, `InvalidMessage`, `ProtocolMismatch`, `DaemonFailed` |
+//! | [`FAILURE`] (1) | 上記以外、および [`rp_core::Error`] 以外のエラー |
+
+use rp_core::Error;
+
+/// その他のエラー（`config check` で問題が見つかった場合など）
+pub const FAILURE: u8 = 1;
+
+/// コマンドライン引数の誤り（clap が使用する終了コードと同じ）
+pub const USAGE: u8 = 2;
+
+/// 設定ファイルの読み込み・保存に失敗
+pub const CONFIG: u8 = 3;
+
+/// Application IDが無効、または登録されていない
+pub const INVALID_APPLICATION_ID: u8 = 4;
+
+/// 表示内容の指定の誤り
+///
+/// プリセット、テンプレート、アクティビティ、スケジュール、ポモドーロタイマーの設定や引数が対象
+/// （プリセットが見つからない、変数の値がない、文字数が Discord の制限を超える、時刻の形式が誤っているなど）
+pub const INVALID_INPUT: u8 = 5;
+
+/// Discord が起動していない
+pub const DISCORD_NOT_RUNNING: u8 = 6;
+
+/// Discord への接続に失敗、または接続が切断された
+pub const CONNECTION: u8 = 7;
+
+/// 更新頻度が高すぎる
+pub const RATE_LIMITED: u8 = 8;
+
+/// アクティビティの更新に失敗
+pub const ACTIVITY_UPDATE: u8 = 9;
+
+/// デーモンが起動していない、デーモンとの通信に失敗、またはデーモンでの処理に失敗
+pub const DAEMON: u8 = 10;
+
+/// エラーに対応する終了コード
+///
+/// 原因をたどって最初に見つかった [`rp_core::Error`] のバリアントから決める。見つからない場合は [`FAILURE`]
+pub fn from_error(error: &anyhow::Error) -> u8 {
+    let Some(e) = error.chain().find_map(|e| e.downcast_ref::<Error>()) else {
+        return FAILURE;
+    };
+    match e {
+        Error::ConfigLoadFailed { .. } | Error::ConfigSaveFailed { .. } => CONFIG,
+        Error::InvalidApplicationId(_) => INVALID_APPLICATION_ID,
+        Error::PresetNotFound(_)
+        | Error::InvalidPreset(_)
+        | Error::MissingVariable(_)
+        | Error::InvalidTemplate(_)
+        | Error::InvalidActivity(_)
+        | Error::InvalidSchedule(_)
+        | Error::InvalidPomodoro(_) => INVALID_INPUT,
+        Error::DiscordNotRunning => DISCORD_NOT_RUNNING,
+        Error::ConnectionFailed { .. } | Error::Disconnected => CONNECTION,
+        Error::RateLimited => RATE_LIMITED,
+        Error::ActivityUpdateFailed { .. } => ACTIVITY_UPDATE,
+        Error::DaemonNotRunning
+        | Error::DaemonConnectionFailed { .. }
+        | Error::InvalidMessage { .. }
+        | Error::ProtocolMismatch { .. }
+        | Error::DaemonFailed(_) => DAEMON,
+        _ => FAILURE,
+    }
+}
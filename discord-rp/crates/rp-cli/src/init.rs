@@ -0,0 +1,202 @@
+//! initコマンド（設定ファイルの生成と対話形式のセットアップ）
+
+use crate::output::{self, OutputFormat, Report};
+use crate::{Cli, config_path};
+use anyhow::{Context, Result};
+use rp_core::{Activity, Application, ApplicationId, Config, Preset, RichPresenceClient};
+use serde::Serialize;
+use std::io::{BufRead, Write};
+use std::path::{Path, PathBuf};
+
+/// initコマンドの結果
+#[derive(Serialize)]
+struct InitReport {
+    /// 作成した設定ファイル
+    path: PathBuf,
+    /// 上書きする前に作成したバックアップ
+    #[serde(skip_serializing_if = "Option::is_none")]
+    backup: Option<PathBuf>,
+    /// 対話形式で設定したか
+    #[serde(skip)]
+    wizard: bool,
+}
+
+impl Report for InitReport {
+    fn print_text(&self) {
+        if let Some(ref backup) = self.backup {
+            println!("{}", t!("init.backup", path = backup.display()));
+        }
+        println!("{}", t!("init.created", path = self.path.display()));
+        if !self.wizard {
+            println!();
+            println!("{}", t!("init.env_hint"));
+            println!("  DISCORD_APPLICATION_ID_1=<Application ID>");
+            println!("  DISCORD_APPLICATION_ID_2=<Application ID>");
+            println!("  ...");
+            println!();
+            println!("{}", t!("init.wizard_hint"));
+        }
+    }
+}
+
+/// initコマンドの実行
+///
+/// 既存のファイルは `force` を指定した場合のみ、バックアップを作成してから上書きする
+pub fn run(cli: &Cli, path: Option<&Path>, force: bool, wizard: bool) -> Result<()> {
+    if wizard && cli.output == OutputFormat::Json {
+        anyhow::bail!(t!("init.wizard_json"));
+    }
+
+    let path = config_path(path)?;
+
+    if path.exists() && !force {
+        anyhow::bail!(t!("init.exists", path = path.display()));
+    }
+
+    let config = if wizard {
+        run_wizard(&mut std::io::stdin().lock())?
+    } else {
+        Config::new()
+    };
+
+    let backup = if path.exists() {
+        Some(Config::backup(&path)?)
+    } else {
+        None
+    };
+    config.save(&path)?;
+
+    output::emit(
+        cli,
+        &InitReport {
+            path,
+            backup,
+            wizard,
+        },
+    )
+}
+
+/// 対話形式でApplication IDと最初のプリセットを設定
+fn run_wizard(input: &mut impl BufRead) -> Result<Config> {
+    let mut config = Config::new();
+
+    println!("{}", t!("init.wizard.intro"));
+    println!("{}", t!("init.wizard.where_to_find"));
+    println!();
+
+    let mut index = 1;
+    loop {
+        let value = prompt(
+            input,
+            &t!("init.wizard.application_id", index = index),
+            None,
+        )?;
+        if value.is_empty() {
+            if config.applications.is_empty() {
+                println!("{}", t!("init.wizard.at_least_one"));
+                continue;
+            }
+            break;
+        }
+
+        let id = match ApplicationId::parse(&value) {
+            Ok(id) => id,
+            Err(e) => {
+                println!("{}", e);
+                continue;
+            }
+        };
+
+        let name = prompt(input, &t!("init.wizard.name", index = index), None)?;
+
+        // 接続できなくても（Discord が起動していない場合など）確認の上で登録できる
+        let connected = test_connection(id);
+        if !connected && !confirm(input, &t!("init.wizard.register_anyway"), true)? {
+            continue;
+        }
+
+        let app = Application::new(id);
+        config.add_application(index, if name.is_empty() { app } else { app.name(name) });
+        index += 1;
+        println!();
+    }
+
+    println!();
+    println!("{}", t!("init.wizard.preset_intro"));
+    let name = prompt(input, &t!("init.wizard.preset_name"), Some("work"))?;
+    let default_details = t!("init.wizard.default_details");
+    let details = prompt(input, &t!("init.wizard.details"), Some(&default_details))?;
+    let state = prompt(input, &t!("init.wizard.state"), None)?;
+    let elapsed = confirm(input, &t!("init.wizard.elapsed"), true)?;
+
+    let mut activity = Activity::new().details(details);
+    if !state.is_empty() {
+        activity = activity.state(state);
+    }
+    config.add_preset(&name, Preset::new(activity).app(1).elapsed(elapsed));
+
+    println!();
+    println!("{}", t!("init.wizard.apply_hint", name = name));
+    Ok(config)
+}
+
+/// Discord への接続を試す（成功した場合は `true`）
+fn test_connection(id: ApplicationId) -> bool {
+    print!("{} ", t!("init.wizard.testing"));
+    let _ = std::io::stdout().flush();
+
+    let result = RichPresenceClient::new(id).and_then(|mut client| {
+        client.connect()?;
+        client.disconnect()
+    });
+    match result {
+        Ok(()) => {
+            println!("✓ {}", t!("init.wizard.connected"));
+            true
+        }
+        Err(e) => {
+            println!(
+                "✗ {}",
+                t!("init.wizard.connect_failed", error = e.full_message())
+            );
+            false
+        }
+    }
+}
+
+/// 1行入力を受け取る（空欄の場合はデフォルト値）
+fn prompt(input: &mut impl BufRead, label: &str, default: Option<&str>) -> Result<String> {
+    match default {
+        Some(default) => print!("{} [{}]: ", label, default),
+        None => print!("{}: ", label),
+    }
+    std::io::stdout().flush()?;
+
+    let mut line = String::new();
+    let read = input
+        .read_line(&mut line)
+        .with_context(|| t!("init.wizard.read_failed"))?;
+    if read == 0 {
+        anyhow::bail!(t!("init.wizard.eof"));
+    }
+
+    let line = line.trim();
+    Ok(match default {
+        Some(default) if line.is_empty() => default.to_string(),
+        _ => line.to_string(),
+    })
+}
+
+/// はい/いいえの入力を受け取る
+fn confirm(input: &mut impl BufRead, label: &str, default: bool) -> Result<bool> {
+    let hint = if default { "Y/n" } else { "y/N" };
+    loop {
+        let answer = prompt(input, &format!("{} [{}]", label, hint), None)?;
+        match answer.to_lowercase().as_str() {
+            "" => return Ok(default),
+            "y" | "yes" => return Ok(true),
+            "n" | "no" => return Ok(false),
+            _ => println!("{}", t!("init.wizard.yes_or_no")),
+        }
+    }
+}
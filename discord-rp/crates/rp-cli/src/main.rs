@@ -1,296 +1,860 @@
 //! Discord Rich Presence CLI ツール
 
+/// 現在の言語のメッセージを取得（`t!("key")`, `t!("key", name = value)`）
+macro_rules! t {
+    ($key:expr) => {
+        $crate::messages::CATALOG.message($key)
+    };
+    ($key:expr, $($name:ident = $value:expr),+ $(,)?) => {
+        $crate::messages::CATALOG.format(
+            $key,
+            &[$((stringify!($name), &$value as &dyn std::fmt::Display)),+],
+        )
+    };
+}
+
+mod config;
+mod daemon;
+mod exit_code;
+mod init;
+mod messages;
+mod output;
+mod pomodoro;
+mod presenter;
+mod preset;
+mod reload;
+mod run;
+mod schedule;
+mod session;
+mod status;
+
 use anyhow::{Context, Result};
-use clap::{Parser, Subcommand};
-use rp_core::{Activity, Config, RichPresenceClient};
-use std::path::PathBuf;
-use tracing::{error, info};
+use clap::error::ErrorKind;
+use clap::{Args, CommandFactory, FromArgMatches, Parser, Subcommand};
+use chrono::{DateTime, Local};
+use config::ConfigCommand;
+use output::{OutputFormat, Report};
+use pomodoro::PomodoroArgs;
+use preset::PresetCommand;
+use rp_core::{
+    Activity, ActivityAssets, ApplicationId, BuiltinVariables, Config, DiscordUser, Locale, Preset,
+    Request, RichPresenceClient, Rotation, RotationTimer, Variables,
+};
+use schedule::ScheduleCommand;
+use serde::Serialize;
+use session::ActivitySource;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::time::Duration;
+use tracing::error;
 use tracing_subscriber::EnvFilter;
 
+/// テンプレートを再評価する間隔
+const REFRESH_INTERVAL: Duration = Duration::from_secs(5);
+
 #[derive(Parser)]
 #[command(name = "discord-rp")]
 #[command(about = "Discord Rich Presence をカスタマイズするCLIツール")]
 #[command(version)]
 struct Cli {
     /// 使用するApplication IDのインデックス（1始まり、デフォルト: 1）
-    #[arg(short, long, default_value = "1")]
-    index: u32,
+    #[arg(short, long)]
+    index: Option<u32>,
 
     /// 設定ファイルのパス
     #[arg(short, long)]
     config: Option<PathBuf>,
 
+    /// プロジェクトの設定ファイル（.discord-rp.toml）を読み込まない
+    #[arg(long)]
+    no_project: bool,
+
+    /// デーモンが起動していても使用せず、直接 Discord に接続する
+    #[arg(long)]
+    no_daemon: bool,
+
     /// ログレベル（trace, debug, info, warn, error）
     #[arg(long, default_value = "info")]
     log_level: String,
 
+    /// 出力形式（json の場合は成功・失敗にかかわらず1つの JSON オブジェクトを出力）
+    #[arg(long, value_enum, default_value_t, global = true)]
+    output: OutputFormat,
+
+    /// メッセージの言語（ja, en。指定しない場合は LC_ALL / LC_MESSAGES / LANG から判定）
+    #[arg(long, global = true, value_name = "LANG", display_order = 100)]
+    lang: Option<Locale>,
+
     #[command(subcommand)]
     command: Commands,
+
+    /// 読み込んだ .env ファイルのパス（優先度の高い順）
+    #[arg(skip)]
+    env_files: Vec<PathBuf>,
+
+    /// 実行したコマンドの名前（例: `preset apply`）
+    #[arg(skip)]
+    command_name: String,
+}
+
+impl Cli {
+    /// 使用するApplication IDのインデックス（未指定の場合は1）
+    fn index(&self) -> u32 {
+        self.index.unwrap_or(1)
+    }
 }
 
 #[derive(Subcommand)]
 enum Commands {
     /// Rich Presenceを設定して表示
     Set {
-        /// 詳細テキスト（1行目）
-        #[arg(short, long)]
-        details: Option<String>,
+        #[command(flatten)]
+        activity: ActivityArgs,
 
-        /// 状態テキスト（2行目）
-        #[arg(short, long)]
-        state: Option<String>,
+        /// 表示を維持する秒数（指定しない場合は Ctrl+C まで維持）
+        #[arg(short = 'D', long)]
+        duration: Option<u64>,
 
-        /// 経過時間を表示
+        /// Discord に接続せず、送信する SET_ACTIVITY のフレーム（JSON）を表示
         #[arg(long)]
-        elapsed: bool,
+        dry_run: bool,
+    },
 
-        /// 大きい画像のキー
-        #[arg(long)]
-        large_image: Option<String>,
+    /// 送信する SET_ACTIVITY のフレーム（JSON）を表示（Discord には接続しない）
+    ///
+    /// set / preset apply と同じく設定を重ね、テンプレートを展開し、Discord の制限を検査する
+    Payload {
+        /// プリセット名（指定した場合はプリセットを適用した内容）
+        #[arg(short, long, conflicts_with = "ActivityArgs")]
+        preset: Option<String>,
 
-        /// 大きい画像のツールチップ
-        #[arg(long)]
-        large_text: Option<String>,
+        /// プリセットの変数の値（NAME=VALUE、複数指定可）
+        #[arg(long = "var", value_name = "NAME=VALUE", requires = "preset", value_parser = preset::parse_var_value)]
+        vars: Vec<(String, String)>,
 
-        /// 小さい画像のキー
-        #[arg(long)]
-        small_image: Option<String>,
+        #[command(flatten)]
+        activity: ActivityArgs,
+    },
 
-        /// 小さい画像のツールチップ
-        #[arg(long)]
-        small_text: Option<String>,
+    /// 経過時間付きでアクティビティを表示しながらコマンドを実行し、終了したらクリアする
+    ///
+    /// 受け取ったシグナルはコマンドに転送し、コマンドの終了コードで終了する
+    Run {
+        #[command(flatten)]
+        activity: ActivityArgs,
 
-        /// 表示を維持する秒数（指定しない場合は Ctrl+C まで維持）
-        #[arg(short = 'D', long)]
-        duration: Option<u64>,
+        /// 成功した場合に終了後に表示する状態テキスト（{elapsed} と {status} を使用可）
+        #[arg(long, value_name = "TEXT")]
+        success_state: Option<String>,
+
+        /// 失敗した場合に終了後に表示する状態テキスト（{elapsed} と {status} を使用可）
+        #[arg(long, value_name = "TEXT")]
+        failure_state: Option<String>,
+
+        /// 終了後の状態テキストを表示する秒数
+        #[arg(long, value_name = "SECS", default_value_t = 10)]
+        final_duration: u64,
+
+        /// 実行するコマンドと引数
+        #[arg(last = true, required = true, value_name = "COMMAND")]
+        command: Vec<String>,
     },
 
     /// Rich Presenceをクリア
     Clear,
 
+    /// デーモンが Discord に表示中の内容と接続の状態を表示
+    Status {
+        /// JSON 形式で出力（--output json と同じ）
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Discord との接続を保持し、set / clear / status / preset apply の操作を受け付ける
+    Daemon {
+        /// 起動中のデーモンを終了する
+        #[arg(long)]
+        stop: bool,
+    },
+
     /// 接続テスト
     Test,
 
+    /// テンプレートで使用できる組み込み変数と現在の値を表示
+    Vars,
+
     /// 登録済みApplication ID一覧を表示
-    List,
+    List {
+        /// Application IDを伏せ字にせず表示
+        #[arg(long)]
+        reveal: bool,
+    },
+
+    /// プリセットを管理
+    #[command(subcommand)]
+    Preset(PresetCommand),
 
-    /// 設定ファイルを生成
+    /// 設定ファイルを検査・表示・変換
+    #[command(subcommand)]
+    Config(ConfigCommand),
+
+    /// 時刻に応じてプリセットを自動で切り替える
+    #[command(subcommand)]
+    Schedule(ScheduleCommand),
+
+    /// ポモドーロタイマー: 作業と休憩を繰り返し、残り時間と作業の回数を表示（Ctrl+C で終了）
+    Pomodoro(PomodoroArgs),
+
+    /// 設定ファイルを生成（既存のファイルは --force を指定した場合のみ上書き）
     Init {
         /// 出力先パス（指定しない場合はデフォルトパス）
         #[arg(short, long)]
-        output: Option<PathBuf>,
+        path: Option<PathBuf>,
+
+        /// 既存のファイルをバックアップしてから上書きする
+        #[arg(short, long)]
+        force: bool,
+
+        /// Application IDと最初のプリセットを対話形式で設定
+        #[arg(short, long)]
+        wizard: bool,
     },
 }
 
-fn main() -> Result<()> {
-    // .env ファイルを読み込む（存在しなくてもエラーにしない）
-    let _ = dotenvy::dotenv();
+/// アクティビティを指定するフラグ
+#[derive(Args)]
+struct ActivityArgs {
+    /// 詳細テキスト（1行目）
+    #[arg(short, long)]
+    details: Option<String>,
+
+    /// 状態テキスト（2行目）
+    #[arg(short, long)]
+    state: Option<String>,
+
+    /// 経過時間を表示
+    #[arg(long)]
+    elapsed: bool,
+
+    /// 大きい画像のキー
+    #[arg(long)]
+    large_image: Option<String>,
+
+    /// 大きい画像のツールチップ
+    #[arg(long)]
+    large_text: Option<String>,
+
+    /// 小さい画像のキー
+    #[arg(long)]
+    small_image: Option<String>,
+
+    /// 小さい画像のツールチップ
+    #[arg(long)]
+    small_text: Option<String>,
+
+    /// 一定間隔で切り替えて表示する詳細テキスト（複数指定可、--details の後に順に表示）
+    #[arg(long, value_name = "TEXT")]
+    rotate: Vec<String>,
+
+    /// 詳細テキストを切り替える間隔（秒、15秒未満の場合は15秒）
+    #[arg(long, value_name = "SECS", default_value_t = 60, requires = "rotate")]
+    rotate_interval: u64,
+
+    /// 切り替えたときの経過時間の扱い（keep: 引き継ぐ, reset: 切り替えた時刻から数え直す）
+    #[arg(long, value_name = "MODE", default_value_t = RotationTimer::Keep, requires = "rotate")]
+    rotate_timer: RotationTimer,
+}
+
+impl ActivityArgs {
+    /// フラグからプリセットを作成
+    fn to_preset(&self) -> Preset {
+        let mut preset = Preset::new(self.build_activity());
+        if self.elapsed {
+            preset = preset.elapsed(true);
+        }
+        if let Some(rotation) = self.build_rotation() {
+            preset = preset.rotation(rotation);
+        }
+        preset
+    }
+
+    /// --rotate を指定した場合は、--details と --rotate の詳細テキストを順に切り替えるローテーションを作成
+    fn build_rotation(&self) -> Option<Rotation> {
+        if self.rotate.is_empty() {
+            return None;
+        }
+        let activities = self
+            .details
+            .iter()
+            .chain(&self.rotate)
+            .map(|details| Activity::new().details(details))
+            .collect();
+        Some(
+            Rotation::new(activities)
+                .interval(self.rotate_interval)
+                .timer(self.rotate_timer),
+        )
+    }
+
+    /// タイムスタンプ以外の項目からアクティビティを作成
+    fn build_activity(&self) -> Activity {
+        let mut activity = Activity::new();
+
+        if let Some(ref d) = self.details {
+            activity = activity.details(d);
+        }
+        if let Some(ref s) = self.state {
+            activity = activity.state(s);
+        }
+        // 継承先で一部の項目だけを上書きできるよう、画像とツールチップは個別に設定する
+        if self.large_image.is_some()
+            || self.large_text.is_some()
+            || self.small_image.is_some()
+            || self.small_text.is_some()
+        {
+            activity = activity.assets(ActivityAssets {
+                large_image: self.large_image.clone(),
+                large_text: self.large_text.clone(),
+                small_image: self.small_image.clone(),
+                small_text: self.small_text.clone(),
+            });
+        }
+
+        activity
+    }
+}
 
-    let cli = Cli::parse();
+fn main() -> ExitCode {
+    Locale::set_current(messages::detect_locale());
+    let matches = match messages::localize_command(Cli::command()).try_get_matches() {
+        Ok(matches) => matches,
+        Err(e) => return usage_error(e),
+    };
+    let mut cli = match Cli::from_arg_matches(&matches) {
+        Ok(cli) => cli,
+        Err(e) => return usage_error(e),
+    };
+    cli.command_name = output::command_name(&matches);
+    cli.env_files = load_env_files(cli.config.as_deref());
+    if let Commands::Status { json: true } = cli.command {
+        cli.output = OutputFormat::Json;
+    }
 
-    // ロギングの初期化
+    // ロギングの初期化（JSON の出力に混ざらないよう、JSON の場合は標準エラー出力に書き込む）
     let filter =
         EnvFilter::try_new(&cli.log_level).unwrap_or_else(|_| EnvFilter::new("info"));
-    tracing_subscriber::fmt().with_env_filter(filter).init();
+    let subscriber = tracing_subscriber::fmt().with_env_filter(filter);
+    match cli.output {
+        OutputFormat::Text => subscriber.init(),
+        OutputFormat::Json => subscriber.with_writer(std::io::stderr).init(),
+    }
+
+    match run(&cli) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            match cli.output {
+                OutputFormat::Text => eprintln!("Error: {:?}", e),
+                OutputFormat::Json => output::emit_error(&cli, &e),
+            }
+            ExitCode::from(exit_code::from_error(&e))
+        }
+    }
+}
 
-    // 環境変数から設定を読み込む
-    let config = Config::from_env();
+/// コマンドライン引数の誤りを出力し、終了コードを返す
+///
+/// `--output json` の場合は JSON で出力する。ヘルプとバージョンはそのまま表示する
+fn usage_error(e: clap::Error) -> ExitCode {
+    let display = matches!(e.kind(), ErrorKind::DisplayHelp | ErrorKind::DisplayVersion);
+    if display || !output::json_requested() {
+        e.exit();
+    }
+    output::emit_usage_error(&e);
+    ExitCode::from(exit_code::USAGE)
+}
 
+/// コマンドの実行
+fn run(cli: &Cli) -> Result<()> {
     match cli.command {
         Commands::Set {
-            ref details,
-            ref state,
-            elapsed,
-            ref large_image,
-            ref large_text,
-            ref small_image,
-            ref small_text,
+            ref activity,
             duration,
+            dry_run,
         } => {
-            let app_id = get_app_id(&config, cli.index)?;
-            cmd_set(
-                &app_id,
-                cli.index,
-                details.clone(),
-                state.clone(),
-                elapsed,
-                large_image.clone(),
-                large_text.clone(),
-                small_image.clone(),
-                small_text.clone(),
-                duration,
-            )
+            let config = load_config(cli)?;
+            if dry_run {
+                return session::payload(cli, &config, ActivitySource::Flags(activity));
+            }
+            session::run(cli, config, ActivitySource::Flags(activity), duration)
+        }
+        Commands::Payload {
+            ref preset,
+            ref vars,
+            ref activity,
+        } => {
+            let config = load_config(cli)?;
+            let source = match preset {
+                Some(name) => ActivitySource::Preset {
+                    name,
+                    values: vars.iter().cloned().collect(),
+                },
+                None => ActivitySource::Flags(activity),
+            };
+            session::payload(cli, &config, source)
+        }
+        Commands::Run {
+            ref activity,
+            ref success_state,
+            ref failure_state,
+            final_duration,
+            ref command,
+        } => {
+            let config = load_config(cli)?;
+            let final_state = run::FinalState {
+                success: success_state.as_deref(),
+                failure: failure_state.as_deref(),
+                duration: final_duration,
+            };
+            run::run(cli, &config, activity, &final_state, command)
         }
         Commands::Clear => {
-            let app_id = get_app_id(&config, cli.index)?;
-            cmd_clear(&app_id)
+            let config = load_config(cli)?;
+            let app_id = get_app_id(&config, cli.index())?;
+            cmd_clear(cli, app_id)
         }
+        Commands::Status { .. } => status::run(cli),
+        Commands::Daemon { stop } => daemon::run(cli, stop),
         Commands::Test => {
-            let app_id = get_app_id(&config, cli.index)?;
-            cmd_test(&app_id, cli.index)
+            let config = load_config(cli)?;
+            let app_id = get_app_id(&config, cli.index())?;
+            cmd_test(cli, app_id)
+        }
+        Commands::List { reveal } => {
+            let config = load_config(cli)?;
+            cmd_list(cli, &config, reveal)
         }
-        Commands::List => cmd_list(&config),
-        Commands::Init { output } => cmd_init(output),
+        Commands::Vars => cmd_vars(cli),
+        Commands::Preset(ref command) => preset::run(cli, command),
+        Commands::Config(ref command) => config::run(cli, command),
+        Commands::Schedule(ref command) => schedule::run(cli, command),
+        Commands::Pomodoro(ref args) => pomodoro::run(cli, args),
+        Commands::Init {
+            ref path,
+            force,
+            wizard,
+        } => init::run(cli, path.as_deref(), force, wizard),
     }
 }
 
-/// Application IDを取得
-fn get_app_id(config: &Config, index: u32) -> Result<String> {
-    config
-        .get_application_id(index)
-        .map(|s| s.to_string())
-        .map_err(|e| anyhow::anyhow!("{}", e))
+/// 設定ファイルのパスを決定
+fn config_path(path: Option<&Path>) -> Result<PathBuf> {
+    path.map(Path::to_path_buf)
+        .or_else(Config::default_path)
+        .with_context(|| t!("cli.config_path_unknown"))
 }
 
-/// setコマンドの実行
-fn cmd_set(
-    app_id: &str,
-    index: u32,
-    details: Option<String>,
-    state: Option<String>,
-    elapsed: bool,
-    large_image: Option<String>,
-    large_text: Option<String>,
-    small_image: Option<String>,
-    small_text: Option<String>,
-    duration: Option<u64>,
-) -> Result<()> {
-    let mut client =
-        RichPresenceClient::new(app_id).context("クライアントの作成に失敗しました")?;
+/// 設定ファイルのみを読み込む
+///
+/// パスが指定されていない場合、デフォルトパスにファイルがなければ空の設定を返す
+fn load_file_config(path: Option<&Path>) -> Result<Config> {
+    load_file_config_with_vars(path, |key| std::env::var(key).ok())
+}
+
+/// 指定した変数で値の参照を展開して、設定ファイルのみを読み込む
+fn load_file_config_with_vars(
+    path: Option<&Path>,
+    var: impl Fn(&str) -> Option<String>,
+) -> Result<Config> {
+    let explicit = path.is_some();
+    let path = config_path(path)?;
+
+    if !explicit && !path.exists() {
+        return Ok(Config::new());
+    }
 
-    client.connect().context("Discord への接続に失敗しました")?;
+    Ok(Config::load_with_vars(&path, var)?)
+}
 
-    let mut activity = Activity::new();
+/// 古い形式の設定ファイルを上書きする前に作成したバックアップ
+#[derive(Serialize)]
+struct MigrationBackup {
+    /// 元の形式のバージョン
+    from_version: u32,
+    /// バックアップのパス
+    path: PathBuf,
+}
 
-    if let Some(d) = details {
-        activity = activity.details(d);
+impl MigrationBackup {
+    /// バックアップを作成したことを表示
+    fn print_text(&self) {
+        println!(
+            "{}",
+            t!(
+                "cli.migration_backup",
+                version = self.from_version,
+                path = self.path.display()
+            )
+        );
     }
-    if let Some(s) = state {
-        activity = activity.state(s);
+}
+
+/// 設定ファイルに保存
+///
+/// 古い形式から移行して読み込んだ設定の場合は、上書きする前にバックアップを作成して返す
+fn save_file_config(config: &Config, path: &Path) -> Result<Option<MigrationBackup>> {
+    let backup = match config.migrated_from() {
+        Some(from_version) if path.exists() => Some(MigrationBackup {
+            from_version,
+            path: Config::backup(path)?,
+        }),
+        _ => None,
+    };
+
+    config.save(path)?;
+    Ok(backup)
+}
+
+/// .env ファイルを読み込む（存在しなくてもエラーにしない）
+///
+/// カレントディレクトリ（とその親）の .env を優先し、設定ファイルのディレクトリの .env で補う。
+/// 既存の環境変数は上書きしない
+fn load_env_files(config: Option<&Path>) -> Vec<PathBuf> {
+    let mut env_files = Vec::new();
+    env_files.extend(dotenvy::dotenv().ok());
+
+    let config_dir_env = config_path(config)
+        .ok()
+        .and_then(|path| Some(path.parent()?.join(".env")))
+        .filter(|path| path.is_file());
+    if let Some(path) = config_dir_env
+        && !env_files.iter().any(|loaded| same_file(loaded, &path))
+        && dotenvy::from_path(&path).is_ok()
+    {
+        env_files.push(path);
     }
-    if elapsed {
-        activity = activity.start_timestamp();
+
+    env_files
+}
+
+/// 同じファイルを指すパスか
+fn same_file(a: &Path, b: &Path) -> bool {
+    match (a.canonicalize(), b.canonicalize()) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => a == b,
     }
-    if let Some(key) = large_image {
-        activity = activity.large_image(key, large_text);
+}
+
+/// プロジェクトの設定ファイルのパス（--no-project の場合や見つからない場合は `None`）
+///
+/// カレントディレクトリから親へ順に探す。ユーザーの設定ファイルと同じファイルは除く
+fn project_config_path(cli: &Cli) -> Option<PathBuf> {
+    if cli.no_project {
+        return None;
     }
-    if let Some(key) = small_image {
-        activity = activity.small_image(key, small_text);
+    let cwd = std::env::current_dir().ok()?;
+    let project = Config::find_project_file(&cwd)?;
+
+    match config_path(cli.config.as_deref()) {
+        Ok(user) if same_file(&user, &project) => None,
+        _ => Some(project),
     }
+}
 
-    client
-        .update_activity(&activity)
-        .context("アクティビティの更新に失敗しました")?;
-
-    info!("Rich Presence を設定しました");
-    println!(
-        "Rich Presence を設定しました（インデックス: {}）。Ctrl+C で終了します。",
-        index
-    );
-
-    // 指定時間または Ctrl+C まで待機
-    if let Some(secs) = duration {
-        std::thread::sleep(std::time::Duration::from_secs(secs));
-    } else {
-        // Ctrl+C を待機
-        let (tx, rx) = std::sync::mpsc::channel();
-        ctrlc::set_handler(move || {
-            let _ = tx.send(());
-        })
-        .context("Ctrl+C ハンドラの設定に失敗しました")?;
-        rx.recv().ok();
+/// 設定ファイル、プロジェクトの設定ファイル、共有プリセット、環境変数から設定を読み込む
+fn load_config(cli: &Cli) -> Result<Config> {
+    load_config_with_vars(cli, |key| std::env::var(key).ok())
+}
+
+/// 設定ファイル、プロジェクトの設定ファイル、共有プリセット、指定した変数から設定を読み込む
+fn load_config_with_vars(cli: &Cli, var: impl Fn(&str) -> Option<String>) -> Result<Config> {
+    let path = cli.config.as_deref();
+    let mut config = load_file_config_with_vars(path, &var)?;
+    if let Some(project) = project_config_path(cli) {
+        config.layer_file(&project)?;
     }
 
-    client.disconnect()?;
-    println!("終了しました。");
-    Ok(())
+    let base_dir = config_path(path)?
+        .parent()
+        .map(Path::to_path_buf)
+        .unwrap_or_default();
+    config.load_preset_dirs_with_vars(&base_dir, &var)?;
+
+    config.load_from_vars(var)?;
+    Ok(config)
+}
+
+/// Application IDを取得
+fn get_app_id(config: &Config, index: u32) -> Result<ApplicationId> {
+    Ok(config.get_application_id(index)?)
+}
+
+/// clearコマンドの結果
+#[derive(Serialize)]
+struct ClearReport {
+    /// Application IDのインデックス
+    index: u32,
+    /// クリアしたApplication ID
+    application_id: ApplicationId,
+    /// デーモンが表示していたアクティビティをクリアしたか
+    daemon: bool,
+}
+
+impl Report for ClearReport {
+    fn print_text(&self) {
+        println!("{}", t!("clear.done"));
+    }
 }
 
 /// clearコマンドの実行
-fn cmd_clear(app_id: &str) -> Result<()> {
-    let mut client =
-        RichPresenceClient::new(app_id).context("クライアントの作成に失敗しました")?;
+///
+/// デーモンが起動している場合はデーモンが表示中のアクティビティをクリアする
+fn cmd_clear(cli: &Cli, app_id: ApplicationId) -> Result<()> {
+    let mut report = ClearReport {
+        index: cli.index(),
+        application_id: app_id,
+        daemon: true,
+    };
+    let request = Request::Clear {
+        application_id: app_id,
+    };
+    if daemon::request(cli, &request)?.is_some() {
+        return output::emit(cli, &report);
+    }
+
+    let mut client = RichPresenceClient::new(app_id).with_context(|| t!("cli.client_failed"))?;
 
-    client.connect().context("Discord への接続に失敗しました")?;
+    client.connect().with_context(|| t!("cli.connect_failed"))?;
 
     client
         .clear_activity()
-        .context("アクティビティのクリアに失敗しました")?;
+        .with_context(|| t!("clear.failed"))?;
 
-    println!("Rich Presence をクリアしました。");
-    Ok(())
+    report.daemon = false;
+    output::emit(cli, &report)
 }
 
-/// testコマンドの実行
-fn cmd_test(app_id: &str, index: u32) -> Result<()> {
-    println!("Discord への接続をテストしています...");
-    println!("インデックス: {}", index);
-    println!("Application ID: {}", app_id);
-
-    let mut client =
-        RichPresenceClient::new(app_id).context("クライアントの作成に失敗しました")?;
-
-    match client.connect() {
-        Ok(()) => {
-            println!("✓ Discord に接続しました");
-            client.disconnect()?;
-            println!("✓ 接続テスト成功");
-            Ok(())
-        }
-        Err(e) => {
-            error!("接続テスト失敗: {}", e);
-            anyhow::bail!("接続テスト失敗: {}", e)
+/// testコマンドの結果
+#[derive(Serialize)]
+struct TestReport {
+    /// Application IDのインデックス
+    index: u32,
+    /// 接続したApplication ID
+    application_id: ApplicationId,
+    /// Application IDの作成日時（RFC 3339）
+    created_at: String,
+    /// 接続先の Discord にログインしているユーザー
+    #[serde(skip_serializing_if = "Option::is_none")]
+    user: Option<DiscordUser>,
+}
+
+impl Report for TestReport {
+    fn print_text(&self) {
+        match self.user {
+            Some(ref user) => println!("✓ {}", t!("test.connected_as", username = user.username)),
+            None => println!("✓ {}", t!("test.connected")),
         }
+        println!("✓ {}", t!("test.succeeded"));
     }
 }
 
-/// listコマンドの実行
-fn cmd_list(config: &Config) -> Result<()> {
-    let indices = config.registered_indices();
+/// testコマンドの実行
+fn cmd_test(cli: &Cli, app_id: ApplicationId) -> Result<()> {
+    let index = cli.index();
+    if cli.output == OutputFormat::Text {
+        println!("{}", t!("test.testing"));
+        println!("{}", t!("test.index", index = index));
+        println!("{}", t!("test.application_id", id = app_id));
+        println!(
+            "{}",
+            t!("test.created_at", created_at = format_created_at(app_id))
+        );
+    }
+
+    let mut client = RichPresenceClient::new(app_id).with_context(|| t!("cli.client_failed"))?;
+
+    if let Err(e) = client.connect() {
+        error!("{}", t!("test.failed_log", error = e.full_message()));
+        return Err(anyhow::Error::new(e).context(t!("test.failed")));
+    }
+    let user = client.user().cloned();
+    client.disconnect()?;
+
+    output::emit(
+        cli,
+        &TestReport {
+            index,
+            application_id: app_id,
+            created_at: DateTime::<Local>::from(app_id.created_at()).to_rfc3339(),
+            user,
+        },
+    )
+}
+
+/// listコマンドの結果
+#[derive(Serialize)]
+struct ListReport {
+    /// 登録済みのApplication ID（インデックス順）
+    applications: Vec<ApplicationReport>,
+}
+
+/// 登録済みのApplication ID
+#[derive(Serialize)]
+struct ApplicationReport {
+    /// インデックス
+    index: u32,
+    /// アプリケーションの表示用の名前
+    #[serde(skip_serializing_if = "Option::is_none")]
+    name: Option<String>,
+    /// Application ID（--reveal を指定しない場合は伏せ字）
+    application_id: String,
+    /// Application IDの作成日時（RFC 3339）
+    created_at: String,
+    #[serde(skip)]
+    id: ApplicationId,
+}
+
+impl Report for ListReport {
+    fn print_text(&self) {
+        if self.applications.is_empty() {
+            println!("{}", t!("list.empty"));
+            println!();
+            println!("{}", t!("list.env_hint"));
+            println!("  DISCORD_APPLICATION_ID_1=<Application ID>");
+            println!("  DISCORD_APPLICATION_ID_2=<Application ID>");
+            println!("  ...");
+            return;
+        }
 
-    if indices.is_empty() {
-        println!("登録されているApplication IDがありません。");
+        println!("{}", t!("list.header"));
         println!();
-        println!("環境変数で設定してください:");
-        println!("  DISCORD_APPLICATION_ID_1=<Application ID>");
-        println!("  DISCORD_APPLICATION_ID_2=<Application ID>");
-        println!("  ...");
-        return Ok(());
+        for app in &self.applications {
+            let name = app
+                .name
+                .as_ref()
+                .map(|name| format!("{} ", name))
+                .unwrap_or_default();
+            println!(
+                "  [{}] {}{}{}",
+                app.index,
+                name,
+                app.application_id,
+                t!("list.created_at", created_at = format_created_at(app.id))
+            );
+        }
+        println!();
+        println!("{}", t!("list.example"));
     }
+}
 
-    println!("登録済みApplication ID:");
-    println!();
-    for idx in indices {
-        if let Ok(app_id) = config.get_application_id(idx) {
+/// listコマンドの実行
+fn cmd_list(cli: &Cli, config: &Config, reveal: bool) -> Result<()> {
+    let applications = config
+        .registered_indices()
+        .into_iter()
+        .filter_map(|index| {
+            let app = config.get_application(index).ok()?;
             // Application IDの一部を隠す
-            let masked = if app_id.len() > 8 {
-                format!("{}...{}", &app_id[..4], &app_id[app_id.len() - 4..])
+            let application_id = if reveal {
+                app.id.to_string()
             } else {
-                app_id.to_string()
+                app.id.masked()
             };
-            println!("  [{}] {}", idx, masked);
+            Some(ApplicationReport {
+                index,
+                name: app.name.clone(),
+                application_id,
+                created_at: DateTime::<Local>::from(app.id.created_at()).to_rfc3339(),
+                id: app.id,
+            })
+        })
+        .collect();
+
+    output::emit(cli, &ListReport { applications })
+}
+
+/// varsコマンドの結果
+#[derive(Serialize)]
+struct VarsReport {
+    /// 組み込み変数
+    variables: Vec<VariableReport>,
+}
+
+/// 組み込み変数と現在の値
+#[derive(Serialize)]
+struct VariableReport {
+    /// 変数名
+    name: &'static str,
+    /// 現在の値（未定義の場合は `None`）
+    value: Option<String>,
+    /// 説明（現在の言語）
+    description: String,
+}
+
+impl Report for VarsReport {
+    fn print_text(&self) {
+        println!("{}", t!("vars.header"));
+        println!();
+        for variable in &self.variables {
+            let value = variable
+                .value
+                .clone()
+                .unwrap_or_else(|| t!("vars.undefined"));
+            println!(
+                "  {:<16} {:<24} {}",
+                variable.name, value, variable.description
+            );
         }
+        println!();
+        println!(
+            "{}",
+            t!(
+                "vars.example",
+                command = r#"discord-rp set -d "Working on {git.repo}" -s "{if git.branch}{git.branch | upper}{end}""#
+            )
+        );
     }
-    println!();
-    println!("使用例: discord-rp -i 1 set -d \"作業中\"");
+}
+
+/// varsコマンドの実行
+fn cmd_vars(cli: &Cli) -> Result<()> {
+    let variables = BuiltinVariables::list()
+        .iter()
+        .map(|builtin| VariableReport {
+            name: builtin.name,
+            value: BuiltinVariables::new().get(builtin.name),
+            description: builtin.description(),
+        })
+        .collect();
 
-    Ok(())
+    output::emit(cli, &VarsReport { variables })
 }
 
-/// initコマンドの実行
-fn cmd_init(output: Option<PathBuf>) -> Result<()> {
-    let path = output
-        .or_else(Config::default_path)
-        .context("設定ファイルのパスを決定できませんでした")?;
-
-    let config = Config::new();
-    config.save(&path)?;
-
-    println!("設定ファイルを作成しました: {}", path.display());
-    println!();
-    println!("Application IDは環境変数で設定してください:");
-    println!("  DISCORD_APPLICATION_ID_1=<Application ID>");
-    println!("  DISCORD_APPLICATION_ID_2=<Application ID>");
-    println!("  ...");
-    Ok(())
+/// Application IDの作成日時をローカル時刻で整形
+fn format_created_at(app_id: ApplicationId) -> String {
+    DateTime::<Local>::from(app_id.created_at())
+        .format("%Y-%m-%d %H:%M:%S")
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn output_is_accepted_after_subcommand() {
+        for args in [
+            ["discord-rp", "list", "--output", "json"].as_slice(),
+            &["discord-rp", "--output", "json", "list"],
+            &["discord-rp", "preset", "list", "--output=json"],
+        ] {
+            let cli = Cli::try_parse_from(args).unwrap();
+            assert!(cli.output == OutputFormat::Json, "{args:?}");
+        }
+
+        let cli = Cli::try_parse_from(["discord-rp", "init", "--path", "c.toml"]).unwrap();
+        assert!(cli.output == OutputFormat::Text);
+        assert!(
+            matches!(cli.command, Commands::Init { path: Some(ref path), .. } if path == Path::new("c.toml"))
+        );
+    }
 }
@@ -0,0 +1,78 @@
+//! CLI のメッセージカタログと言語の選択
+
+use clap::Command;
+use rp_core::{Catalog, Locale};
+use std::sync::LazyLock;
+
+/// CLI のメッセージ
+pub static CATALOG: LazyLock<Catalog> = LazyLock::new(|| {
+    Catalog::new()
+        .add(Locale::Ja, include_str!("../locales/ja.toml"))
+        .add(Locale::En, include_str!("../locales/en.toml"))
+});
+
+/// 使用する言語を決定
+///
+/// `--lang` を優先し、指定されていない場合は環境変数（`LC_ALL`, `LC_MESSAGES`, `LANG`）から判定する。
+/// ヘルプやパースエラーも選択した言語で表示するため、clap でパースする前にコマンドライン引数から探す
+pub fn detect_locale() -> Locale {
+    lang_arg().or_else(Locale::from_env).unwrap_or_default()
+}
+
+/// コマンドライン引数の `--lang` の値（対応していない言語の場合は `None`）
+fn lang_arg() -> Option<Locale> {
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == "--" {
+            break;
+        }
+        if arg == "--lang" {
+            return args.next()?.parse().ok();
+        }
+        if let Some(value) = arg.strip_prefix("--lang=") {
+            return value.parse().ok();
+        }
+    }
+    None
+}
+
+/// コマンドのヘルプを現在の言語に置き換える
+///
+/// ヘルプはドキュメントコメント（日本語）を使うため、カタログの `help` にある項目のみを置き換える。
+/// コマンドの説明は `help.<サブコマンドのパス>.about`、引数の説明は `help.<サブコマンドのパス>.<引数>`
+/// または全コマンド共通の `help.args.<引数>` で指定する
+pub fn localize_command(command: Command) -> Command {
+    localize(command, "help")
+}
+
+fn localize(mut command: Command, prefix: &str) -> Command {
+    let locale = Locale::current();
+    let message = |key: &str| CATALOG.get_in(locale, key).map(str::to_string);
+
+    if let Some(about) = message(&format!("{}.about", prefix)) {
+        command = command.about(about).long_about(None);
+    }
+
+    let args = command
+        .get_arguments()
+        .map(|arg| arg.get_id().to_string())
+        .collect::<Vec<_>>();
+    for id in args {
+        let help = message(&format!("{}.{}", prefix, id))
+            .or_else(|| message(&format!("help.args.{}", id)));
+        if let Some(help) = help {
+            command = command.mut_arg(&id, |arg| arg.help(help).long_help(None));
+        }
+    }
+
+    let subcommands = command
+        .get_subcommands()
+        .map(|subcommand| subcommand.get_name().to_string())
+        .collect::<Vec<_>>();
+    for name in subcommands {
+        let prefix = format!("{}.{}", prefix, name);
+        command = command.mut_subcommand(&name, |subcommand| localize(subcommand, &prefix));
+    }
+
+    command
+}
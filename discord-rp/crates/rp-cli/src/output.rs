@@ -0,0 +1,207 @@
+//! コマンドの出力形式（人が読むテキスト / 機械可読な JSON）
+//!
+//! JSON の場合、各コマンドは成功・失敗にかかわらず標準出力に1つのオブジェクトのみを出力する
+//!
+//! ```json
+//! {"ok":true,"command":"preset delete","result":{"name":"work","path":"/home/user/.config/discord-rp/config.toml"}}
+//! {"ok":false,"command":"preset delete","error":{"code":"preset_not_found","message":"プリセット work が見つかりません"}}
+//! ```
+
+use crate::{Cli, exit_code};
+use anyhow::{Context, Result};
+use clap::{ArgMatches, ValueEnum};
+use serde::Serialize;
+use std::sync::atomic::{AtomicBool, Ordering};
+use tracing::error;
+
+/// 出力形式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    /// 人が読む形式
+    #[default]
+    Text,
+    /// コマンドごとに1つの JSON オブジェクト
+    Json,
+}
+
+/// コマンドの結果（JSON の場合は `result` としてシリアライズする）
+pub trait Report: Serialize {
+    /// 人が読む形式で表示
+    fn print_text(&self);
+}
+
+/// JSON のオブジェクトを出力済みか
+static EMITTED: AtomicBool = AtomicBool::new(false);
+
+/// 失敗を含む JSON のオブジェクトを出力済みか
+static FAILURE_EMITTED: AtomicBool = AtomicBool::new(false);
+
+/// JSON で出力するオブジェクト
+#[derive(Serialize)]
+struct Envelope<'a, T> {
+    /// 成功したか
+    ok: bool,
+    /// 実行したコマンド（例: `preset apply`）
+    command: &'a str,
+    /// コマンドの結果
+    #[serde(skip_serializing_if = "Option::is_none")]
+    result: Option<&'a T>,
+    /// 失敗した場合のエラー
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<ErrorReport>,
+}
+
+/// JSON で出力するエラー
+#[derive(Serialize)]
+struct ErrorReport {
+    /// エラーの種類を表すコード
+    code: &'static str,
+    /// エラーの内容（原因を含む）
+    message: String,
+    /// プロセスの終了コード
+    exit_code: u8,
+}
+
+impl ErrorReport {
+    fn new(error: &anyhow::Error) -> Self {
+        Self {
+            code: error_code(error),
+            message: format!("{:#}", error),
+            exit_code: exit_code::from_error(error),
+        }
+    }
+}
+
+/// コマンドの結果を出力
+pub fn emit<T: Report>(cli: &Cli, report: &T) -> Result<()> {
+    match cli.output {
+        OutputFormat::Text => {
+            report.print_text();
+            Ok(())
+        }
+        OutputFormat::Json => print_json(&Envelope {
+            ok: true,
+            command: &cli.command_name,
+            result: Some(report),
+            error: None,
+        }),
+    }
+}
+
+/// 結果とともに失敗を出力し、エラーを返す（config check で見つかった問題など）
+pub fn emit_failure<T: Report>(cli: &Cli, report: &T, error: anyhow::Error) -> anyhow::Error {
+    let printed = match cli.output {
+        OutputFormat::Text => {
+            report.print_text();
+            Ok(())
+        }
+        OutputFormat::Json => print_json(&Envelope {
+            ok: false,
+            command: &cli.command_name,
+            result: Some(report),
+            error: Some(ErrorReport::new(&error)),
+        }),
+    };
+    match printed {
+        Ok(()) => {
+            FAILURE_EMITTED.store(true, Ordering::SeqCst);
+            error
+        }
+        Err(e) => e,
+    }
+}
+
+/// 失敗を JSON で出力
+///
+/// [`emit_failure`] で出力済みの場合は何もしない。
+/// 結果を出力した後に失敗した場合（表示を維持している間の切断など）は、ログに記録するのみ
+pub fn emit_error(cli: &Cli, error: &anyhow::Error) {
+    if FAILURE_EMITTED.load(Ordering::SeqCst) {
+        return;
+    }
+    if EMITTED.load(Ordering::SeqCst) {
+        error!("{:#}", error);
+        return;
+    }
+
+    let envelope = Envelope::<()> {
+        ok: false,
+        command: &cli.command_name,
+        result: None,
+        error: Some(ErrorReport::new(error)),
+    };
+    if let Err(e) = print_json(&envelope) {
+        error!("{:#}", e);
+    }
+}
+
+/// コマンドライン引数の誤りを JSON で出力
+///
+/// コマンドを判定できないため、`command` は空にする
+pub fn emit_usage_error(error: &clap::Error) {
+    let envelope = Envelope::<()> {
+        ok: false,
+        command: "",
+        result: None,
+        error: Some(ErrorReport {
+            code: "usage",
+            message: error.render().to_string().trim_end().to_string(),
+            exit_code: exit_code::USAGE,
+        }),
+    };
+    if let Err(e) = print_json(&envelope) {
+        error!("{:#}", e);
+    }
+}
+
+/// コマンドライン引数で JSON の出力（`--output json` または `status --json`）を指定しているか
+///
+/// 引数の解析に失敗した場合に出力形式を決めるため、引数を直接調べる
+pub fn json_requested() -> bool {
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--" => break,
+            "--json" | "--output=json" => return true,
+            "--output" if args.next().as_deref() == Some("json") => return true,
+            _ => {}
+        }
+    }
+    false
+}
+
+/// 1行の JSON として標準出力に書き込む
+fn print_json<T: Serialize>(envelope: &Envelope<'_, T>) -> Result<()> {
+    let json = serde_json::to_string(envelope).with_context(|| t!("output.serialize_failed"))?;
+    println!("{}", json);
+    EMITTED.store(true, Ordering::SeqCst);
+    Ok(())
+}
+
+/// エラーの種類を表すコード
+///
+/// 原因をたどって最初に見つかった [`rp_core::Error`] のバリアントから決める。
+/// 見つからない場合は、ファイルの読み書きなどの入出力のエラーなら `io`、それ以外は `failed`
+pub fn error_code(error: &anyhow::Error) -> &'static str {
+    if let Some(e) = error
+        .chain()
+        .find_map(|e| e.downcast_ref::<rp_core::Error>())
+    {
+        return e.code();
+    }
+    if error.chain().any(|e| e.is::<std::io::Error>()) {
+        return "io";
+    }
+    "failed"
+}
+
+/// 実行したコマンドの名前（サブコマンドを空白で区切る）
+pub fn command_name(matches: &ArgMatches) -> String {
+    let mut names = Vec::new();
+    let mut matches = matches;
+    while let Some((name, sub)) = matches.subcommand() {
+        names.push(name);
+        matches = sub;
+    }
+    names.join(" ")
+}
@@ -0,0 +1,348 @@
+//! pomodoroコマンド（作業と休憩を繰り返すタイマーの表示）
+
+use crate::output::{self, OutputFormat, Report};
+use crate::presenter::{Presenter, Target};
+use crate::session::ActivitySource;
+use crate::{Cli, REFRESH_INTERVAL, daemon, get_app_id, load_config};
+use anyhow::{Context, Result};
+use chrono::{DateTime, Local};
+use clap::Args;
+use rp_core::{ActivityTemplate, ApplicationId, Config, Error, Pomodoro, PomodoroStep, Request};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::process::{Command, Stdio};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant, SystemTime};
+use tracing::{info, warn};
+
+/// 終了時のフックの終了を待つ最大の時間（過ぎた場合はフックを残したまま終了する）
+const FINISH_HOOK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// 終了時のフックの終了を確認する間隔
+const FINISH_HOOK_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// pomodoroコマンドの引数（指定した項目は設定ファイルの `[pomodoro]` より優先）
+#[derive(Args)]
+pub struct PomodoroArgs {
+    /// 作業の長さ（分）
+    #[arg(long, value_name = "MIN", value_parser = clap::value_parser!(u32).range(1..))]
+    work: Option<u32>,
+
+    /// 短い休憩の長さ（分）
+    #[arg(long = "break", value_name = "MIN", value_parser = clap::value_parser!(u32).range(1..))]
+    short_break: Option<u32>,
+
+    /// 長い休憩の長さ（分）
+    #[arg(long, value_name = "MIN", value_parser = clap::value_parser!(u32).range(1..))]
+    long_break: Option<u32>,
+
+    /// 長い休憩までの作業の回数
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u32).range(1..))]
+    cycles: Option<u32>,
+
+    /// 表示の元にするプリセット（画像や状態テキストなど）
+    #[arg(short, long)]
+    preset: Option<String>,
+
+    /// 長い休憩の後も最初から繰り返す（指定しない場合は長い休憩の後に終了）
+    #[arg(long)]
+    repeat: bool,
+
+    /// 作業を始めるときに実行するコマンド
+    #[arg(long, value_name = "COMMAND")]
+    on_work: Option<String>,
+
+    /// 短い休憩を始めるときに実行するコマンド
+    #[arg(long, value_name = "COMMAND")]
+    on_break: Option<String>,
+
+    /// 長い休憩を始めるときに実行するコマンド
+    #[arg(long, value_name = "COMMAND")]
+    on_long_break: Option<String>,
+
+    /// 長い休憩が終わったときに実行するコマンド
+    #[arg(long, value_name = "COMMAND")]
+    on_finish: Option<String>,
+}
+
+impl PomodoroArgs {
+    /// 設定ファイルの設定にフラグで指定した項目を重ねる
+    fn apply(&self, mut pomodoro: Pomodoro) -> Pomodoro {
+        pomodoro.work = self.work.unwrap_or(pomodoro.work);
+        pomodoro.short_break = self.short_break.unwrap_or(pomodoro.short_break);
+        pomodoro.long_break = self.long_break.unwrap_or(pomodoro.long_break);
+        pomodoro.cycles = self.cycles.unwrap_or(pomodoro.cycles);
+        pomodoro.preset = self.preset.clone().or(pomodoro.preset);
+
+        let hooks = &mut pomodoro.hooks;
+        for (hook, flag) in [
+            (&mut hooks.work, &self.on_work),
+            (&mut hooks.short_break, &self.on_break),
+            (&mut hooks.long_break, &self.on_long_break),
+            (&mut hooks.finish, &self.on_finish),
+        ] {
+            if flag.is_some() {
+                hook.clone_from(flag);
+            }
+        }
+        pomodoro
+    }
+}
+
+/// pomodoroコマンドを開始したときの結果
+#[derive(Serialize)]
+struct StartReport<'a> {
+    /// 作業の長さ（分）
+    work: u32,
+    /// 短い休憩の長さ（分）
+    #[serde(rename = "break")]
+    short_break: u32,
+    /// 長い休憩の長さ（分）
+    long_break: u32,
+    /// 長い休憩までの作業の回数
+    cycles: u32,
+    /// 長い休憩の後も繰り返すか
+    repeat: bool,
+    /// 表示の元にするプリセット
+    #[serde(skip_serializing_if = "Option::is_none")]
+    preset: Option<&'a str>,
+    /// Application IDのインデックス
+    index: u32,
+    /// 使用するApplication ID
+    application_id: ApplicationId,
+    /// デーモンに表示を任せるか
+    daemon: bool,
+}
+
+impl Report for StartReport<'_> {
+    fn print_text(&self) {
+        println!(
+            "{}",
+            t!(
+                "pomodoro.started",
+                work = self.work,
+                short_break = self.short_break,
+                long_break = self.long_break,
+                cycles = self.cycles
+            )
+        );
+    }
+}
+
+/// ポモドーロタイマーの設定を検査（長さが 0 分の場合やプリセットを適用できない場合はエラー）
+fn check_pomodoro(config: &Config) -> rp_core::Result<()> {
+    let problems = config
+        .validate()
+        .into_iter()
+        .filter(|diagnostic| diagnostic.key.first().is_some_and(|key| key == "pomodoro"))
+        .map(|diagnostic| diagnostic.to_string())
+        .collect::<Vec<_>>();
+    if !problems.is_empty() {
+        return Err(Error::InvalidPomodoro(problems.join("; ")));
+    }
+    Ok(())
+}
+
+/// pomodoro: 作業と休憩を繰り返し、フェーズの名前と残り時間、作業の回数を表示する
+///
+/// 残り時間は終了時刻のタイムスタンプとして送信し、Discord が数える。
+/// フェーズが切り替わるたびにフックのコマンドを実行する
+pub fn run(cli: &Cli, args: &PomodoroArgs) -> Result<()> {
+    let mut config = load_config(cli)?;
+    config.pomodoro = args.apply(config.pomodoro.clone());
+    check_pomodoro(&config)?;
+    let pomodoro = &config.pomodoro;
+
+    let target = resolve(cli, &config)?;
+    // 表示の前に、どのフェーズの表示内容も Discord の制限を満たすかを検査する
+    for step in pomodoro.steps() {
+        step_template(pomodoro, &target.template, &step, 0)
+            .to_ipc_payloads()
+            .with_context(|| t!("cli.payload_failed"))?;
+    }
+
+    let daemon = daemon::request(cli, &Request::Status)?.is_some();
+    output::emit(
+        cli,
+        &StartReport {
+            work: pomodoro.work,
+            short_break: pomodoro.short_break,
+            long_break: pomodoro.long_break,
+            cycles: pomodoro.cycles,
+            repeat: args.repeat,
+            preset: pomodoro.preset.as_deref(),
+            index: target.index,
+            application_id: target.application_id,
+            daemon,
+        },
+    )?;
+
+    let (tx, rx) = mpsc::channel();
+    ctrlc::set_handler(move || {
+        let _ = tx.send(());
+    })
+    .with_context(|| t!("cli.ctrlc_failed"))?;
+
+    let mut presenter = Presenter::new(cli, daemon, Duration::from_secs(config.reconnect_interval));
+    let finished = 'session: loop {
+        for step in pomodoro.steps() {
+            let end = SystemTime::now() + step.length;
+            let end_secs = end
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs() as i64;
+            info!(
+                "{}",
+                t!(
+                    "pomodoro.phase",
+                    label = pomodoro.label(step.phase),
+                    cycle = step.cycle,
+                    cycles = pomodoro.cycles,
+                    until = DateTime::<Local>::from(end).format("%H:%M")
+                )
+            );
+            if let Some(hook) = pomodoro.hook(step.phase) {
+                run_hook(cli, hook, step.phase.name(), step.cycle, pomodoro.cycles);
+            }
+            presenter.show(
+                Target {
+                    template: step_template(pomodoro, &target.template, &step, end_secs),
+                    ..target.clone()
+                },
+                false,
+            );
+
+            // フェーズの終了時刻まで、更新間隔の制限で持ち越した送信を再試行しながら待機
+            let deadline = Instant::now() + step.length;
+            loop {
+                let remaining = deadline.saturating_duration_since(Instant::now());
+                if remaining.is_zero() {
+                    break;
+                }
+                match rx.recv_timeout(remaining.min(REFRESH_INTERVAL)) {
+                    Err(RecvTimeoutError::Timeout) => presenter.tick(),
+                    Ok(()) | Err(RecvTimeoutError::Disconnected) => break 'session false,
+                }
+            }
+        }
+        if !args.repeat {
+            break true;
+        }
+    };
+
+    presenter.stop();
+    if finished {
+        info!("{}", t!("pomodoro.finished"));
+        if let Some(ref hook) = pomodoro.hooks.finish
+            && let Some(waiter) = run_hook(cli, hook, "finish", pomodoro.cycles, pomodoro.cycles)
+        {
+            // 失敗した場合の警告を表示できるよう、終了を待ってから終了する
+            wait_hook(hook, waiter, &rx);
+        }
+    }
+    if cli.output == OutputFormat::Text {
+        println!("{}", t!("session.finished"));
+    }
+    Ok(())
+}
+
+/// 表示先と、フェーズの表示内容の元にするテンプレートを決定（プリセットを指定しない場合は設定ファイルのデフォルトのアクティビティ）
+fn resolve(cli: &Cli, config: &Config) -> Result<Target> {
+    let (index, mut template) = match config.pomodoro.preset {
+        Some(ref name) => ActivitySource::Preset {
+            name,
+            values: BTreeMap::new(),
+        }
+        .resolve(cli, config)?,
+        None => (cli.index(), ActivityTemplate::new(config.activity.clone())),
+    };
+    // 詳細テキストはフェーズの名前で置き換えるため、プリセットのローテーションは使用しない
+    template.rotation = None;
+    Ok(Target {
+        index,
+        application_id: get_app_id(config, index)?,
+        name: config
+            .get_application(index)
+            .ok()
+            .and_then(|app| app.name.clone()),
+        template: template
+            .in_dir(std::env::current_dir().with_context(|| t!("session.cwd_failed"))?),
+    })
+}
+
+/// フェーズの表示内容のテンプレート（`end` は終了時刻の Unixタイムスタンプ）
+fn step_template(
+    pomodoro: &Pomodoro,
+    base: &ActivityTemplate,
+    step: &PomodoroStep,
+    end: i64,
+) -> ActivityTemplate {
+    let mut template = base.clone();
+    template.activity = pomodoro.activity(&base.activity, step, end);
+    template
+}
+
+/// フックのコマンドをシェルで実行する（終了を待たずに次に進み、失敗した場合は警告のみ）
+///
+/// 環境変数 `DISCORD_RP_PHASE`、`DISCORD_RP_CYCLE`、`DISCORD_RP_CYCLES` を渡す。
+/// JSON 出力の場合は出力に混ざらないよう、コマンドの標準出力を標準エラー出力に書き込む。
+/// 起動できた場合は、終了を待って結果を警告するスレッドを返す
+fn run_hook(cli: &Cli, hook: &str, phase: &str, cycle: u32, cycles: u32) -> Option<JoinHandle<()>> {
+    let (shell, flag) = if cfg!(windows) {
+        ("cmd", "/C")
+    } else {
+        ("sh", "-c")
+    };
+    let mut command = Command::new(shell);
+    command
+        .args([flag, hook])
+        .env("DISCORD_RP_PHASE", phase)
+        .env("DISCORD_RP_CYCLE", cycle.to_string())
+        .env("DISCORD_RP_CYCLES", cycles.to_string())
+        .stdin(Stdio::null());
+    if cli.output == OutputFormat::Json {
+        command.stdout(std::io::stderr());
+    }
+
+    match command.spawn() {
+        Ok(mut child) => {
+            let hook = hook.to_string();
+            Some(std::thread::spawn(move || match child.wait() {
+                Ok(status) if !status.success() => warn!(
+                    "{}",
+                    t!("pomodoro.hook_failed", command = hook, status = status)
+                ),
+                Ok(_) => {}
+                Err(e) => warn!("{}", t!("pomodoro.hook_failed", command = hook, status = e)),
+            }))
+        }
+        Err(e) => {
+            warn!("{}", t!("pomodoro.hook_failed", command = hook, status = e));
+            None
+        }
+    }
+}
+
+/// フックの終了を [`FINISH_HOOK_TIMEOUT`] まで待つ（Ctrl+C で待つのをやめる）
+fn wait_hook(hook: &str, waiter: JoinHandle<()>, interrupt: &Receiver<()>) {
+    let deadline = Instant::now() + FINISH_HOOK_TIMEOUT;
+    while !waiter.is_finished() {
+        if Instant::now() >= deadline {
+            warn!(
+                "{}",
+                t!(
+                    "pomodoro.hook_timeout",
+                    command = hook,
+                    secs = FINISH_HOOK_TIMEOUT.as_secs()
+                )
+            );
+            return;
+        }
+        match interrupt.recv_timeout(FINISH_HOOK_POLL_INTERVAL) {
+            Err(RecvTimeoutError::Timeout) => {}
+            Ok(()) | Err(RecvTimeoutError::Disconnected) => return,
+        }
+    }
+    let _ = waiter.join();
+}
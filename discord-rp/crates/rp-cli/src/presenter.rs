@@ -0,0 +1,310 @@
+//! アクティビティを表示し続ける処理（set / preset apply / schedule run / pomodoro の共通処理）
+//!
+//! デーモンが起動している場合は表示をデーモンに任せ、起動していない場合は直接 Discord に接続する
+
+use crate::{Cli, daemon};
+use rp_core::{ActivityTemplate, ApplicationId, PresenceSession, Request, RichPresenceClient};
+use std::time::{Duration, Instant};
+use tracing::warn;
+
+/// 表示するアクティビティと表示先
+#[derive(Clone)]
+pub struct Target {
+    /// Application IDのインデックス
+    pub index: u32,
+    pub application_id: ApplicationId,
+    /// アプリケーションの表示用の名前
+    pub name: Option<String>,
+    pub template: ActivityTemplate,
+}
+
+/// 表示中のアクティビティと Discord との接続
+pub struct Presenter<'a> {
+    cli: &'a Cli,
+    /// デーモンに表示を任せるか
+    daemon: bool,
+    /// 接続できない場合や切断された場合に、接続を再試行する間隔
+    reconnect_interval: Duration,
+    /// 表示中のアクティビティ（表示していない場合は `None`）
+    target: Option<Target>,
+    /// 直接 Discord に接続している場合のセッション
+    session: Option<PresenceSession>,
+    /// 最後に接続を試みた時刻
+    last_attempt: Option<Instant>,
+    /// 最後に接続が維持されていることを確認した時刻（送信に成功した場合を含む）
+    last_check: Option<Instant>,
+}
+
+impl<'a> Presenter<'a> {
+    pub fn new(cli: &'a Cli, daemon: bool, reconnect_interval: Duration) -> Self {
+        Self {
+            cli,
+            daemon,
+            reconnect_interval,
+            target: None,
+            session: None,
+            last_attempt: None,
+            last_check: None,
+        }
+    }
+
+    /// 接続済みのセッションでアクティビティを表示している状態から始める
+    ///
+    /// 最初の接続の失敗をエラーとして扱う場合（set / preset apply）に、最初の表示を済ませてから使う
+    pub fn connected(
+        cli: &'a Cli,
+        reconnect_interval: Duration,
+        target: Target,
+        session: PresenceSession,
+    ) -> Self {
+        Self {
+            cli,
+            daemon: false,
+            reconnect_interval,
+            target: Some(target),
+            session: Some(session),
+            last_attempt: Some(Instant::now()),
+            last_check: Some(Instant::now()),
+        }
+    }
+
+    /// 接続を再試行する間隔を変更（設定を読み込み直した場合）
+    pub fn set_reconnect_interval(&mut self, interval: Duration) {
+        self.reconnect_interval = interval;
+    }
+
+    /// 表示するアクティビティを切り替える
+    ///
+    /// Application IDが変わる場合は表示中のアクティビティを先に消す。
+    /// `keep_start` の場合は経過時間の開始時刻を引き継ぐ（設定を読み込み直しただけの場合）
+    pub fn show(&mut self, target: Target, keep_start: bool) {
+        let same_app = self
+            .target
+            .as_ref()
+            .is_some_and(|current| current.application_id == target.application_id);
+        if !same_app {
+            self.clear();
+        }
+
+        if self.daemon {
+            self.request(&Request::Set {
+                index: target.index,
+                application_id: target.application_id,
+                name: target.name.clone(),
+                template: Box::new(target.template.clone()),
+                duration: None,
+            });
+        } else if let Some(ref mut session) = self.session {
+            if keep_start {
+                session.set_template(target.template.clone());
+            } else {
+                session.replace_template(target.template.clone());
+            }
+        }
+        self.target = Some(target);
+        self.tick();
+    }
+
+    /// テンプレートを再評価し、接続していない場合は再接続の間隔ごとに接続を試みる
+    ///
+    /// 更新間隔の制限中は送信せず、次回に持ち越す。
+    /// 表示内容が変わらない間も再接続の間隔ごとに接続を確認し、
+    /// Discord との接続が失われた場合はセッションを破棄して次回以降に接続し直す
+    pub fn tick(&mut self) {
+        if self.daemon {
+            return;
+        }
+        let Some(ref target) = self.target else {
+            return;
+        };
+
+        if self.session.is_none() {
+            if self
+                .last_attempt
+                .is_some_and(|last| last.elapsed() < self.reconnect_interval)
+            {
+                return;
+            }
+            self.last_attempt = Some(Instant::now());
+            let result = RichPresenceClient::new(target.application_id).and_then(|mut client| {
+                client.connect()?;
+                Ok(client)
+            });
+            match result {
+                Ok(client) => {
+                    self.session = Some(PresenceSession::new(client, target.template.clone()));
+                }
+                Err(e) => {
+                    warn!(
+                        "{}",
+                        t!("presenter.connect_failed", error = e.full_message())
+                    );
+                    return;
+                }
+            }
+        }
+
+        let Some(ref mut session) = self.session else {
+            return;
+        };
+        match session.refresh() {
+            Ok(true) => self.last_check = Some(Instant::now()),
+            Ok(false) => {
+                if self
+                    .last_check
+                    .is_none_or(|last| last.elapsed() >= self.reconnect_interval)
+                {
+                    self.last_check = Some(Instant::now());
+                    // 失敗した場合はクライアントが警告を出力し、切断した状態になる
+                    let _ = session.client_mut().ping();
+                }
+            }
+            Err(e) => warn!("{}", t!("session.refresh_failed", error = e.full_message())),
+        }
+        if !session.client().is_connected() {
+            self.session = None;
+        }
+    }
+
+    /// 表示中のアクティビティを消す
+    pub fn clear(&mut self) {
+        let Some(target) = self.target.take() else {
+            return;
+        };
+        if self.daemon {
+            self.request(&Request::Clear {
+                application_id: target.application_id,
+            });
+            return;
+        }
+
+        // 切断すると Discord は表示を消すため、更新間隔の制限中でもすぐに消える
+        if let Some(session) = self.session.take() {
+            let mut client = session.into_client();
+            if client.next_update_in().is_zero()
+                && let Err(e) = client.clear_activity()
+            {
+                warn!("{}", t!("presenter.clear_failed", error = e.full_message()));
+            }
+            if let Err(e) = client.disconnect() {
+                warn!("{}", t!("presenter.clear_failed", error = e.full_message()));
+            }
+        }
+        self.last_attempt = None;
+        self.last_check = None;
+    }
+
+    /// 終了時に表示中のアクティビティを消す
+    pub fn stop(mut self) {
+        self.clear();
+    }
+
+    /// デーモンにリクエストを送信（失敗した場合は警告のみ）
+    fn request(&self, request: &Request) {
+        if let Err(e) = daemon::request(self.cli, request) {
+            warn!(
+                "{}",
+                t!("presenter.daemon_failed", error = format!("{:#}", e))
+            );
+        }
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use clap::Parser;
+    use rp_core::Activity;
+    use serde_json::{Value, json};
+    use std::io::{Read, Write};
+    use std::os::unix::net::{UnixListener, UnixStream};
+    use std::sync::mpsc;
+
+    /// IPC のフレーム（オペコード、長さ、JSON）を1つ読み込む（接続が閉じられた場合は `None`）
+    fn read_frame(stream: &mut UnixStream) -> Option<Value> {
+        let mut header = [0; 8];
+        stream.read_exact(&mut header).ok()?;
+        let len = u32::from_le_bytes(header[4..].try_into().unwrap());
+        let mut body = vec![0; len as usize];
+        stream.read_exact(&mut body).ok()?;
+        serde_json::from_slice(&body).ok()
+    }
+
+    fn write_frame(stream: &mut UnixStream, value: &Value) {
+        let body = value.to_string();
+        let mut frame = 1u32.to_le_bytes().to_vec();
+        frame.extend((body.len() as u32).to_le_bytes());
+        frame.extend(body.as_bytes());
+        stream.write_all(&frame).unwrap();
+    }
+
+    /// ハンドシェイクに応答し、`commands` 回だけ SET_ACTIVITY に応答して詳細テキストを `tx` に送る
+    ///
+    /// ソケットを確かめるだけの接続（ハンドシェイクをしない）の場合は `false`
+    fn serve(mut stream: UnixStream, commands: usize, tx: &mpsc::Sender<String>) -> bool {
+        if read_frame(&mut stream).is_none() {
+            return false;
+        }
+        write_frame(
+            &mut stream,
+            &json!({"cmd": "DISPATCH", "evt": "READY", "data": {}}),
+        );
+        for _ in 0..commands {
+            let Some(command) = read_frame(&mut stream) else {
+                return true;
+            };
+            let details = &command["args"]["activity"]["details"];
+            tx.send(details.as_str().unwrap_or_default().to_string())
+                .unwrap();
+            write_frame(
+                &mut stream,
+                &json!({"cmd": command["cmd"], "evt": null, "nonce": command["nonce"]}),
+            );
+        }
+        true
+    }
+
+    #[test]
+    fn reconnects_when_discord_connection_is_lost() {
+        let dir = std::env::temp_dir().join(format!("rp-cli-ipc-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let listener = UnixListener::bind(dir.join("discord-ipc-0")).unwrap();
+        // SAFETY: Discord のソケットを探すディレクトリを変更するのはこのテストのみ
+        unsafe { std::env::set_var("XDG_RUNTIME_DIR", &dir) };
+
+        // 1つ目の接続はハンドシェイクの後に閉じ（Discord の終了）、2つ目の接続は正常に応答する
+        let (tx, rx) = mpsc::channel();
+        let server = std::thread::spawn(move || {
+            let mut connections = listener.incoming().map(|stream| stream.unwrap());
+            while !serve(connections.next().unwrap(), 0, &tx) {}
+            while !serve(connections.next().unwrap(), 1, &tx) {}
+        });
+
+        let cli = Cli::try_parse_from(["discord-rp", "--no-daemon", "status"]).unwrap();
+        let mut presenter = Presenter::new(&cli, false, Duration::ZERO);
+        presenter.show(
+            Target {
+                index: 1,
+                application_id: "123456789012345678".parse().unwrap(),
+                name: None,
+                template: ActivityTemplate::new(Activity::new().details("working")),
+            },
+            false,
+        );
+        // 送信に失敗して切断された状態になり、セッションを破棄する
+        assert!(presenter.session.is_none());
+
+        presenter.tick();
+        assert!(
+            presenter
+                .session
+                .as_ref()
+                .is_some_and(|session| session.client().is_connected())
+        );
+        assert_eq!(rx.recv().unwrap(), "working");
+
+        presenter.stop();
+        server.join().unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,483 @@
+//! presetサブコマンド
+
+use crate::output::{self, Report};
+use crate::session::{self, ActivitySource};
+use crate::{
+    ActivityArgs, Cli, MigrationBackup, config_path, load_config, load_file_config,
+    save_file_config,
+};
+use anyhow::{Context, Result};
+use clap::Subcommand;
+use rp_core::{Config, ConfigFormat, Preset, PresetVar};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+#[derive(Subcommand)]
+pub enum PresetCommand {
+    /// プリセット一覧を表示
+    List,
+
+    /// プリセットの内容を表示
+    Show {
+        /// プリセット名
+        name: String,
+
+        /// 継承を解決した結果と継承の連鎖を表示
+        #[arg(long)]
+        resolved: bool,
+    },
+
+    /// プリセットを適用して表示（-i を指定するとプリセットのインデックスより優先）
+    Apply {
+        /// プリセット名
+        name: String,
+
+        /// 変数の値（NAME=VALUE、複数指定可）
+        #[arg(long = "var", value_name = "NAME=VALUE", value_parser = parse_var_value)]
+        vars: Vec<(String, String)>,
+
+        /// 表示を維持する秒数（指定しない場合は Ctrl+C まで維持）
+        #[arg(short = 'D', long)]
+        duration: Option<u64>,
+    },
+
+    /// setコマンドと同じフラグでプリセットを保存（-i のインデックスを使用）
+    ///
+    /// テキスト中の {NAME} は --var で宣言した変数に置換される
+    Save {
+        /// プリセット名
+        name: String,
+
+        /// 継承元のプリセット名（指定しなかった項目は継承元の値を使用）
+        #[arg(long)]
+        extends: Option<String>,
+
+        #[command(flatten)]
+        activity: ActivityArgs,
+
+        /// 変数の宣言（NAME=DEFAULT でデフォルト値付き、NAME のみで必須、複数指定可）
+        #[arg(long = "var", value_name = "NAME[=DEFAULT]", value_parser = parse_var_decl)]
+        vars: Vec<(String, PresetVar)>,
+
+        /// 同名のプリセットを上書きする
+        #[arg(short, long)]
+        force: bool,
+    },
+
+    /// プリセットを削除
+    Delete {
+        /// プリセット名
+        name: String,
+    },
+}
+
+/// presetサブコマンドの実行
+pub fn run(cli: &Cli, command: &PresetCommand) -> Result<()> {
+    match command {
+        PresetCommand::List => cmd_list(cli),
+        PresetCommand::Show { name, resolved } => cmd_show(cli, name, *resolved),
+        PresetCommand::Apply {
+            name,
+            vars,
+            duration,
+        } => cmd_apply(cli, name, vars, *duration),
+        PresetCommand::Save {
+            name,
+            extends,
+            activity,
+            vars,
+            force,
+        } => cmd_save(cli, name, extends.as_deref(), activity, vars, *force),
+        PresetCommand::Delete { name } => cmd_delete(cli, name),
+    }
+}
+
+/// `NAME=VALUE` 形式の変数指定をパース
+pub fn parse_var_value(s: &str) -> std::result::Result<(String, String), String> {
+    let (name, value) = s
+        .split_once('=')
+        .ok_or_else(|| t!("preset.var_format", value = s))?;
+    Ok((name.trim().to_string(), value.to_string()))
+}
+
+/// `NAME[=DEFAULT]` 形式の変数宣言をパース
+fn parse_var_decl(s: &str) -> std::result::Result<(String, PresetVar), String> {
+    Ok(match s.split_once('=') {
+        Some((name, default)) => (name.trim().to_string(), PresetVar::with_default(default)),
+        None => (s.trim().to_string(), PresetVar::required()),
+    })
+}
+
+/// preset listの結果
+#[derive(Serialize)]
+struct ListReport {
+    /// 登録済みのプリセット（名前順）
+    presets: Vec<PresetSummary>,
+}
+
+/// 登録済みのプリセットの概要
+#[derive(Serialize)]
+struct PresetSummary {
+    /// プリセット名
+    name: String,
+    /// 使用するApplication IDのインデックス（継承を解決した結果）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    app: Option<u32>,
+    /// 詳細テキスト（継承を解決した結果）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    details: Option<String>,
+    /// 状態テキスト（継承を解決した結果）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    state: Option<String>,
+    /// 継承元のプリセット名
+    #[serde(skip_serializing_if = "Option::is_none")]
+    extends: Option<String>,
+    /// 定義元の共有プリセットのファイル（設定ファイルで定義した場合は `None`）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    source: Option<PathBuf>,
+    /// 継承を解決できなかった場合のエラー
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+impl Report for ListReport {
+    fn print_text(&self) {
+        if self.presets.is_empty() {
+            println!("{}", t!("preset.list.empty"));
+            println!();
+            println!("{}", t!("preset.list.example"));
+            return;
+        }
+
+        println!("{}", t!("preset.list.header"));
+        println!();
+        for preset in &self.presets {
+            let line = match preset.error {
+                Some(ref error) => format!(
+                    "  {} {}",
+                    preset.name,
+                    t!("preset.list.error", error = error)
+                ),
+                None => {
+                    let summary = [preset.details.as_deref(), preset.state.as_deref()]
+                        .into_iter()
+                        .flatten()
+                        .collect::<Vec<_>>()
+                        .join(" / ");
+                    format!(
+                        "  {} [{}] {}",
+                        preset.name,
+                        preset.app.unwrap_or(1),
+                        summary
+                    )
+                }
+            };
+            let mut notes = Vec::new();
+            if let Some(ref parent) = preset.extends {
+                notes.push(t!("preset.list.extends", parent = parent));
+            }
+            if let Some(ref source) = preset.source {
+                notes.push(t!("preset.list.source", path = source.display()));
+            }
+
+            if notes.is_empty() {
+                println!("{}", line);
+            } else {
+                println!(
+                    "{}",
+                    t!(
+                        "preset.list.with_notes",
+                        line = line,
+                        notes = notes.join(&t!("preset.list.separator"))
+                    )
+                );
+            }
+        }
+    }
+}
+
+/// preset listの実行
+fn cmd_list(cli: &Cli) -> Result<()> {
+    let config = load_config(cli)?;
+
+    let presets = config
+        .presets
+        .iter()
+        .map(|(name, preset)| {
+            let resolved = config.resolve_preset(name);
+            let activity = resolved.as_ref().ok().map(|resolved| &resolved.activity);
+            PresetSummary {
+                name: name.clone(),
+                app: resolved
+                    .as_ref()
+                    .ok()
+                    .map(|resolved| resolved.app.unwrap_or(1)),
+                details: activity.and_then(|activity| activity.details.clone()),
+                state: activity.and_then(|activity| activity.state.clone()),
+                extends: preset.extends.clone(),
+                source: config.preset_source(name).map(PathBuf::from),
+                error: resolved.as_ref().err().map(ToString::to_string),
+            }
+        })
+        .collect();
+
+    output::emit(cli, &ListReport { presets })
+}
+
+/// preset showの結果
+#[derive(Serialize)]
+struct ShowReport<'a> {
+    /// プリセット名
+    name: &'a str,
+    /// プリセットの内容（--resolved の場合は継承を解決した結果）
+    preset: Preset,
+    /// 定義元の共有プリセットのファイル（設定ファイルで定義した場合は `None`）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    source: Option<PathBuf>,
+    /// 継承の連鎖（先に適用されるものから順、--resolved の場合のみ）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    chain: Option<Vec<ChainEntry>>,
+    /// 設定ファイルにそのまま貼り付けられる形式の内容
+    #[serde(skip)]
+    toml: String,
+}
+
+/// 継承の連鎖の各プリセット
+#[derive(Serialize)]
+struct ChainEntry {
+    /// プリセット名
+    name: String,
+    /// 定義元の共有プリセットのファイル（設定ファイルで定義した場合は `None`）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    source: Option<PathBuf>,
+}
+
+impl Report for ShowReport<'_> {
+    fn print_text(&self) {
+        match self.chain {
+            Some(ref chain) => {
+                println!("# {}", t!("preset.show.chain"));
+                for entry in chain {
+                    match entry.source {
+                        Some(ref source) => println!("#   {} ({})", entry.name, source.display()),
+                        None => println!("#   {} ({})", entry.name, t!("preset.show.config_file")),
+                    }
+                }
+                println!();
+            }
+            None => {
+                if let Some(ref source) = self.source {
+                    println!("# {}", t!("preset.show.source", path = source.display()));
+                    println!();
+                }
+            }
+        }
+
+        print!("{}", self.toml);
+
+        if !self.preset.vars.is_empty() {
+            println!();
+            println!("# {}", t!("preset.show.vars"));
+            for (var_name, var) in &self.preset.vars {
+                let value = match var.default {
+                    Some(ref default) => {
+                        t!("preset.show.default", value = format!("{:?}", default))
+                    }
+                    None => t!("preset.show.required"),
+                };
+                match var.description {
+                    Some(ref description) => {
+                        println!("#   {} ({}) {}", var_name, value, description)
+                    }
+                    None => println!("#   {} ({})", var_name, value),
+                }
+            }
+        }
+    }
+}
+
+/// preset showの実行
+fn cmd_show(cli: &Cli, name: &str, resolved: bool) -> Result<()> {
+    let config = load_config(cli)?;
+
+    let mut report = if resolved {
+        let chain = config
+            .preset_chain(name)?
+            .into_iter()
+            .map(|preset_name| ChainEntry {
+                name: preset_name.to_string(),
+                source: config.preset_source(preset_name).map(PathBuf::from),
+            })
+            .collect();
+        ShowReport {
+            name,
+            preset: config.resolve_preset(name)?,
+            source: config.preset_source(name).map(PathBuf::from),
+            chain: Some(chain),
+            toml: String::new(),
+        }
+    } else {
+        ShowReport {
+            name,
+            preset: config.get_preset(name)?.clone(),
+            source: config.preset_source(name).map(PathBuf::from),
+            chain: None,
+            toml: String::new(),
+        }
+    };
+
+    // 設定ファイルにそのまま貼り付けられる形式で出力する
+    let table = BTreeMap::from([("presets", BTreeMap::from([(name, &report.preset)]))]);
+    report.toml = toml::to_string_pretty(&table).with_context(|| t!("preset.serialize_failed"))?;
+
+    output::emit(cli, &report)
+}
+
+/// preset applyの実行
+fn cmd_apply(
+    cli: &Cli,
+    name: &str,
+    vars: &[(String, String)],
+    duration: Option<u64>,
+) -> Result<()> {
+    let config = load_config(cli)?;
+    let values = vars.iter().cloned().collect::<BTreeMap<_, _>>();
+
+    session::run(
+        cli,
+        config,
+        ActivitySource::Preset { name, values },
+        duration,
+    )
+}
+
+/// preset save / preset deleteの結果
+#[derive(Serialize)]
+struct SaveReport<'a> {
+    /// プリセット名
+    name: &'a str,
+    /// 書き込んだ設定ファイル
+    path: PathBuf,
+    /// 古い形式の設定ファイルを上書きする前に作成したバックアップ
+    #[serde(skip_serializing_if = "Option::is_none")]
+    backup: Option<MigrationBackup>,
+    /// 削除したか（保存した場合は `false`）
+    #[serde(skip)]
+    deleted: bool,
+}
+
+impl Report for SaveReport<'_> {
+    fn print_text(&self) {
+        if let Some(ref backup) = self.backup {
+            backup.print_text();
+        }
+        if self.deleted {
+            println!(
+                "{}",
+                t!(
+                    "preset.deleted",
+                    name = self.name,
+                    path = self.path.display()
+                )
+            );
+        } else {
+            println!(
+                "{}",
+                t!("preset.saved", name = self.name, path = self.path.display())
+            );
+        }
+    }
+}
+
+/// preset saveの実行
+fn cmd_save(
+    cli: &Cli,
+    name: &str,
+    extends: Option<&str>,
+    activity: &ActivityArgs,
+    vars: &[(String, PresetVar)],
+    force: bool,
+) -> Result<()> {
+    let path = config_path(cli.config.as_deref())?;
+    // 環境変数由来のApplication IDを書き込まないよう、ファイルの内容のみを読み込む
+    let mut config = if path.exists() {
+        Config::load(&path)?
+    } else {
+        Config::new()
+    };
+
+    if config.presets.contains_key(name) && !force {
+        anyhow::bail!(t!("preset.exists", name = name));
+    }
+
+    let mut preset = vars
+        .iter()
+        .cloned()
+        .fold(activity.to_preset(), |preset, (var_name, var)| {
+            preset.var(var_name, var)
+        });
+    match extends {
+        Some(parent) => {
+            preset = preset.extends(parent);
+            preset.app = cli.index;
+        }
+        None => preset = preset.app(cli.index()),
+    }
+
+    // 共有プリセットを含めて継承を解決し、変数の参照を検証する
+    let mut resolved_config = load_config(cli)?;
+    resolved_config.add_preset(name, preset.clone());
+    resolved_config.resolve_preset(name)?.validate()?;
+
+    let backup = if keeps_layout(&config, &path) {
+        Config::save_preset(&path, name, &preset)?;
+        None
+    } else {
+        config.add_preset(name, preset);
+        save_file_config(&config, &path)?
+    };
+
+    output::emit(
+        cli,
+        &SaveReport {
+            name,
+            path,
+            backup,
+            deleted: false,
+        },
+    )
+}
+
+/// 設定ファイルの `presets` テーブルのみを書き換えられるか
+///
+/// 現在の形式の TOML ファイルは、コメントや参照（`${VAR}`, `file:`）を残すため設定全体を書き込まない
+fn keeps_layout(config: &Config, path: &Path) -> bool {
+    path.exists()
+        && ConfigFormat::from_path(path) == ConfigFormat::Toml
+        && config.migrated_from().is_none()
+}
+
+/// preset deleteの実行
+fn cmd_delete(cli: &Cli, name: &str) -> Result<()> {
+    let path = config_path(cli.config.as_deref())?;
+    let mut config = load_file_config(cli.config.as_deref())?;
+
+    let backup = if keeps_layout(&config, &path) {
+        Config::delete_preset(&path, name)?;
+        None
+    } else {
+        config.remove_preset(name)?;
+        save_file_config(&config, &path)?
+    };
+
+    output::emit(
+        cli,
+        &SaveReport {
+            name,
+            path,
+            backup,
+            deleted: true,
+        },
+    )
+}
@@ -0,0 +1,83 @@
+//! 設定ファイルと .env の変更の検出
+
+use crate::{Cli, config_path, load_config_with_vars, project_config_path};
+use anyhow::{Context, Result};
+use rp_core::{Config, FileWatcher};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use tracing::{debug, info};
+
+/// 設定ファイル、プロジェクトの設定ファイル、共有プリセット、.env の変更を検出して設定を読み込み直す
+pub struct ConfigReloader<'a> {
+    cli: &'a Cli,
+    watcher: FileWatcher,
+}
+
+impl<'a> ConfigReloader<'a> {
+    /// 現在の設定が参照しているファイルの監視を開始
+    pub fn new(cli: &'a Cli, config: &Config) -> Self {
+        let watcher = FileWatcher::new(watched_paths(cli, config));
+        for path in watcher.paths() {
+            debug!("{}", t!("reload.watching", path = path.display()));
+        }
+        Self { cli, watcher }
+    }
+
+    /// 変更があれば設定を読み込み直す（変更がなければ `None`）
+    ///
+    /// .env は読み込み直した内容を既存の環境変数より優先する
+    pub fn poll(&mut self) -> Option<Result<Config>> {
+        let changed = self.watcher.poll();
+        if changed.is_empty() {
+            return None;
+        }
+        for path in &changed {
+            info!("{}", t!("reload.changed", path = path.display()));
+        }
+
+        let result = read_env_files(&self.cli.env_files).and_then(|env| {
+            load_config_with_vars(self.cli, |key| {
+                env.get(key).cloned().or_else(|| std::env::var(key).ok())
+            })
+        });
+
+        // 共有プリセットのファイル構成が変わっていても追従できるよう監視対象を更新する
+        if let Ok(ref config) = result {
+            self.watcher = FileWatcher::new(watched_paths(self.cli, config));
+        }
+
+        Some(result)
+    }
+}
+
+/// 監視対象のファイルとディレクトリ
+fn watched_paths(cli: &Cli, config: &Config) -> Vec<PathBuf> {
+    let mut paths = Vec::new();
+
+    if let Ok(path) = config_path(cli.config.as_deref()) {
+        if let Some(base_dir) = path.parent() {
+            paths.extend(config.preset_files(base_dir).unwrap_or_default());
+            // ディレクトリの更新日時はファイルの追加と削除で変わるため、新しいプリセットファイルも検出できる
+            paths.extend(config.preset_dirs.iter().map(|dir| base_dir.join(dir)));
+        }
+        paths.push(path);
+    }
+    paths.extend(project_config_path(cli));
+    paths.extend(cli.env_files.iter().cloned());
+
+    paths
+}
+
+/// .env ファイルの内容を読み込む（先に指定したファイルの値を優先、ファイルがない場合は空）
+fn read_env_files(paths: &[PathBuf]) -> Result<BTreeMap<String, String>> {
+    let mut env = BTreeMap::new();
+
+    for path in paths.iter().rev().filter(|path| path.exists()) {
+        let values = dotenvy::from_path_iter(path)
+            .and_then(|iter| iter.collect::<Result<BTreeMap<_, _>, _>>())
+            .with_context(|| t!("reload.env_failed", path = path.display()))?;
+        env.extend(values);
+    }
+
+    Ok(env)
+}
@@ -0,0 +1,416 @@
+//! runコマンド（子プロセスの実行中だけアクティビティを表示）
+
+use crate::output::{self, Report};
+use crate::session::ActivitySource;
+use crate::{ActivityArgs, Cli, REFRESH_INTERVAL, daemon, get_app_id};
+use anyhow::{Context, Result};
+use rp_core::{
+    ActivityTemplate, ApplicationId, Config, PresenceSession, Request, RichPresenceClient,
+};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::process::{Child, Command, ExitStatus};
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+/// 子プロセスの終了を確認する間隔
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// 終了時の表示の指定
+pub struct FinalState<'a> {
+    /// 成功した場合の状態テキスト
+    pub success: Option<&'a str>,
+    /// 失敗した場合の状態テキスト
+    pub failure: Option<&'a str>,
+    /// 表示する秒数
+    pub duration: u64,
+}
+
+/// runコマンドの結果
+#[derive(Serialize)]
+struct RunReport<'a> {
+    /// Application IDのインデックス
+    index: u32,
+    /// 使用したApplication ID
+    application_id: ApplicationId,
+    /// 実行したコマンド
+    command: &'a [String],
+    /// 子プロセスの終了コード（シグナルで終了した場合は 128 + シグナル番号）
+    status: u8,
+    /// 子プロセスの実行時間（秒）
+    elapsed_secs: u64,
+    /// デーモンに表示を任せたか
+    daemon: bool,
+}
+
+impl Report for RunReport<'_> {
+    fn print_text(&self) {
+        // 子プロセスの出力を妨げないよう、テキスト形式では何も表示しない
+    }
+}
+
+/// runコマンドの実行
+///
+/// 経過時間付きでアクティビティを表示して子プロセスを実行し、終了したら表示をクリアする。
+/// 受け取ったシグナルは子プロセスに転送し、子プロセスの終了コードで終了する
+pub fn run(
+    cli: &Cli,
+    config: &Config,
+    activity: &ActivityArgs,
+    final_state: &FinalState<'_>,
+    command: &[String],
+) -> Result<()> {
+    let (index, mut template) = ActivitySource::Flags(activity).resolve(cli, config)?;
+    let application_id = get_app_id(config, index)?;
+    template.activity = template.activity.start_timestamp();
+    template = template.in_dir(std::env::current_dir().with_context(|| t!("session.cwd_failed"))?);
+
+    // テンプレートの誤りは子プロセスを起動する前に検出する
+    template
+        .to_ipc_payloads()
+        .with_context(|| t!("cli.payload_failed"))?;
+    for text in [final_state.success, final_state.failure]
+        .into_iter()
+        .flatten()
+    {
+        final_template(&template, text, 0, Duration::ZERO)
+            .render()
+            .with_context(|| t!("cli.render_failed"))?;
+    }
+
+    let name = config
+        .get_application(index)
+        .ok()
+        .and_then(|app| app.name.clone());
+
+    // 起動に失敗した場合に表示が残らないよう、子プロセスを起動してから表示する
+    signals::install();
+    let start = Instant::now();
+    let mut child = Command::new(&command[0])
+        .args(&command[1..])
+        .spawn()
+        .with_context(|| t!("run.spawn_failed", command = command[0]))?;
+    let mut presence = Presence::start(cli, index, application_id, name, template);
+    let status = presence.supervise(&mut child)?;
+    let elapsed = start.elapsed();
+    let code = exit_code(status);
+    info!(
+        "{}",
+        t!(
+            "run.exited",
+            status = code,
+            elapsed = format_elapsed(elapsed)
+        )
+    );
+
+    let text = if status.success() {
+        final_state.success
+    } else {
+        final_state.failure
+    };
+    let daemon = presence.is_daemon();
+    let final_template = text.map(|text| final_template(presence.template(), text, code, elapsed));
+    presence.finish(cli, final_template, final_state.duration);
+
+    output::emit(
+        cli,
+        &RunReport {
+            index,
+            application_id,
+            command,
+            status: code,
+            elapsed_secs: elapsed.as_secs(),
+            daemon,
+        },
+    )?;
+    std::process::exit(code.into())
+}
+
+/// 終了時に表示するテンプレート（状態テキストを置き換え、経過時間とローテーションは表示しない）
+///
+/// テキストでは `{status}`（終了コード）と `{elapsed}`（実行時間、例: `3m12s`）を参照できる
+fn final_template(
+    template: &ActivityTemplate,
+    text: &str,
+    status: u8,
+    elapsed: Duration,
+) -> ActivityTemplate {
+    let mut template = template.clone();
+    template.activity.state = Some(text.to_string());
+    template.activity.timestamps = None;
+    template.rotation = None;
+    template.vars.extend(BTreeMap::from([
+        ("status".to_string(), status.to_string()),
+        ("elapsed".to_string(), format_elapsed(elapsed)),
+    ]));
+    template
+}
+
+/// アクティビティの表示先
+enum Presence {
+    /// デーモンに表示を任せる
+    Daemon {
+        index: u32,
+        application_id: ApplicationId,
+        name: Option<String>,
+        template: ActivityTemplate,
+    },
+    /// 直接 Discord に接続して表示する
+    Direct(Box<PresenceSession>),
+    /// 表示できなかった（子プロセスはそのまま実行する）
+    None(ActivityTemplate),
+}
+
+impl Presence {
+    /// アクティビティの表示を開始
+    ///
+    /// Discord に接続できない場合も子プロセスは実行するため、警告を出して表示せずに続ける
+    fn start(
+        cli: &Cli,
+        index: u32,
+        application_id: ApplicationId,
+        name: Option<String>,
+        template: ActivityTemplate,
+    ) -> Self {
+        let request = Request::Set {
+            index,
+            application_id,
+            name: name.clone(),
+            template: Box::new(template.clone()),
+            duration: None,
+        };
+        match daemon::request(cli, &request) {
+            Ok(Some(_)) => {
+                return Presence::Daemon {
+                    index,
+                    application_id,
+                    name,
+                    template,
+                };
+            }
+            Ok(None) => {}
+            Err(e) => {
+                warn!("{}", t!("run.presence_failed", error = format!("{:#}", e)));
+                return Presence::None(template);
+            }
+        }
+
+        let result = RichPresenceClient::new(application_id).and_then(|mut client| {
+            client.connect()?;
+            let mut session = PresenceSession::new(client, template.clone());
+            session.refresh()?;
+            Ok(session)
+        });
+        match result {
+            Ok(session) => {
+                info!("{}", t!("session.set_log"));
+                Presence::Direct(Box::new(session))
+            }
+            Err(e) => {
+                warn!("{}", t!("run.presence_failed", error = e.full_message()));
+                Presence::None(template)
+            }
+        }
+    }
+
+    /// 表示中のテンプレート
+    fn template(&self) -> &ActivityTemplate {
+        match self {
+            Presence::Daemon { template, .. } | Presence::None(template) => template,
+            Presence::Direct(session) => session.template(),
+        }
+    }
+
+    /// デーモンに表示を任せたか
+    fn is_daemon(&self) -> bool {
+        matches!(self, Presence::Daemon { .. })
+    }
+
+    /// 子プロセスが終了するまで、シグナルを転送しながらテンプレートを再評価する
+    fn supervise(&mut self, child: &mut Child) -> Result<ExitStatus> {
+        let mut last_refresh = Instant::now();
+        loop {
+            if let Some(status) = child.try_wait().with_context(|| t!("run.wait_failed"))? {
+                return Ok(status);
+            }
+            if let Some(signal) = signals::take() {
+                signals::forward(child, signal);
+            }
+
+            if let Presence::Direct(ref mut session) = *self
+                && last_refresh.elapsed() >= REFRESH_INTERVAL
+            {
+                last_refresh = Instant::now();
+                if let Err(e) = session.refresh() {
+                    warn!("{}", t!("session.refresh_failed", error = e.full_message()));
+                }
+            }
+            std::thread::sleep(POLL_INTERVAL);
+        }
+    }
+
+    /// 終了時の表示を `duration` 秒間表示してから（指定しない場合はすぐに）クリアする
+    ///
+    /// デーモンの場合は表示と終了をデーモンに任せてすぐに戻る。
+    /// 待機中にシグナルを受け取った場合は待たずにクリアする
+    fn finish(self, cli: &Cli, final_template: Option<ActivityTemplate>, duration: u64) {
+        match self {
+            Presence::Daemon {
+                index,
+                application_id,
+                name,
+                ..
+            } => {
+                let request = match final_template {
+                    Some(template) => Request::Set {
+                        index,
+                        application_id,
+                        name,
+                        template: Box::new(template),
+                        duration: Some(duration),
+                    },
+                    None => Request::Clear { application_id },
+                };
+                if let Err(e) = daemon::request(cli, &request) {
+                    warn!("{}", t!("run.clear_failed", error = format!("{:#}", e)));
+                }
+            }
+            Presence::Direct(mut session) => {
+                if let Some(template) = final_template {
+                    session.replace_template(template);
+                    flash(&mut session, Duration::from_secs(duration));
+                }
+                let mut client = session.into_client();
+                let result = client.clear_activity().and_then(|()| client.disconnect());
+                if let Err(e) = result {
+                    warn!("{}", t!("run.clear_failed", error = e.full_message()));
+                }
+            }
+            Presence::None(_) => {}
+        }
+    }
+}
+
+/// 終了時の表示を送信し、`duration` の間表示する
+///
+/// 更新間隔の制限中は送信できるまで待つ
+fn flash(session: &mut PresenceSession, duration: Duration) {
+    let mut shown: Option<Instant> = None;
+    loop {
+        if signals::take().is_some() {
+            return;
+        }
+        match shown {
+            Some(at) if at.elapsed() >= duration => return,
+            Some(_) => {}
+            None => match session.refresh() {
+                Ok(true) => shown = Some(Instant::now()),
+                Ok(false) if !session.is_pending() => shown = Some(Instant::now()),
+                Ok(false) => {}
+                Err(e) => {
+                    warn!("{}", t!("session.refresh_failed", error = e.full_message()));
+                    return;
+                }
+            },
+        }
+        std::thread::sleep(POLL_INTERVAL);
+    }
+}
+
+/// 子プロセスの終了コード（シグナルで終了した場合は 128 + シグナル番号）
+fn exit_code(status: ExitStatus) -> u8 {
+    if let Some(code) = status.code() {
+        return code as u8;
+    }
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+        if let Some(signal) = status.signal() {
+            return (128 + signal) as u8;
+        }
+    }
+    1
+}
+
+/// 実行時間を整形（例: `45s`, `3m12s`, `1h2m3s`）
+fn format_elapsed(elapsed: Duration) -> String {
+    let secs = elapsed.as_secs();
+    let (hours, minutes, seconds) = (secs / 3600, secs / 60 % 60, secs % 60);
+    if hours > 0 {
+        format!("{}h{}m{}s", hours, minutes, seconds)
+    } else if minutes > 0 {
+        format!("{}m{}s", minutes, seconds)
+    } else {
+        format!("{}s", seconds)
+    }
+}
+
+#[cfg(unix)]
+mod signals {
+    use std::process::Child;
+    use std::sync::atomic::{AtomicI32, Ordering};
+
+    /// 受け取ったシグナル（まだ処理していない場合）
+    static RECEIVED: AtomicI32 = AtomicI32::new(0);
+
+    /// 子プロセスに転送するシグナル
+    ///
+    /// SIGINT と SIGQUIT は端末から子プロセスにも直接届くため、転送せずに受け取るだけにする
+    const FORWARDED: [libc::c_int; 2] = [libc::SIGTERM, libc::SIGHUP];
+
+    extern "C" fn record(signal: libc::c_int) {
+        RECEIVED.store(signal, Ordering::SeqCst);
+    }
+
+    /// シグナルで終了せず、受け取ったシグナルを記録するようにする
+    pub fn install() {
+        let handler = record as extern "C" fn(libc::c_int) as libc::sighandler_t;
+        for signal in [libc::SIGINT, libc::SIGQUIT].into_iter().chain(FORWARDED) {
+            // SAFETY: ハンドラはアトミック変数への書き込みのみを行う（async-signal-safe）
+            unsafe {
+                libc::signal(signal, handler);
+            }
+        }
+    }
+
+    /// 受け取ったシグナルを取り出す
+    pub fn take() -> Option<i32> {
+        match RECEIVED.swap(0, Ordering::SeqCst) {
+            0 => None,
+            signal => Some(signal),
+        }
+    }
+
+    /// シグナルを子プロセスに転送
+    pub fn forward(child: &Child, signal: i32) {
+        if !FORWARDED.contains(&signal) {
+            return;
+        }
+        // SAFETY: 終了を確認していない子プロセスのプロセスIDに送信する
+        unsafe {
+            libc::kill(child.id() as libc::pid_t, signal);
+        }
+    }
+}
+
+#[cfg(not(unix))]
+mod signals {
+    use std::process::Child;
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    /// Ctrl+C を受け取ったか
+    static RECEIVED: AtomicBool = AtomicBool::new(false);
+
+    /// Ctrl+C で終了せず、受け取ったことを記録するようにする（子プロセスには直接届く）
+    pub fn install() {
+        let _ = ctrlc::set_handler(|| RECEIVED.store(true, Ordering::SeqCst));
+    }
+
+    /// 受け取ったシグナルを取り出す
+    pub fn take() -> Option<i32> {
+        RECEIVED.swap(false, Ordering::SeqCst).then_some(2)
+    }
+
+    /// 子プロセスには直接届くため転送しない
+    pub fn forward(_child: &Child, _signal: i32) {}
+}
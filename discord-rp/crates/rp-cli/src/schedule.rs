@@ -0,0 +1,398 @@
+//! scheduleサブコマンド（時刻によるプリセットの自動切り替え）
+
+use crate::output::{self, OutputFormat, Report};
+use crate::presenter::{Presenter, Target};
+use crate::reload::ConfigReloader;
+use crate::session::ActivitySource;
+use crate::{Cli, REFRESH_INTERVAL, daemon, get_app_id, load_config};
+use anyhow::{Context, Result};
+use chrono::{DateTime, Datelike, Duration as ChronoDuration, NaiveDate, Utc, Weekday};
+use clap::Subcommand;
+use rp_core::{Config, Error, Request, Schedule, ScheduleRule};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::time::Duration;
+use tracing::{info, warn};
+
+#[derive(Subcommand)]
+pub enum ScheduleCommand {
+    /// スケジュールに従ってプリセットを切り替えながら表示（Ctrl+C で終了）
+    Run,
+
+    /// 指定した日のスケジュール（どの時間にどのプリセットを表示するか）を表示
+    Preview {
+        /// 日付（YYYY-MM-DD、today、tomorrow、または mon〜sun で今日以降の最初のその曜日）
+        #[arg(long, value_name = "DAY", default_value = "today")]
+        day: String,
+    },
+}
+
+/// scheduleサブコマンドの実行
+pub fn run(cli: &Cli, command: &ScheduleCommand) -> Result<()> {
+    match command {
+        ScheduleCommand::Run => cmd_run(cli),
+        ScheduleCommand::Preview { day } => cmd_preview(cli, day),
+    }
+}
+
+/// スケジュールの問題を検査（ルールがない場合やプリセットを適用できない場合はエラー）
+fn check_schedule(config: &Config) -> rp_core::Result<()> {
+    if config.schedule.is_empty() {
+        return Err(Error::InvalidSchedule(t!("schedule.empty")));
+    }
+    let problems = config
+        .validate()
+        .into_iter()
+        .filter(|diagnostic| diagnostic.key.first().is_some_and(|key| key == "schedule"))
+        .map(|diagnostic| diagnostic.to_string())
+        .collect::<Vec<_>>();
+    if !problems.is_empty() {
+        return Err(Error::InvalidSchedule(problems.join("; ")));
+    }
+    Ok(())
+}
+
+/// タイムゾーンの表示名
+fn timezone_name(schedule: &Schedule<'_>) -> String {
+    schedule
+        .timezone()
+        .map_or_else(|| t!("schedule.local_timezone"), |tz| tz.to_string())
+}
+
+/// ルールの表示名（例: `#1 work`、`#3 クリア`）
+fn rule_label(index: usize, rule: &ScheduleRule) -> String {
+    match rule.preset {
+        Some(ref preset) => format!("#{} {}", index + 1, preset),
+        None => format!("#{} {}", index + 1, t!("schedule.clear")),
+    }
+}
+
+/// schedule previewの結果
+#[derive(Serialize)]
+struct PreviewReport {
+    /// 日付（YYYY-MM-DD）
+    date: String,
+    /// 曜日（mon〜sun）
+    weekday: String,
+    /// タイムゾーン（システムのタイムゾーンの場合は `None`）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    timezone: Option<String>,
+    /// 時間帯ごとの表示内容（00:00 から 24:00 まで）
+    slots: Vec<SlotReport>,
+    /// タイムゾーンの表示名
+    #[serde(skip)]
+    timezone_name: String,
+    /// 現在の時間帯の番号（表示する日が今日の場合のみ）
+    #[serde(skip)]
+    current: Option<usize>,
+}
+
+/// 時間帯の表示内容
+#[derive(Serialize)]
+struct SlotReport {
+    /// 開始時刻（HH:MM）
+    start: String,
+    /// 終了時刻（HH:MM）
+    end: String,
+    /// 一致したルールの番号（1始まり、どのルールにも一致しない場合は `None`）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    rule: Option<usize>,
+    /// 表示するプリセット（クリアする場合は `None`）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    preset: Option<String>,
+    /// プリセットの変数の値
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    vars: BTreeMap<String, String>,
+}
+
+impl Report for PreviewReport {
+    fn print_text(&self) {
+        println!(
+            "{}",
+            t!(
+                "schedule.preview_header",
+                date = self.date,
+                weekday = t!(&format!("schedule.weekday.{}", self.weekday)),
+                timezone = self.timezone_name
+            )
+        );
+        for (i, slot) in self.slots.iter().enumerate() {
+            let content = match (slot.rule, &slot.preset) {
+                (Some(rule), Some(preset)) => {
+                    let vars = slot
+                        .vars
+                        .iter()
+                        .map(|(name, value)| format!(" {}={}", name, value))
+                        .collect::<String>();
+                    format!("{}{}{}", preset, vars, t!("schedule.rule_ref", rule = rule))
+                }
+                (Some(rule), None) => format!(
+                    "{}{}",
+                    t!("schedule.clear"),
+                    t!("schedule.rule_ref", rule = rule)
+                ),
+                (None, _) => t!("schedule.no_rule"),
+            };
+            let marker = if self.current == Some(i) {
+                t!("schedule.now_marker")
+            } else {
+                String::new()
+            };
+            println!("  {}-{}  {}{}", slot.start, slot.end, content, marker);
+        }
+    }
+}
+
+/// schedule preview: 指定した日のタイムラインを表示
+fn cmd_preview(cli: &Cli, day: &str) -> Result<()> {
+    let config = load_config(cli)?;
+    let schedule = config.schedule();
+    let now = schedule.local_time(Utc::now());
+    let date = parse_day(day, now.date())?;
+
+    let timeline = schedule.timeline(date);
+    let current = (date == now.date())
+        .then(|| {
+            let minute = (now.time() - chrono::NaiveTime::MIN).num_minutes() as u16;
+            timeline
+                .iter()
+                .position(|slot| (slot.start.minutes()..slot.end.minutes()).contains(&minute))
+        })
+        .flatten();
+    let slots = timeline
+        .into_iter()
+        .map(|slot| {
+            let rule = slot.rule.map(|index| &schedule.rules()[index]);
+            SlotReport {
+                start: slot.start.to_string(),
+                end: slot.end.to_string(),
+                rule: slot.rule.map(|index| index + 1),
+                preset: rule.and_then(|rule| rule.preset.clone()),
+                vars: rule.map(|rule| rule.vars.clone()).unwrap_or_default(),
+            }
+        })
+        .collect();
+
+    output::emit(
+        cli,
+        &PreviewReport {
+            date: date.to_string(),
+            weekday: date.weekday().to_string().to_lowercase(),
+            timezone: schedule.timezone().map(|tz| tz.to_string()),
+            slots,
+            timezone_name: timezone_name(&schedule),
+            current,
+        },
+    )
+}
+
+/// `--day` の値をパース（`today`、`tomorrow`、`YYYY-MM-DD`、または `mon`〜`sun`）
+fn parse_day(day: &str, today: NaiveDate) -> Result<NaiveDate> {
+    match day {
+        "today" => return Ok(today),
+        "tomorrow" => return Ok(today + ChronoDuration::days(1)),
+        _ => {}
+    }
+    if let Ok(weekday) = day.parse::<Weekday>() {
+        let offset =
+            (7 + weekday.num_days_from_monday() - today.weekday().num_days_from_monday()) % 7;
+        return Ok(today + ChronoDuration::days(offset.into()));
+    }
+    NaiveDate::parse_from_str(day, "%Y-%m-%d")
+        .with_context(|| t!("schedule.invalid_day", value = day))
+}
+
+/// schedule runを開始したときの結果
+#[derive(Serialize)]
+struct RunReport {
+    /// ルールの数
+    rules: usize,
+    /// タイムゾーン（システムのタイムゾーンの場合は `None`）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    timezone: Option<String>,
+    /// デーモンに表示を任せるか
+    daemon: bool,
+    /// タイムゾーンの表示名
+    #[serde(skip)]
+    timezone_name: String,
+}
+
+impl Report for RunReport {
+    fn print_text(&self) {
+        println!(
+            "{}",
+            t!(
+                "schedule.started",
+                rules = self.rules,
+                timezone = self.timezone_name
+            )
+        );
+    }
+}
+
+/// schedule run: スケジュールに従ってプリセットを切り替えながら Ctrl+C まで表示する
+///
+/// 切り替えの時刻まで待機し、その間もテンプレートを再評価する。
+/// 設定ファイルが変更された場合は読み込み直し、現在の時刻のルールを適用し直す
+fn cmd_run(cli: &Cli) -> Result<()> {
+    let mut config = load_config(cli)?;
+    check_schedule(&config)?;
+
+    let daemon = daemon::request(cli, &Request::Status)?.is_some();
+    let schedule = config.schedule();
+    output::emit(
+        cli,
+        &RunReport {
+            rules: schedule.rules().len(),
+            timezone: schedule.timezone().map(|tz| tz.to_string()),
+            daemon,
+            timezone_name: timezone_name(&schedule),
+        },
+    )?;
+
+    let (tx, rx) = mpsc::channel();
+    ctrlc::set_handler(move || {
+        let _ = tx.send(());
+    })
+    .with_context(|| t!("cli.ctrlc_failed"))?;
+
+    let mut reloader = ConfigReloader::new(cli, &config);
+    let mut scheduler = Scheduler::new(cli, daemon, &config);
+    scheduler.apply(&config, Utc::now(), false);
+
+    loop {
+        // 次の切り替えの時刻か、テンプレートを再評価する時刻まで待機
+        let now = Utc::now();
+        let timeout = config
+            .schedule()
+            .next_change(now)
+            .and_then(|next| (next - now).to_std().ok())
+            .map_or(REFRESH_INTERVAL, |wait| wait.min(REFRESH_INTERVAL));
+        match rx.recv_timeout(timeout) {
+            Err(RecvTimeoutError::Timeout) => {}
+            Ok(()) | Err(RecvTimeoutError::Disconnected) => break,
+        }
+
+        if let Some(reloaded) = reloader.poll() {
+            match reloaded.and_then(|reloaded| {
+                check_schedule(&reloaded)
+                    .map(|()| reloaded)
+                    .map_err(Into::into)
+            }) {
+                Ok(reloaded) => {
+                    config = reloaded;
+                    scheduler.apply(&config, Utc::now(), true);
+                    info!("{}", t!("session.reloaded"));
+                }
+                Err(e) => warn!(
+                    "{}",
+                    t!("session.reload_failed", error = format!("{:#}", e))
+                ),
+            }
+        }
+
+        if config.schedule().rule_at(Utc::now()) != scheduler.rule {
+            scheduler.apply(&config, Utc::now(), false);
+        }
+        scheduler.presenter.tick();
+    }
+
+    scheduler.presenter.stop();
+    if cli.output == OutputFormat::Text {
+        println!("{}", t!("session.finished"));
+    }
+    Ok(())
+}
+
+/// スケジュールの適用状態
+struct Scheduler<'a> {
+    cli: &'a Cli,
+    /// 適用中のルールの番号（どのルールにも一致しない場合は `None`）
+    rule: Option<usize>,
+    presenter: Presenter<'a>,
+}
+
+impl<'a> Scheduler<'a> {
+    fn new(cli: &'a Cli, daemon: bool, config: &Config) -> Self {
+        Self {
+            cli,
+            rule: None,
+            presenter: Presenter::new(cli, daemon, Duration::from_secs(config.reconnect_interval)),
+        }
+    }
+
+    /// 指定した時刻のルールを適用
+    ///
+    /// `reloaded` の場合は設定を読み込み直しただけなので、経過時間の開始時刻を引き継ぐ
+    fn apply(&mut self, config: &Config, now: DateTime<Utc>, reloaded: bool) {
+        let schedule = config.schedule();
+        self.rule = schedule.rule_at(now);
+        self.presenter
+            .set_reconnect_interval(Duration::from_secs(config.reconnect_interval));
+        let until = schedule
+            .next_change(now)
+            .map(|next| schedule.local_time(next).format("%m-%d %H:%M").to_string())
+            .unwrap_or_else(|| "-".to_string());
+
+        let rule = self.rule.map(|index| (index, &schedule.rules()[index]));
+        let target = match rule {
+            Some((index, rule)) if rule.preset.is_some() => match resolve(self.cli, config, rule) {
+                Ok(target) => {
+                    info!(
+                        "{}",
+                        t!(
+                            "schedule.applying",
+                            rule = rule_label(index, rule),
+                            until = until
+                        )
+                    );
+                    Some(target)
+                }
+                Err(e) => {
+                    warn!(
+                        "{}",
+                        t!(
+                            "schedule.apply_failed",
+                            rule = rule_label(index, rule),
+                            error = format!("{:#}", e)
+                        )
+                    );
+                    None
+                }
+            },
+            _ => {
+                info!("{}", t!("schedule.clearing", until = until));
+                None
+            }
+        };
+
+        match target {
+            Some(target) => self.presenter.show(target, reloaded),
+            None => self.presenter.clear(),
+        }
+    }
+}
+
+/// ルールのプリセットを適用するアクティビティを決定
+fn resolve(cli: &Cli, config: &Config, rule: &ScheduleRule) -> Result<Target> {
+    let name = rule.preset.as_deref().unwrap_or_default();
+    let source = ActivitySource::Preset {
+        name,
+        values: rule.vars.clone(),
+    };
+    let (index, template) = source.resolve(cli, config)?;
+    template
+        .to_ipc_payloads()
+        .with_context(|| t!("cli.payload_failed"))?;
+    Ok(Target {
+        index,
+        application_id: get_app_id(config, index)?,
+        name: config
+            .get_application(index)
+            .ok()
+            .and_then(|app| app.name.clone()),
+        template: template
+            .in_dir(std::env::current_dir().with_context(|| t!("session.cwd_failed"))?),
+    })
+}
@@ -0,0 +1,301 @@
+//! アクティビティを表示し続けるコマンド（set / preset apply）の共通処理
+
+use crate::output::{self, OutputFormat, Report};
+use crate::presenter::{Presenter, Target};
+use crate::reload::ConfigReloader;
+use crate::{ActivityArgs, Cli, REFRESH_INTERVAL, daemon, get_app_id};
+use anyhow::{Context, Result};
+use rp_core::{
+    Activity, ActivityTemplate, ApplicationId, Config, PresenceSession, Request, RichPresenceClient,
+};
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+/// 表示内容の決定方法
+///
+/// 設定を読み込み直したときは同じ方法で表示内容を決定し直す
+pub enum ActivitySource<'a> {
+    /// setコマンドのフラグ（設定ファイルのデフォルトのアクティビティに重ねる）
+    Flags(&'a ActivityArgs),
+
+    /// プリセットと変数の値
+    Preset {
+        name: &'a str,
+        values: BTreeMap<String, String>,
+    },
+}
+
+impl ActivitySource<'_> {
+    /// 適用するプリセット名（フラグの場合は `None`）
+    fn preset(&self) -> Option<&str> {
+        match self {
+            ActivitySource::Flags(_) => None,
+            ActivitySource::Preset { name, .. } => Some(name),
+        }
+    }
+
+    /// 使用するApplication IDのインデックスとテンプレートを決定
+    pub fn resolve(&self, cli: &Cli, config: &Config) -> Result<(u32, ActivityTemplate)> {
+        match self {
+            ActivitySource::Flags(args) => {
+                let mut activity = config.activity.clone();
+                activity.merge(&args.build_activity());
+                if args.elapsed {
+                    activity = activity.start_timestamp();
+                }
+                let template = ActivityTemplate::new(activity);
+                Ok((
+                    cli.index(),
+                    match args.build_rotation() {
+                        Some(rotation) => template.with_rotation(rotation),
+                        None => template,
+                    },
+                ))
+            }
+            ActivitySource::Preset { name, values } => {
+                let preset = config.resolve_preset(name)?;
+                let template = preset
+                    .to_template(values)
+                    .with_context(|| t!("session.preset_vars_failed", name = name))?;
+                Ok((cli.index.or(preset.app).unwrap_or(1), template))
+            }
+        }
+    }
+}
+
+/// アクティビティを表示したときの結果
+#[derive(Serialize)]
+struct SetReport<'a> {
+    /// 適用したプリセット
+    #[serde(skip_serializing_if = "Option::is_none")]
+    preset: Option<&'a str>,
+    /// Application IDのインデックス
+    index: u32,
+    /// 使用したApplication ID
+    application_id: ApplicationId,
+    /// 表示したアクティビティ（テンプレートを展開した結果）
+    activity: Activity,
+    /// 表示を維持する秒数（`None` の場合はクリアするまで）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    duration: Option<u64>,
+    /// デーモンに表示を任せたか
+    daemon: bool,
+}
+
+impl Report for SetReport<'_> {
+    fn print_text(&self) {
+        if let Some(preset) = self.preset {
+            println!("{}", t!("session.applying", name = preset));
+        }
+        if self.daemon {
+            println!("{}", t!("session.set_by_daemon", index = self.index));
+        } else {
+            println!("{}", t!("session.set", index = self.index));
+        }
+    }
+}
+
+/// payloadコマンドと set --dry-run の結果
+#[derive(Serialize)]
+struct PayloadReport<'a> {
+    /// 適用したプリセット
+    #[serde(skip_serializing_if = "Option::is_none")]
+    preset: Option<&'a str>,
+    /// Application IDのインデックス
+    index: u32,
+    /// 使用するApplication ID
+    application_id: ApplicationId,
+    /// IPC のオペコード（1: FRAME）
+    opcode: u8,
+    /// 送信する SET_ACTIVITY のフレーム
+    payload: Value,
+}
+
+impl Report for PayloadReport<'_> {
+    fn print_text(&self) {
+        println!("{:#}", self.payload);
+    }
+}
+
+/// 表示する場合と同じ手順（設定の重ね合わせ、テンプレートの展開、Discord の制限の検査）で
+/// 送信する SET_ACTIVITY のフレームを作成して表示する（Discord には接続しない）
+///
+/// ローテーションの場合はすべての順番を検査し、最初に表示するフレームを表示する
+pub fn payload(cli: &Cli, config: &Config, source: ActivitySource<'_>) -> Result<()> {
+    let (index, template) = source.resolve(cli, config)?;
+    let application_id = get_app_id(config, index)?;
+    let payload = template
+        .to_ipc_payloads()
+        .with_context(|| t!("cli.payload_failed"))?
+        .swap_remove(0);
+
+    output::emit(
+        cli,
+        &PayloadReport {
+            preset: source.preset(),
+            index,
+            application_id,
+            opcode: 1,
+            payload,
+        },
+    )
+}
+
+/// アクティビティを表示し、指定時間または Ctrl+C まで維持する
+///
+/// テンプレートは定期的に再評価し、表示内容が変わった場合のみ更新する。
+/// 設定ファイルや .env が変更された場合は読み込み直して反映する。
+/// デーモンが起動している場合は表示をデーモンに任せてすぐに終了する
+pub fn run(
+    cli: &Cli,
+    config: Config,
+    source: ActivitySource<'_>,
+    duration: Option<u64>,
+) -> Result<()> {
+    let (index, template) = source.resolve(cli, &config)?;
+    let app_id = get_app_id(&config, index)?;
+
+    // テンプレートの誤りと Discord の制限を超える内容は接続前に検出する（ローテーションはすべての順番）
+    template
+        .to_ipc_payloads()
+        .with_context(|| t!("cli.payload_failed"))?;
+    let activity = template.render().with_context(|| t!("cli.render_failed"))?;
+    let mut report = SetReport {
+        preset: source.preset(),
+        index,
+        application_id: app_id,
+        activity,
+        duration,
+        daemon: true,
+    };
+
+    let name = config
+        .get_application(index)
+        .ok()
+        .and_then(|app| app.name.clone());
+
+    // デーモンはカレントディレクトリが異なるため、組み込み変数の基準のディレクトリを渡す
+    let request = Request::Set {
+        index,
+        application_id: app_id,
+        name: name.clone(),
+        template: Box::new(
+            template
+                .clone()
+                .in_dir(std::env::current_dir().with_context(|| t!("session.cwd_failed"))?),
+        ),
+        duration,
+    };
+    if daemon::request(cli, &request)?.is_some() {
+        return output::emit(cli, &report);
+    }
+
+    let mut client = RichPresenceClient::new(app_id).with_context(|| t!("cli.client_failed"))?;
+
+    client.connect().with_context(|| t!("cli.connect_failed"))?;
+
+    let mut session = PresenceSession::new(client, template.clone());
+    session
+        .refresh()
+        .with_context(|| t!("session.update_failed"))?;
+    let target = Target {
+        index,
+        application_id: app_id,
+        name,
+        template,
+    };
+    let mut presenter = Presenter::connected(
+        cli,
+        Duration::from_secs(config.reconnect_interval),
+        target,
+        session,
+    );
+
+    info!("{}", t!("session.set_log"));
+    report.daemon = false;
+    output::emit(cli, &report)?;
+
+    let (tx, rx) = mpsc::channel();
+    ctrlc::set_handler(move || {
+        let _ = tx.send(());
+    })
+    .with_context(|| t!("cli.ctrlc_failed"))?;
+
+    let mut reloader = ConfigReloader::new(cli, &config);
+
+    // 指定時間または Ctrl+C まで、テンプレートを再評価しながら待機
+    let deadline = duration.map(|secs| Instant::now() + Duration::from_secs(secs));
+    loop {
+        let timeout = match deadline {
+            Some(deadline) => {
+                let remaining = deadline.saturating_duration_since(Instant::now());
+                if remaining.is_zero() {
+                    break;
+                }
+                remaining.min(REFRESH_INTERVAL)
+            }
+            None => REFRESH_INTERVAL,
+        };
+
+        match rx.recv_timeout(timeout) {
+            Err(RecvTimeoutError::Timeout) => {}
+            Ok(()) | Err(RecvTimeoutError::Disconnected) => break,
+        }
+
+        if let Some(reloaded) = reloader.poll() {
+            apply_reloaded(cli, &source, &mut presenter, reloaded);
+        }
+
+        // Discord が再起動して接続が失われた場合は、再接続の間隔ごとに接続し直す
+        presenter.tick();
+    }
+
+    presenter.stop();
+    if cli.output == OutputFormat::Text {
+        println!("{}", t!("session.finished"));
+    }
+    Ok(())
+}
+
+/// 読み込み直した設定を検証し、問題がなければ表示を差し替える
+///
+/// Application IDが変わった場合は表示中のアクティビティを消してから接続し直す
+fn apply_reloaded(
+    cli: &Cli,
+    source: &ActivitySource<'_>,
+    presenter: &mut Presenter<'_>,
+    reloaded: Result<Config>,
+) {
+    let result = reloaded.and_then(|config| {
+        let (index, template) = source.resolve(cli, &config)?;
+        // 表示の開始時と同じく、Discord の制限を超える内容は差し替えない
+        template
+            .to_ipc_payloads()
+            .with_context(|| t!("cli.payload_failed"))?;
+        presenter.set_reconnect_interval(Duration::from_secs(config.reconnect_interval));
+        Ok(Target {
+            index,
+            application_id: get_app_id(&config, index)?,
+            name: config
+                .get_application(index)
+                .ok()
+                .and_then(|app| app.name.clone()),
+            template,
+        })
+    });
+
+    match result {
+        Ok(target) => {
+            presenter.show(target, true);
+            info!("{}", t!("session.reloaded"));
+        }
+        Err(e) => warn!(
+            "{}",
+            t!("session.reload_failed", error = format!("{:#}", e))
+        ),
+    }
+}
@@ -0,0 +1,209 @@
+//! statusコマンド（デーモンが Discord に表示中の内容）
+
+use crate::output::{self, Report};
+use crate::{Cli, daemon};
+use anyhow::Result;
+use chrono::{DateTime, Local};
+use rp_core::{Activity, Request, Response, SessionStatus, daemon_socket_path};
+use serde::Serialize;
+use std::path::PathBuf;
+
+/// statusコマンドの出力
+#[derive(Serialize)]
+struct StatusReport {
+    /// デーモンが起動しているか
+    running: bool,
+    /// デーモンのソケットのパス
+    socket: PathBuf,
+    /// デーモンのプロセスID
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pid: Option<u32>,
+    /// 表示中のアクティビティ（Application IDごと）
+    sessions: Vec<SessionStatus>,
+}
+
+impl Report for StatusReport {
+    fn print_text(&self) {
+        print_report(self);
+    }
+}
+
+/// statusコマンドの実行
+pub fn run(cli: &Cli) -> Result<()> {
+    let report = match daemon::request(cli, &Request::Status)? {
+        Some(Response::Status(status)) => StatusReport {
+            running: true,
+            socket: daemon_socket_path(),
+            pid: Some(status.pid),
+            sessions: status.sessions,
+        },
+        Some(response) => anyhow::bail!(t!(
+            "status.unexpected_response",
+            response = format!("{:?}", response)
+        )),
+        None => StatusReport {
+            running: false,
+            socket: daemon_socket_path(),
+            pid: None,
+            sessions: Vec::new(),
+        },
+    };
+
+    output::emit(cli, &report)
+}
+
+/// 状態を人が読む形式で表示
+fn print_report(report: &StatusReport) {
+    let Some(pid) = report.pid else {
+        println!("{}", t!("status.not_running"));
+        println!();
+        println!("{}", t!("status.daemon_only"));
+        println!("{}", t!("status.start_hint"));
+        return;
+    };
+
+    println!(
+        "{}",
+        t!(
+            "status.running",
+            pid = pid,
+            socket = report.socket.display()
+        )
+    );
+
+    if report.sessions.is_empty() {
+        println!();
+        println!("{}", t!("status.no_activity"));
+        return;
+    }
+
+    for session in &report.sessions {
+        println!();
+        print_session(session);
+    }
+}
+
+/// Application IDごとの状態を表示
+fn print_session(session: &SessionStatus) {
+    let name = session
+        .name
+        .as_ref()
+        .map(|name| format!("{} ", name))
+        .unwrap_or_default();
+    println!(
+        "[{}] {}{}",
+        session.index,
+        name,
+        session.application_id.masked()
+    );
+
+    if let Some(ref user) = session.user {
+        let account = match user.global_name {
+            Some(ref global_name) => t!(
+                "status.account_with_name",
+                name = global_name,
+                username = user.username
+            ),
+            None => t!("status.account", username = user.username),
+        };
+        println!("  {}", account);
+    }
+    let connection = if session.connected {
+        t!("status.connected")
+    } else {
+        t!("status.disconnected")
+    };
+    println!("  {}", t!("status.connection", state = connection));
+
+    match session.activity {
+        Some(ref activity) => {
+            println!("  {}", t!("status.activity"));
+            print_activity(activity);
+        }
+        None => println!("  {}", t!("status.activity_none")),
+    }
+    if session.pending {
+        println!("  {}", t!("status.pending"));
+    }
+
+    if session.next_update_secs == 0 {
+        println!("  {}", t!("status.next_update_now"));
+    } else {
+        println!(
+            "  {}",
+            t!("status.next_update", seconds = session.next_update_secs)
+        );
+    }
+    if let Some(secs) = session.remaining_secs {
+        println!("  {}", t!("status.remaining", seconds = secs));
+    }
+
+    if let Some(ref error) = session.last_error {
+        println!("  {}", t!("status.last_error", error = error));
+    }
+    if let Some(ref reconnect) = session.reconnect {
+        let next = match reconnect.next_attempt_secs {
+            Some(secs) => t!("status.next_reconnect", seconds = secs),
+            None => t!("status.reconnect_disabled"),
+        };
+        println!(
+            "  {}",
+            t!(
+                "status.reconnect",
+                attempts = reconnect.attempts,
+                next = next,
+                error = reconnect.last_error
+            )
+        );
+    }
+}
+
+/// アクティビティの各項目を表示
+fn print_activity(activity: &Activity) {
+    if let Some(ref details) = activity.details {
+        println!("    {}", t!("status.details", details = details));
+    }
+    if let Some(ref state) = activity.state {
+        println!("    {}", t!("status.state", state = state));
+    }
+    if let Some(ref timestamps) = activity.timestamps {
+        if let Some(start) = timestamps.start {
+            println!("    {}", t!("status.start", time = format_timestamp(start)));
+        }
+        if let Some(end) = timestamps.end {
+            println!("    {}", t!("status.end", time = format_timestamp(end)));
+        }
+    }
+    if let Some([current, max]) = activity.party.as_ref().and_then(|party| party.size) {
+        println!("    {}", t!("status.party", current = current, max = max));
+    }
+    if let Some(ref assets) = activity.assets {
+        if let Some(ref key) = assets.large_image {
+            let image = with_text(key, assets.large_text.as_deref());
+            println!("    {}", t!("status.large_image", image = image));
+        }
+        if let Some(ref key) = assets.small_image {
+            let image = with_text(key, assets.small_text.as_deref());
+            println!("    {}", t!("status.small_image", image = image));
+        }
+    }
+}
+
+/// 画像のキーにツールチップを添える
+fn with_text(key: &str, text: Option<&str>) -> String {
+    match text {
+        Some(text) => t!("status.image_with_text", key = key, text = text),
+        None => key.to_string(),
+    }
+}
+
+/// Unixタイムスタンプをローカル時刻で整形
+fn format_timestamp(secs: i64) -> String {
+    DateTime::from_timestamp(secs, 0)
+        .map(|time| {
+            time.with_timezone(&Local)
+                .format("%Y-%m-%d %H:%M:%S")
+                .to_string()
+        })
+        .unwrap_or_else(|| secs.to_string())
+}
@@ -1,16 +1,37 @@
 //! Discord Rich Presence アクティビティの定義
 
+use crate::template::{self, Template, Variables};
+use crate::{BuiltinVariables, Diagnostic, Error, Result, Rotation, RotationTimer};
+use discord_rich_presence::activity as discord_activity;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use uuid::Uuid;
+
+/// テキスト項目の最小文字数（Discord の制限）
+const MIN_TEXT_LEN: usize = 2;
+
+/// テキスト項目の最大文字数（Discord の制限）
+const MAX_TEXT_LEN: usize = 128;
+
+/// 画像キーの最大文字数（Discord の制限）
+const MAX_IMAGE_KEY_LEN: usize = 256;
 
 /// Rich Presenceのアクティビティ設定
-#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[schemars(deny_unknown_fields)]
 pub struct Activity {
     /// 詳細テキスト（1行目）
     #[serde(skip_serializing_if = "Option::is_none")]
+    #[schemars(length(min = MIN_TEXT_LEN, max = MAX_TEXT_LEN))]
     pub details: Option<String>,
 
     /// 状態テキスト（2行目）
     #[serde(skip_serializing_if = "Option::is_none")]
+    #[schemars(length(min = MIN_TEXT_LEN, max = MAX_TEXT_LEN))]
     pub state: Option<String>,
 
     /// タイムスタンプ設定
@@ -20,10 +41,15 @@ pub struct Activity {
     /// アセット設定（画像）
     #[serde(skip_serializing_if = "Option::is_none")]
     pub assets: Option<ActivityAssets>,
+
+    /// パーティー設定（人数は状態テキストの後に「(2 of 4)」のように表示される）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub party: Option<ActivityParty>,
 }
 
 /// タイムスタンプ設定
-#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[schemars(deny_unknown_fields)]
 pub struct ActivityTimestamps {
     /// 開始時刻（Unixタイムスタンプ、秒）
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -34,23 +60,41 @@ pub struct ActivityTimestamps {
     pub end: Option<i64>,
 }
 
+/// パーティー設定
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[schemars(deny_unknown_fields)]
+pub struct ActivityParty {
+    /// パーティーのID
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+
+    /// 現在の人数と最大人数（例: `[2, 4]`）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub size: Option<[u32; 2]>,
+}
+
 /// アセット設定（画像）
-#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[schemars(deny_unknown_fields)]
 pub struct ActivityAssets {
     /// 大きい画像のキー（Developer Portalで設定した名前）
     #[serde(skip_serializing_if = "Option::is_none")]
+    #[schemars(length(max = MAX_IMAGE_KEY_LEN))]
     pub large_image: Option<String>,
 
     /// 大きい画像のツールチップテキスト
     #[serde(skip_serializing_if = "Option::is_none")]
+    #[schemars(length(min = MIN_TEXT_LEN, max = MAX_TEXT_LEN))]
     pub large_text: Option<String>,
 
     /// 小さい画像のキー
     #[serde(skip_serializing_if = "Option::is_none")]
+    #[schemars(length(max = MAX_IMAGE_KEY_LEN))]
     pub small_image: Option<String>,
 
     /// 小さい画像のツールチップテキスト
     #[serde(skip_serializing_if = "Option::is_none")]
+    #[schemars(length(min = MIN_TEXT_LEN, max = MAX_TEXT_LEN))]
     pub small_text: Option<String>,
 }
 
@@ -74,14 +118,7 @@ impl Activity {
 
     /// 開始時刻を現在時刻に設定
     pub fn start_timestamp(mut self) -> Self {
-        let now = std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .unwrap()
-            .as_secs() as i64;
-        self.timestamps = Some(ActivityTimestamps {
-            start: Some(now),
-            end: None,
-        });
+        self.timestamps = Some(ActivityTimestamps::from_now());
         self
     }
 
@@ -105,6 +142,13 @@ impl Activity {
         self
     }
 
+    /// パーティーの人数（現在の人数と最大人数）を設定
+    pub fn party_size(mut self, current: u32, max: u32) -> Self {
+        let party = self.party.get_or_insert_with(ActivityParty::default);
+        party.size = Some([current, max]);
+        self
+    }
+
     /// 小さい画像を設定
     pub fn small_image(mut self, key: impl Into<String>, text: Option<String>) -> Self {
         let assets = self.assets.get_or_insert_with(ActivityAssets::default);
@@ -112,18 +156,429 @@ impl Activity {
         assets.small_text = text;
         self
     }
+
+    /// 別のアクティビティの設定を重ねる（`overlay` で指定された項目が優先）
+    pub fn merge(&mut self, overlay: &Activity) {
+        if overlay.details.is_some() {
+            self.details = overlay.details.clone();
+        }
+        if overlay.state.is_some() {
+            self.state = overlay.state.clone();
+        }
+        if overlay.timestamps.is_some() {
+            self.timestamps = overlay.timestamps.clone();
+        }
+        if let Some(ref overlay_assets) = overlay.assets {
+            let assets = self.assets.get_or_insert_with(ActivityAssets::default);
+            if overlay_assets.large_image.is_some() {
+                assets.large_image = overlay_assets.large_image.clone();
+            }
+            if overlay_assets.large_text.is_some() {
+                assets.large_text = overlay_assets.large_text.clone();
+            }
+            if overlay_assets.small_image.is_some() {
+                assets.small_image = overlay_assets.small_image.clone();
+            }
+            if overlay_assets.small_text.is_some() {
+                assets.small_text = overlay_assets.small_text.clone();
+            }
+        }
+        if let Some(ref overlay_party) = overlay.party {
+            let party = self.party.get_or_insert_with(ActivityParty::default);
+            if overlay_party.id.is_some() {
+                party.id = overlay_party.id.clone();
+            }
+            if overlay_party.size.is_some() {
+                party.size = overlay_party.size;
+            }
+        }
+    }
+
+    /// テキスト項目（details, state, 画像キー, ツールチップ）を変換した新しいアクティビティを作成
+    pub fn try_map_text(&self, mut f: impl FnMut(&str) -> Result<String>) -> Result<Self> {
+        let mut map = |value: &Option<String>| value.as_deref().map(&mut f).transpose();
+
+        let assets = match self.assets {
+            Some(ref assets) => Some(ActivityAssets {
+                large_image: map(&assets.large_image)?,
+                large_text: map(&assets.large_text)?,
+                small_image: map(&assets.small_image)?,
+                small_text: map(&assets.small_text)?,
+            }),
+            None => None,
+        };
+
+        Ok(Self {
+            details: map(&self.details)?,
+            state: map(&self.state)?,
+            timestamps: self.timestamps.clone(),
+            assets,
+            party: self.party.clone(),
+        })
+    }
+
+    /// テキスト項目を順に走査
+    pub fn texts(&self) -> impl Iterator<Item = &str> {
+        self.text_fields().map(|(_, text)| text)
+    }
+
+    /// テキスト項目をキーのパス（例: `["assets", "large_text"]`）とともに走査
+    pub fn text_fields(&self) -> impl Iterator<Item = (&'static [&'static str], &str)> {
+        let assets = self.assets.as_ref();
+        [
+            (&["details"][..], self.details.as_deref()),
+            (&["state"], self.state.as_deref()),
+            (
+                &["assets", "large_image"],
+                assets.and_then(|a| a.large_image.as_deref()),
+            ),
+            (
+                &["assets", "large_text"],
+                assets.and_then(|a| a.large_text.as_deref()),
+            ),
+            (
+                &["assets", "small_image"],
+                assets.and_then(|a| a.small_image.as_deref()),
+            ),
+            (
+                &["assets", "small_text"],
+                assets.and_then(|a| a.small_text.as_deref()),
+            ),
+        ]
+        .into_iter()
+        .filter_map(|(key, text)| Some((key, text?)))
+    }
+
+    /// Discord の制限（文字数、タイムスタンプの順序）を満たしているか検査
+    ///
+    /// 変数を含む項目は展開後の文字数が決まらないため検査しない
+    pub fn check_limits(&self) -> Vec<Diagnostic> {
+        self.check_limits_with(|text| {
+            let template = Template::parse(text).ok()?;
+            if !template.is_static() {
+                return None;
+            }
+            template.render(&BTreeMap::<String, String>::new()).ok()
+        })
+    }
+
+    /// Discord の制限を検査（`resolve` が `None` を返したテキスト項目は文字数を検査しない）
+    fn check_limits_with(&self, resolve: impl Fn(&str) -> Option<String>) -> Vec<Diagnostic> {
+        let mut diagnostics = Vec::new();
+
+        for (key, text) in self.text_fields() {
+            let Some(text) = resolve(text) else {
+                continue;
+            };
+            let len = text.chars().count();
+
+            let is_image_key = key.last().is_some_and(|k| k.ends_with("_image"));
+            if is_image_key && len > MAX_IMAGE_KEY_LEN {
+                diagnostics.push(Diagnostic::new(
+                    key.iter().copied(),
+                    t!(
+                        "diagnostic.image_key_too_long",
+                        max = MAX_IMAGE_KEY_LEN,
+                        len = len
+                    ),
+                ));
+            } else if !is_image_key && !(MIN_TEXT_LEN..=MAX_TEXT_LEN).contains(&len) {
+                diagnostics.push(Diagnostic::new(
+                    key.iter().copied(),
+                    t!(
+                        "diagnostic.text_length",
+                        min = MIN_TEXT_LEN,
+                        max = MAX_TEXT_LEN,
+                        len = len
+                    ),
+                ));
+            }
+        }
+
+        if let Some(ActivityTimestamps {
+            start: Some(start),
+            end: Some(end),
+        }) = self.timestamps
+            && end < start
+        {
+            diagnostics.push(Diagnostic::new(
+                ["timestamps", "end"],
+                t!("diagnostic.end_before_start"),
+            ));
+        }
+
+        if let Some(ActivityParty {
+            size: Some([current, max]),
+            ..
+        }) = self.party
+            && (current == 0 || current > max)
+        {
+            diagnostics.push(Diagnostic::new(
+                ["party", "size"],
+                t!("diagnostic.party_size", current = current, max = max),
+            ));
+        }
+
+        diagnostics
+    }
+
+    /// Discord に送信する SET_ACTIVITY のフレーム（JSON）を作成
+    ///
+    /// テキストはテンプレートとして扱わず、そのまま Discord の制限を検査する。
+    /// [`RichPresenceClient::update_activity`](crate::RichPresenceClient::update_activity) はこのフレームを送信する。
+    /// `pid` は現在のプロセスID、`nonce` は呼び出すたびに新しく生成する
+    pub fn to_ipc_payload(&self) -> Result<Value> {
+        let diagnostics = self.check_limits_with(|text| Some(text.to_string()));
+        if !diagnostics.is_empty() {
+            return Err(Error::InvalidActivity(diagnostics));
+        }
+
+        Ok(json!({
+            "cmd": "SET_ACTIVITY",
+            "args": {
+                "pid": std::process::id(),
+                "activity": self.to_discord_activity(),
+            },
+            "nonce": Uuid::new_v4().to_string(),
+        }))
+    }
+
+    /// discord-rich-presence の Activity 型に変換
+    fn to_discord_activity(&self) -> discord_activity::Activity<'_> {
+        let mut da = discord_activity::Activity::new();
+
+        if let Some(ref details) = self.details {
+            da = da.details(details);
+        }
+
+        if let Some(ref state) = self.state {
+            da = da.state(state);
+        }
+
+        if let Some(ref ts) = self.timestamps {
+            let mut timestamps = discord_activity::Timestamps::new();
+            if let Some(start) = ts.start {
+                timestamps = timestamps.start(start);
+            }
+            if let Some(end) = ts.end {
+                timestamps = timestamps.end(end);
+            }
+            da = da.timestamps(timestamps);
+        }
+
+        if let Some(ref assets) = self.assets {
+            let mut discord_assets = discord_activity::Assets::new();
+            if let Some(ref large_image) = assets.large_image {
+                discord_assets = discord_assets.large_image(large_image);
+            }
+            if let Some(ref large_text) = assets.large_text {
+                discord_assets = discord_assets.large_text(large_text);
+            }
+            if let Some(ref small_image) = assets.small_image {
+                discord_assets = discord_assets.small_image(small_image);
+            }
+            if let Some(ref small_text) = assets.small_text {
+                discord_assets = discord_assets.small_text(small_text);
+            }
+            da = da.assets(discord_assets);
+        }
+
+        if let Some(ref party) = self.party {
+            let mut discord_party = discord_activity::Party::new();
+            if let Some(ref id) = party.id {
+                discord_party = discord_party.id(id);
+            }
+            if let Some([current, max]) = party.size {
+                let clamp = |n: u32| i32::try_from(n).unwrap_or(i32::MAX);
+                discord_party = discord_party.size([clamp(current), clamp(max)]);
+            }
+            da = da.party(discord_party);
+        }
+
+        da
+    }
+
+    /// テキスト項目のテンプレートの書式と、参照している変数が宣言されているかを検査
+    pub(crate) fn check_templates(&self, is_declared: impl Fn(&str) -> bool) -> Vec<Diagnostic> {
+        let mut diagnostics = Vec::new();
+
+        for (key, text) in self.text_fields() {
+            match Template::parse(text) {
+                Ok(template) => {
+                    for name in template.variables() {
+                        if !is_declared(name) && !BuiltinVariables::is_builtin(name) {
+                            diagnostics.push(Diagnostic::new(
+                                key.iter().copied(),
+                                t!("diagnostic.undeclared_variable", name = name),
+                            ));
+                        }
+                    }
+                }
+                Err(e) => diagnostics.push(Diagnostic::new(key.iter().copied(), e.to_string())),
+            }
+        }
+
+        diagnostics
+    }
 }
 
 impl ActivityTimestamps {
     /// 現在時刻から開始するタイムスタンプを作成
     pub fn from_now() -> Self {
-        let now = std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .unwrap()
-            .as_secs() as i64;
         Self {
-            start: Some(now),
+            start: Some(unix_now()),
             end: None,
         }
     }
 }
+
+/// 現在時刻（Unixタイムスタンプ、秒）
+fn unix_now() -> i64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_secs() as i64
+}
+
+/// テンプレートを含むアクティビティと、その変数の値
+///
+/// テキスト項目は表示の直前に [`render`](Self::render) で展開する
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ActivityTemplate {
+    /// テンプレートを含むアクティビティ
+    pub activity: Activity,
+
+    /// 変数の値（組み込み変数より優先）
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub vars: BTreeMap<String, String>,
+
+    /// 組み込み変数 `cwd` と `git.*` の基準とするディレクトリ（`None` の場合はカレントディレクトリ）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dir: Option<PathBuf>,
+
+    /// 順に切り替えて `activity` に重ねるアクティビティ（`None` の場合は切り替えない）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rotation: Option<Rotation>,
+
+    /// ローテーションを開始した時刻（Unixタイムスタンプ、秒）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rotation_start: Option<i64>,
+}
+
+impl ActivityTemplate {
+    /// 新しいテンプレートを作成
+    pub fn new(activity: Activity) -> Self {
+        Self {
+            activity,
+            vars: BTreeMap::new(),
+            dir: None,
+            rotation: None,
+            rotation_start: None,
+        }
+    }
+
+    /// 変数の値を設定
+    pub fn with_vars(mut self, vars: BTreeMap<String, String>) -> Self {
+        self.vars = vars;
+        self
+    }
+
+    /// 組み込み変数の基準とするディレクトリを設定
+    pub fn in_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.dir = Some(dir.into());
+        self
+    }
+
+    /// ローテーションを設定し、現在時刻から切り替えを始める（アクティビティが空の場合は切り替えない）
+    pub fn with_rotation(mut self, rotation: Rotation) -> Self {
+        if rotation.activities.is_empty() {
+            return self;
+        }
+        self.rotation = Some(rotation);
+        self.rotation_start = Some(unix_now());
+        self
+    }
+
+    /// 指定した時刻（Unixタイムスタンプ、秒）に表示するアクティビティ（展開前）
+    ///
+    /// ローテーションの場合は現在の順番のアクティビティを重ねる。
+    /// 経過時間の扱いが `reset` の場合は、開始時刻を最後に切り替えた時刻にする
+    pub fn activity_at(&self, now: i64) -> Cow<'_, Activity> {
+        let Some(ref rotation) = self.rotation else {
+            return Cow::Borrowed(&self.activity);
+        };
+        if rotation.activities.is_empty() {
+            return Cow::Borrowed(&self.activity);
+        }
+
+        let start = self.rotation_start.unwrap_or(now);
+        let round = rotation.round_at(now.saturating_sub(start).max(0) as u64);
+        let len = rotation.activities.len() as u64;
+        let mut activity = self.activity.clone();
+        activity.merge(&rotation.activities[(round % len) as usize]);
+
+        if rotation.timer == RotationTimer::Reset
+            && let Some(ref mut timestamps) = activity.timestamps
+            && timestamps.start.is_some()
+        {
+            let switched = round * rotation.effective_interval().as_secs();
+            timestamps.start = Some(start + switched as i64);
+        }
+        Cow::Owned(activity)
+    }
+
+    /// 変数の値と組み込み変数でテンプレートを展開
+    pub fn render(&self) -> Result<Activity> {
+        self.render_with(&self.builtins())
+    }
+
+    /// 変数の値と指定した変数でテンプレートを展開
+    pub fn render_with(&self, builtins: &dyn Variables) -> Result<Activity> {
+        let vars = (&self.vars, builtins);
+        self.activity_at(unix_now())
+            .try_map_text(|text| template::render(text, &vars))
+    }
+
+    /// ローテーションのすべてのアクティビティを順に展開（ローテーションでない場合は1つ）
+    ///
+    /// 表示する前に、どの順番のアクティビティも展開できて Discord の制限を満たすかを検査するために使用する
+    pub fn render_all(&self) -> Result<Vec<Activity>> {
+        let builtins = self.builtins();
+        let vars = (&self.vars, &builtins as &dyn Variables);
+        let start = self.rotation_start.unwrap_or_else(unix_now);
+        let interval = self
+            .rotation
+            .as_ref()
+            .map_or(0, |rotation| rotation.effective_interval().as_secs());
+        let len = self
+            .rotation
+            .as_ref()
+            .map_or(1, |rotation| rotation.activities.len().max(1));
+
+        (0..len)
+            .map(|round| {
+                self.activity_at(start + (round as u64 * interval) as i64)
+                    .try_map_text(|text| template::render(text, &vars))
+            })
+            .collect()
+    }
+
+    /// ローテーションのすべての順番について、Discord に送信する SET_ACTIVITY のフレームを作成
+    ///
+    /// [`render_all`](Self::render_all) で展開し、Discord の制限を検査してからフレームを作成する。
+    /// 表示する前の検査と、送信する内容の確認（`set --dry-run`）に使用する
+    pub fn to_ipc_payloads(&self) -> Result<Vec<Value>> {
+        self.render_all()?
+            .iter()
+            .map(Activity::to_ipc_payload)
+            .collect()
+    }
+
+    /// 組み込み変数（`dir` を基準とする）
+    fn builtins(&self) -> BuiltinVariables {
+        match self.dir {
+            Some(ref dir) => BuiltinVariables::in_dir(dir),
+            None => BuiltinVariables::new(),
+        }
+    }
+}
@@ -0,0 +1,172 @@
+//! Discord Application ID の定義
+
+use crate::{Error, Result};
+use schemars::{JsonSchema, Schema, SchemaGenerator, json_schema};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::borrow::Cow;
+use std::fmt;
+use std::str::FromStr;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Discord エポック（2015-01-01T00:00:00Z、Unixミリ秒）
+const DISCORD_EPOCH_MILLIS: u64 = 1_420_070_400_000;
+
+/// Snowflake の最小桁数
+const MIN_DIGITS: usize = 17;
+
+/// Snowflake の最大桁数
+const MAX_DIGITS: usize = 20;
+
+/// 検証済みの Discord Application ID（Snowflake）
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ApplicationId(u64);
+
+impl ApplicationId {
+    /// 文字列から Application ID をパース
+    pub fn parse(value: &str) -> Result<Self> {
+        if value.is_empty() {
+            return Err(Error::InvalidApplicationId(t!("app_id.empty")));
+        }
+
+        if !value.bytes().all(|b| b.is_ascii_digit()) {
+            return Err(Error::InvalidApplicationId(t!(
+                "app_id.not_digits",
+                value = format!("{:?}", value)
+            )));
+        }
+
+        if !(MIN_DIGITS..=MAX_DIGITS).contains(&value.len()) {
+            return Err(Error::InvalidApplicationId(t!(
+                "app_id.length",
+                value = format!("{:?}", value),
+                min = MIN_DIGITS,
+                max = MAX_DIGITS,
+                len = value.len()
+            )));
+        }
+
+        value.parse::<u64>().map(Self).map_err(|_| {
+            Error::InvalidApplicationId(t!("app_id.out_of_range", value = format!("{:?}", value)))
+        })
+    }
+
+    /// 数値としての値を取得
+    pub fn get(&self) -> u64 {
+        self.0
+    }
+
+    /// 作成日時（Unixミリ秒）を取得
+    pub fn timestamp_millis(&self) -> u64 {
+        (self.0 >> 22) + DISCORD_EPOCH_MILLIS
+    }
+
+    /// 作成日時を取得
+    pub fn created_at(&self) -> SystemTime {
+        UNIX_EPOCH + Duration::from_millis(self.timestamp_millis())
+    }
+
+    /// 先頭と末尾4桁以外を隠した文字列を取得
+    pub fn masked(&self) -> String {
+        let s = self.to_string();
+        format!("{}...{}", &s[..4], &s[s.len() - 4..])
+    }
+}
+
+impl fmt::Display for ApplicationId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl FromStr for ApplicationId {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Self::parse(s)
+    }
+}
+
+impl Serialize for ApplicationId {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl JsonSchema for ApplicationId {
+    fn schema_name() -> Cow<'static, str> {
+        "ApplicationId".into()
+    }
+
+    /// 17〜20桁の数字の文字列（`${VAR}` と `file:` の参照も可）
+    fn json_schema(_generator: &mut SchemaGenerator) -> Schema {
+        json_schema!({
+            "type": "string",
+            "description": "Discord Application ID（17〜20桁の数字）。${VAR} や file: の参照も使用可",
+            "pattern": format!(r"^([0-9]{{{},{}}}|file:.+|.*\$\{{[^}}]+\}}.*)$", MIN_DIGITS, MAX_DIGITS),
+        })
+    }
+}
+
+impl<'de> Deserialize<'de> for ApplicationId {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        let value = String::deserialize(deserializer)?;
+        Self::parse(&value).map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_accepts_snowflake() {
+        let id = ApplicationId::parse("123456789012345678").unwrap();
+        assert_eq!(id.get(), 123_456_789_012_345_678);
+        assert_eq!(id.to_string(), "123456789012345678");
+        assert_eq!(id.masked(), "1234...5678");
+        assert_eq!(
+            "12345678901234567".parse::<ApplicationId>().unwrap().get(),
+            12_345_678_901_234_567
+        );
+    }
+
+    #[test]
+    fn parse_rejects_invalid_values() {
+        for value in [
+            "",
+            "12345678901234567a",
+            " 123456789012345678",
+            "-123456789012345678",
+            "1234567890123456",
+            "123456789012345678901",
+            // u64 の範囲外（20桁）
+            "18446744073709551616",
+        ] {
+            assert!(
+                matches!(
+                    ApplicationId::parse(value),
+                    Err(Error::InvalidApplicationId(_))
+                ),
+                "{value:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn timestamp_is_decoded_from_snowflake() {
+        // 上位42ビットが Discord エポックからの経過ミリ秒
+        let id = ApplicationId::parse("175928847299117063").unwrap();
+        assert_eq!(id.timestamp_millis(), 1_462_015_105_796);
+        assert_eq!(
+            id.created_at(),
+            UNIX_EPOCH + Duration::from_millis(1_462_015_105_796)
+        );
+    }
+
+    #[test]
+    fn deserialize_validates_value() {
+        let id: ApplicationId = serde_json::from_str("\"123456789012345678\"").unwrap();
+        assert_eq!(id.get(), 123_456_789_012_345_678);
+        assert!(serde_json::from_str::<ApplicationId>("\"abc\"").is_err());
+    }
+}
@@ -1,46 +1,59 @@
 //! Discord Rich Presence クライアント
 
-use crate::{Activity, Error, Result};
-use discord_rich_presence::{activity as discord_activity, DiscordIpc, DiscordIpcClient};
-use std::time::Instant;
+use crate::error::IpcError;
+use crate::{Activity, ApplicationId, Error, Result};
+use discord_rich_presence::{DiscordIpc, DiscordIpcClient};
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use std::io::ErrorKind;
+use std::time::{Duration, Instant};
 use tracing::{debug, error, info, warn};
 
 /// 最小更新間隔（秒）
 const MIN_UPDATE_INTERVAL_SECS: u64 = 15;
 
+/// 最小更新間隔
+pub(crate) const MIN_UPDATE_INTERVAL: Duration = Duration::from_secs(MIN_UPDATE_INTERVAL_SECS);
+
+/// Discord にログインしているユーザー（接続時に Discord から通知される）
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DiscordUser {
+    /// ユーザーID
+    pub id: String,
+    /// ユーザー名
+    pub username: String,
+    /// 表示名
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub global_name: Option<String>,
+}
+
 /// Rich Presenceクライアント
 pub struct RichPresenceClient {
     client: DiscordIpcClient,
-    application_id: String,
+    application_id: ApplicationId,
     connected: bool,
     last_update: Option<Instant>,
+    user: Option<DiscordUser>,
 }
 
 impl RichPresenceClient {
     /// 新しいクライアントを作成
-    pub fn new(application_id: impl Into<String>) -> Result<Self> {
-        let app_id = application_id.into();
-
-        if app_id.is_empty() {
-            return Err(Error::InvalidApplicationId(
-                "Application ID が空です".to_string(),
-            ));
-        }
-
-        let client = DiscordIpcClient::new(&app_id)
+    pub fn new(application_id: ApplicationId) -> Result<Self> {
+        let client = DiscordIpcClient::new(&application_id.to_string())
             .map_err(|e| Error::InvalidApplicationId(e.to_string()))?;
 
         Ok(Self {
             client,
-            application_id: app_id,
+            application_id,
             connected: false,
             last_update: None,
+            user: None,
         })
     }
 
     /// Application IDを取得
-    pub fn application_id(&self) -> &str {
-        &self.application_id
+    pub fn application_id(&self) -> ApplicationId {
+        self.application_id
     }
 
     /// 接続状態を取得
@@ -48,47 +61,61 @@ impl RichPresenceClient {
         self.connected
     }
 
+    /// 接続先の Discord にログインしているユーザーを取得（接続していない場合は `None`）
+    pub fn user(&self) -> Option<&DiscordUser> {
+        self.user.as_ref()
+    }
+
+    /// 次にアクティビティを更新できるまでの時間を取得（更新可能な場合はゼロ）
+    pub fn next_update_in(&self) -> Duration {
+        self.last_update
+            .map(|last| MIN_UPDATE_INTERVAL.saturating_sub(last.elapsed()))
+            .unwrap_or(Duration::ZERO)
+    }
+
     /// Discord IPCに接続
     pub fn connect(&mut self) -> Result<()> {
         if self.connected {
-            debug!("既に接続済みです");
+            debug!("{}", t!("client.already_connected"));
             return Ok(());
         }
 
-        info!("Discord に接続中...");
+        info!("{}", t!("client.connecting"));
 
-        self.client.connect().map_err(|e| {
-            let err_str = e.to_string();
-            if err_str.contains("No such file or directory")
-                || err_str.contains("Connection refused")
-            {
-                Error::DiscordNotRunning
-            } else {
-                Error::ConnectionFailed(err_str)
-            }
+        // discord-rich-presence はどのソケットにも接続できなかった理由を返さないため、
+        // 同じ順序でソケットを探して、Discord が起動していないのか接続に失敗したのかを判別する
+        #[cfg(unix)]
+        probe_socket().map_err(|e| match e.kind() {
+            ErrorKind::NotFound | ErrorKind::ConnectionRefused => Error::DiscordNotRunning,
+            _ => Error::ConnectionFailed {
+                source: IpcError::Io(e),
+            },
         })?;
+        self.client
+            .connect_ipc()
+            .map_err(|_| Error::DiscordNotRunning)?;
+        self.user = self.handshake()?;
 
         self.connected = true;
-        info!("Discord に接続しました");
+        info!("{}", t!("client.connected"));
         Ok(())
     }
 
     /// Discord IPCから切断
     pub fn disconnect(&mut self) -> Result<()> {
         if !self.connected {
-            debug!("既に切断済みです");
+            debug!("{}", t!("client.already_disconnected"));
             return Ok(());
         }
 
-        info!("Discord から切断中...");
+        info!("{}", t!("client.disconnecting"));
 
-        self.client
-            .close()
-            .map_err(|e| Error::ConnectionFailed(e.to_string()))?;
+        self.client.close().map_err(connection_failed)?;
 
         self.connected = false;
         self.last_update = None;
-        info!("Discord から切断しました");
+        self.user = None;
+        info!("{}", t!("client.disconnected"));
         Ok(())
     }
 
@@ -103,23 +130,32 @@ impl RichPresenceClient {
             let elapsed = last.elapsed().as_secs();
             if elapsed < MIN_UPDATE_INTERVAL_SECS {
                 warn!(
-                    "更新間隔が短すぎます（{}秒経過、最低{}秒必要）",
-                    elapsed, MIN_UPDATE_INTERVAL_SECS
+                    "{}",
+                    t!(
+                        "client.too_frequent",
+                        elapsed = elapsed,
+                        min = MIN_UPDATE_INTERVAL_SECS
+                    )
                 );
                 return Err(Error::RateLimited);
             }
         }
 
-        debug!("アクティビティを更新中: {:?}", activity);
-
-        let discord_activity = Self::build_discord_activity(activity);
+        debug!(
+            "{}",
+            t!("client.updating", activity = format!("{:?}", activity))
+        );
 
-        self.client
-            .set_activity(discord_activity)
-            .map_err(|e| Error::ActivityUpdateFailed(e.to_string()))?;
+        let payload = activity.to_ipc_payload()?;
+        let result = self
+            .client
+            .send(payload, 1)
+            .map_err(activity_update_failed)
+            .and_then(|()| self.receive_response());
+        self.check_connection(result)?;
 
         self.last_update = Some(Instant::now());
-        info!("アクティビティを更新しました");
+        info!("{}", t!("client.updated"));
         Ok(())
     }
 
@@ -129,84 +165,276 @@ impl RichPresenceClient {
             return Err(Error::Disconnected);
         }
 
-        debug!("アクティビティをクリア中...");
+        debug!("{}", t!("client.clearing"));
 
-        self.client
+        let result = self
+            .client
             .clear_activity()
-            .map_err(|e| Error::ActivityUpdateFailed(e.to_string()))?;
+            .map_err(activity_update_failed)
+            .and_then(|()| self.receive_response());
+        self.check_connection(result)?;
 
         self.last_update = Some(Instant::now());
-        info!("アクティビティをクリアしました");
+        info!("{}", t!("client.cleared"));
         Ok(())
     }
 
+    /// 接続が維持されているか確認（PING を送信し、PONG を受け取る）
+    ///
+    /// アクティビティを送信しない間も Discord の終了に気付けるよう、定期的に呼び出す。
+    /// 更新間隔の制限には数えない
+    pub fn ping(&mut self) -> Result<()> {
+        if !self.connected {
+            return Err(Error::Disconnected);
+        }
+
+        let result = self
+            .client
+            .send(json!({}), 3)
+            .and_then(|()| self.client.recv())
+            .map_err(connection_failed)
+            .and_then(|(opcode, response)| match opcode {
+                4 => Ok(()),
+                actual => {
+                    debug!("{}", t!("client.unexpected_response", response = response));
+                    Err(Error::ConnectionFailed {
+                        source: IpcError::UnexpectedOpcode {
+                            expected: 4,
+                            actual,
+                        },
+                    })
+                }
+            });
+        self.check_connection(result)
+    }
+
     /// 再接続を試みる
     pub fn reconnect(&mut self) -> Result<()> {
-        info!("再接続を試みています...");
+        info!("{}", t!("client.reconnecting"));
 
-        if self.connected {
-            if let Err(e) = self.disconnect() {
-                warn!("切断中にエラーが発生: {}", e);
-            }
+        if self.connected
+            && let Err(e) = self.disconnect()
+        {
+            warn!("{}", t!("client.disconnect_failed", error = e));
         }
 
         // 新しいクライアントを作成して再接続
-        self.client = DiscordIpcClient::new(&self.application_id)
+        self.client = DiscordIpcClient::new(&self.application_id.to_string())
             .map_err(|e| Error::InvalidApplicationId(e.to_string()))?;
 
         self.connect()
     }
 
-    /// Activityをdiscord-rich-presenceのActivity型に変換
-    fn build_discord_activity(activity: &Activity) -> discord_activity::Activity<'_> {
-        let mut da = discord_activity::Activity::new();
-
-        if let Some(ref details) = activity.details {
-            da = da.details(details);
+    /// ハンドシェイクを行い、ログインしているユーザーを取得
+    ///
+    /// Discord が接続を拒否した場合（Application IDが存在しない場合など）は `Err`
+    fn handshake(&mut self) -> Result<Option<DiscordUser>> {
+        let payload = json!({
+            "v": 1,
+            "client_id": self.application_id.to_string(),
+        });
+        self.client.send(payload, 0).map_err(connection_failed)?;
+        let (_, ready) = self.client.recv().map_err(connection_failed)?;
+
+        if ready["evt"] != "READY" {
+            return Err(Error::ConnectionFailed {
+                source: rejection(&ready),
+            });
         }
+        Ok(serde_json::from_value(ready["data"]["user"].clone()).ok())
+    }
 
-        if let Some(ref state) = activity.state {
-            da = da.state(state);
+    /// 通信に失敗した場合は接続が失われたとみなし、切断した状態にする
+    ///
+    /// Discord が終了した場合などにも [`is_connected`](Self::is_connected) が `false` になり、
+    /// 呼び出し側が再接続できるようにする。Discord が要求を拒否した場合は接続を維持する
+    fn check_connection(&mut self, result: Result<()>) -> Result<()> {
+        if let Err(ref e) = result
+            && e.is_connection_error()
+        {
+            warn!("{}", t!("client.connection_lost", error = e.full_message()));
+            self.connected = false;
+            self.last_update = None;
+            self.user = None;
         }
+        result
+    }
 
-        if let Some(ref ts) = activity.timestamps {
-            let mut timestamps = discord_activity::Timestamps::new();
-            if let Some(start) = ts.start {
-                timestamps = timestamps.start(start);
-            }
-            if let Some(end) = ts.end {
-                timestamps = timestamps.end(end);
-            }
-            da = da.timestamps(timestamps);
+    /// コマンドの応答を受け取る（Discord がエラーを返した場合は `Err`）
+    fn receive_response(&mut self) -> Result<()> {
+        let (_, response) = self.client.recv().map_err(activity_update_failed)?;
+
+        if response["evt"] == "ERROR" {
+            return Err(Error::ActivityUpdateFailed {
+                source: rejection(&response),
+            });
         }
+        Ok(())
+    }
+}
 
-        if let Some(ref assets) = activity.assets {
-            let mut discord_assets = discord_activity::Assets::new();
-            if let Some(ref large_image) = assets.large_image {
-                discord_assets = discord_assets.large_image(large_image);
-            }
-            if let Some(ref large_text) = assets.large_text {
-                discord_assets = discord_assets.large_text(large_text);
-            }
-            if let Some(ref small_image) = assets.small_image {
-                discord_assets = discord_assets.small_image(small_image);
-            }
-            if let Some(ref small_text) = assets.small_text {
-                discord_assets = discord_assets.small_text(small_text);
+/// Discord がエラーを返した応答からエラーコードとメッセージを取り出す
+fn rejection(response: &Value) -> IpcError {
+    IpcError::Rejected {
+        code: response["data"]["code"]
+            .as_i64()
+            .or_else(|| response["code"].as_i64()),
+        message: response["data"]["message"]
+            .as_str()
+            .or_else(|| response["message"].as_str())
+            .map_or_else(|| t!("ipc_error.unknown"), str::to_string),
+    }
+}
+
+/// Discord の IPC のソケットを、discord-rich-presence と同じ順序で探して接続できるか確かめる
+///
+/// どれにも接続できなかった場合は、ソケットがない・接続を拒否された以外の理由
+/// （権限がないなど）を優先して返す
+#[cfg(unix)]
+fn probe_socket() -> std::io::Result<()> {
+    use std::os::unix::net::UnixStream;
+    use std::path::PathBuf;
+
+    const ENV_KEYS: [&str; 4] = ["XDG_RUNTIME_DIR", "TMPDIR", "TMP", "TEMP"];
+    const APP_SUBPATHS: [&str; 4] = [
+        "",
+        "app/com.discordapp.Discord/",
+        "snap.discord-canary/",
+        "snap.discord/",
+    ];
+
+    let dir = ENV_KEYS
+        .iter()
+        .find_map(std::env::var_os)
+        .map(PathBuf::from)
+        .unwrap_or_default();
+
+    let (mut missing, mut failure) = (None, None);
+    for i in 0..10 {
+        for subpath in APP_SUBPATHS {
+            let path = dir.join(subpath).join(format!("discord-ipc-{i}"));
+            match UnixStream::connect(&path) {
+                Ok(_) => return Ok(()),
+                Err(e) => {
+                    debug!(
+                        "{}",
+                        t!("client.socket_failed", path = path.display(), error = e)
+                    );
+                    if matches!(e.kind(), ErrorKind::NotFound | ErrorKind::ConnectionRefused) {
+                        missing.get_or_insert(e);
+                    } else {
+                        failure.get_or_insert(e);
+                    }
+                }
             }
-            da = da.assets(discord_assets);
         }
+    }
+    Err(failure
+        .or(missing)
+        .unwrap_or_else(|| ErrorKind::NotFound.into()))
+}
 
-        da
+/// 接続中のエラーに変換
+fn connection_failed(error: Box<dyn std::error::Error>) -> Error {
+    Error::ConnectionFailed {
+        source: IpcError::from_boxed(error),
+    }
+}
+
+/// アクティビティの更新中のエラーに変換
+fn activity_update_failed(error: Box<dyn std::error::Error>) -> Error {
+    Error::ActivityUpdateFailed {
+        source: IpcError::from_boxed(error),
     }
 }
 
 impl Drop for RichPresenceClient {
     fn drop(&mut self) {
-        if self.connected {
-            if let Err(e) = self.disconnect() {
-                error!("切断中にエラーが発生: {}", e);
-            }
+        if self.connected
+            && let Err(e) = self.disconnect()
+        {
+            error!("{}", t!("client.disconnect_failed", error = e));
         }
     }
 }
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::io::{Read, Write};
+    use std::os::unix::net::{UnixListener, UnixStream};
+
+    /// IPC のフレーム（オペコード、長さ、JSON）を1つ読み込む（接続が閉じられた場合は `None`）
+    fn read_frame(stream: &mut UnixStream) -> Option<(u32, Value)> {
+        let mut header = [0; 8];
+        stream.read_exact(&mut header).ok()?;
+        let op = u32::from_le_bytes(header[..4].try_into().unwrap());
+        let len = u32::from_le_bytes(header[4..].try_into().unwrap());
+        let mut body = vec![0; len as usize];
+        stream.read_exact(&mut body).ok()?;
+        Some((op, serde_json::from_slice(&body).unwrap()))
+    }
+
+    fn write_frame(stream: &mut UnixStream, value: &Value) {
+        let body = value.to_string();
+        let mut frame = 1u32.to_le_bytes().to_vec();
+        frame.extend((body.len() as u32).to_le_bytes());
+        frame.extend(body.as_bytes());
+        stream.write_all(&frame).unwrap();
+    }
+
+    /// ハンドシェイクに応答し、`commands` 回だけコマンドに応答してから接続を閉じる
+    ///
+    /// ソケットを確かめるだけの接続（ハンドシェイクをしない）の場合は `false`
+    fn serve(mut stream: UnixStream, commands: usize) -> bool {
+        let Some((0, _)) = read_frame(&mut stream) else {
+            return false;
+        };
+        write_frame(
+            &mut stream,
+            &json!({"cmd": "DISPATCH", "evt": "READY", "data": {"user": {"id": "1", "username": "tester"}}}),
+        );
+        for _ in 0..commands {
+            let Some((_, command)) = read_frame(&mut stream) else {
+                return true;
+            };
+            write_frame(
+                &mut stream,
+                &json!({"cmd": command["cmd"], "evt": null, "nonce": command["nonce"]}),
+            );
+        }
+        true
+    }
+
+    #[test]
+    fn reconnects_after_connection_is_lost() {
+        let dir = std::env::temp_dir().join(format!("rp-core-ipc-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let listener = UnixListener::bind(dir.join("discord-ipc-0")).unwrap();
+        // SAFETY: Discord のソケットを探すディレクトリを変更するのはこのテストのみ
+        unsafe { std::env::set_var("XDG_RUNTIME_DIR", &dir) };
+
+        // 1つ目の接続は更新の途中で閉じ（Discord の終了）、2つ目の接続は正常に応答する
+        let server = std::thread::spawn(move || {
+            let mut connections = listener.incoming().map(|stream| stream.unwrap());
+            while !serve(connections.next().unwrap(), 0) {}
+            while !serve(connections.next().unwrap(), 1) {}
+        });
+
+        let mut client = RichPresenceClient::new("123456789012345678".parse().unwrap()).unwrap();
+        client.connect().unwrap();
+        let activity = Activity::new().details("test");
+
+        let error = client.update_activity(&activity).unwrap_err();
+        assert!(error.is_connection_error(), "{}", error.full_message());
+        assert!(!client.is_connected());
+
+        client.reconnect().unwrap();
+        assert!(client.is_connected());
+        client.update_activity(&activity).unwrap();
+
+        drop(client);
+        server.join().unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
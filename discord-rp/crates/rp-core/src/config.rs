@@ -1,16 +1,48 @@
 //! 設定ファイルの管理
 
-use crate::{Activity, Error, Result};
+use crate::diagnostic::{check_format, check_value};
+use crate::error::ConfigError;
+use crate::expand::{self, Reference};
+use crate::format::ConfigFormat;
+use crate::migrate::{self, CURRENT_VERSION};
+use crate::{
+    Activity, ApplicationId, BuiltinVariables, Diagnostic, Error, Pomodoro, Preset, Result,
+    Schedule, ScheduleRule,
+};
+use chrono_tz::Tz;
+use schemars::generate::SchemaSettings;
+use schemars::transform::RecursiveTransform;
+use schemars::{JsonSchema, Schema};
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
 use std::collections::BTreeMap;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use toml_edit::{ArrayOfTables, DocumentMut, Item, Table, TableLike, TomlError};
+use tracing::warn;
 
 /// アプリケーション設定
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[schemars(title = "discord-rp の設定ファイル", deny_unknown_fields)]
 pub struct Config {
-    /// Discord Application IDs（キー: インデックス番号）
-    #[serde(default)]
-    pub application_ids: BTreeMap<u32, String>,
+    /// エディタの補完と検証に使用する JSON Schema のパスまたは URL（`discord-rp config schema` で出力）
+    #[serde(rename = "$schema", default, skip_serializing_if = "Option::is_none")]
+    pub schema: Option<String>,
+
+    /// 設定ファイルの形式のバージョン
+    #[serde(default = "default_version")]
+    pub version: u32,
+
+    /// Discordアプリケーション（キー: インデックス番号、ファイルでは `[[applications]]` の配列）
+    #[serde(
+        with = "applications",
+        default,
+        skip_serializing_if = "BTreeMap::is_empty"
+    )]
+    #[schemars(with = "Vec<ApplicationEntry>")]
+    #[schemars(
+        description = "Discordアプリケーション（-i で指定するインデックスとApplication ID）"
+    )]
+    pub applications: BTreeMap<u32, Application>,
 
     /// デフォルトのアクティビティ設定
     #[serde(default)]
@@ -27,12 +59,148 @@ pub struct Config {
     /// 再接続の間隔（秒）
     #[serde(default = "default_reconnect_interval")]
     pub reconnect_interval: u64,
+
+    /// 名前付きプリセット（キー: プリセット名）
+    #[serde(default)]
+    pub presets: BTreeMap<String, Preset>,
+
+    /// 共有プリセットを読み込むディレクトリ（相対パスは設定ファイルのディレクトリ基準）
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub preset_dirs: Vec<PathBuf>,
+
+    /// スケジュールの時刻のタイムゾーン（IANA のタイムゾーン名、例: Asia/Tokyo。省略した場合はシステムのタイムゾーン）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schemars(with = "Option<String>")]
+    pub timezone: Option<Tz>,
+
+    /// 時刻によるプリセットの自動切り替え（記載順に評価し、最初に一致したルールを適用）
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub schedule: Vec<ScheduleRule>,
+
+    /// ポモドーロタイマー（`pomodoro` コマンド）の設定
+    #[serde(default, skip_serializing_if = "is_default_pomodoro")]
+    pub pomodoro: Pomodoro,
+
+    /// ディレクトリから読み込んだプリセットの定義元ファイル
+    #[serde(skip)]
+    preset_sources: BTreeMap<String, PathBuf>,
+
+    /// 読み込み時に移行した場合の元の形式のバージョン
+    #[serde(skip)]
+    migrated_from: Option<u32>,
+
+    /// 読み込み時に展開した参照（保存時に元の参照に戻す）
+    #[serde(skip)]
+    references: Vec<Reference>,
+
+    /// 設定を読み込んだファイル
+    #[serde(skip)]
+    sources: Vec<PathBuf>,
+}
+
+/// プロジェクトの設定ファイル名（拡張子を除く）
+const PROJECT_FILE_STEM: &str = ".discord-rp";
+
+/// プリセットディレクトリ内のファイル形式
+#[derive(Debug, Deserialize)]
+struct PresetFile {
+    #[serde(default)]
+    presets: BTreeMap<String, Preset>,
+}
+
+/// 登録するDiscordアプリケーション
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Application {
+    /// Application ID
+    pub id: ApplicationId,
+    /// 表示用の名前（例: Working）
+    pub name: Option<String>,
+}
+
+impl Application {
+    /// Application IDを指定して作成
+    pub fn new(id: ApplicationId) -> Self {
+        Self { id, name: None }
+    }
+
+    /// 表示用の名前を設定
+    pub fn name(mut self, name: impl Into<String>) -> Self {
+        self.name = Some(name.into());
+        self
+    }
+}
+
+/// `[[applications]]` の各要素
+#[derive(Serialize, Deserialize, JsonSchema)]
+#[schemars(deny_unknown_fields)]
+struct ApplicationEntry {
+    /// インデックス（`-i` で指定する番号）
+    #[schemars(range(min = 1))]
+    index: u32,
+    /// Application ID
+    id: ApplicationId,
+    /// 表示用の名前
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    name: Option<String>,
+}
+
+/// アプリケーションの表と `[[applications]]` の配列の変換
+mod applications {
+    use super::{Application, ApplicationEntry};
+    use serde::de::Error as _;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+    use std::collections::BTreeMap;
+
+    pub fn serialize<S: Serializer>(
+        applications: &BTreeMap<u32, Application>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        applications
+            .iter()
+            .map(|(&index, app)| ApplicationEntry {
+                index,
+                id: app.id,
+                name: app.name.clone(),
+            })
+            .collect::<Vec<_>>()
+            .serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<BTreeMap<u32, Application>, D::Error> {
+        let mut applications = BTreeMap::new();
+        for entry in Vec::<ApplicationEntry>::deserialize(deserializer)? {
+            if entry.index == 0 {
+                return Err(D::Error::custom(t!("config.index_starts_at_one")));
+            }
+            let app = Application {
+                id: entry.id,
+                name: entry.name,
+            };
+            if applications.insert(entry.index, app).is_some() {
+                return Err(D::Error::custom(t!(
+                    "config.duplicate_index",
+                    index = entry.index
+                )));
+            }
+        }
+        Ok(applications)
+    }
+}
+
+fn default_version() -> u32 {
+    CURRENT_VERSION
 }
 
 fn default_true() -> bool {
     true
 }
 
+fn is_default_pomodoro(pomodoro: &Pomodoro) -> bool {
+    *pomodoro == Pomodoro::default()
+}
+
 fn default_reconnect_interval() -> u64 {
     30
 }
@@ -40,11 +208,22 @@ fn default_reconnect_interval() -> u64 {
 impl Default for Config {
     fn default() -> Self {
         Self {
-            application_ids: BTreeMap::new(),
+            schema: None,
+            version: CURRENT_VERSION,
+            applications: BTreeMap::new(),
             activity: Activity::default(),
             auto_connect: true,
             auto_reconnect: true,
             reconnect_interval: 30,
+            presets: BTreeMap::new(),
+            preset_dirs: Vec::new(),
+            timezone: None,
+            schedule: Vec::new(),
+            pomodoro: Pomodoro::default(),
+            preset_sources: BTreeMap::new(),
+            migrated_from: None,
+            references: Vec::new(),
+            sources: Vec::new(),
         }
     }
 }
@@ -55,92 +234,1213 @@ impl Config {
         Self::default()
     }
 
-    /// Application IDを追加
-    pub fn add_application_id(&mut self, index: u32, app_id: impl Into<String>) {
-        self.application_ids.insert(index, app_id.into());
+    /// Application IDを追加（登録済みのインデックスの場合は名前を残してIDのみ置き換える）
+    pub fn add_application_id(&mut self, index: u32, app_id: ApplicationId) {
+        self.applications
+            .entry(index)
+            .and_modify(|app| app.id = app_id)
+            .or_insert_with(|| Application::new(app_id));
     }
 
-    /// 指定インデックスのApplication IDを取得（1始まり）
-    pub fn get_application_id(&self, index: u32) -> Result<&str> {
+    /// アプリケーションを追加（同じインデックスのアプリケーションは置き換える）
+    pub fn add_application(&mut self, index: u32, app: Application) {
+        self.applications.insert(index, app);
+    }
+
+    /// 指定インデックスのアプリケーションを取得（1始まり）
+    pub fn get_application(&self, index: u32) -> Result<&Application> {
         if index == 0 {
-            return Err(Error::InvalidApplicationId(
-                "インデックスは1から始まります".to_string(),
-            ));
+            return Err(Error::InvalidApplicationId(t!(
+                "config.index_starts_at_one"
+            )));
         }
 
-        self.application_ids
-            .get(&index)
-            .map(|s| s.as_str())
-            .ok_or_else(|| {
-                Error::InvalidApplicationId(format!(
-                    "インデックス {} のApplication IDが登録されていません（登録済み: {:?}）",
-                    index,
-                    self.application_ids.keys().collect::<Vec<_>>()
-                ))
-            })
+        self.applications.get(&index).ok_or_else(|| {
+            Error::InvalidApplicationId(t!(
+                "config.index_not_registered",
+                index = index,
+                registered = format!("{:?}", self.applications.keys().collect::<Vec<_>>())
+            ))
+        })
+    }
+
+    /// 指定インデックスのApplication IDを取得（1始まり）
+    pub fn get_application_id(&self, index: u32) -> Result<ApplicationId> {
+        self.get_application(index).map(|app| app.id)
     }
 
     /// 登録済みのApplication ID数を取得
     pub fn application_id_count(&self) -> usize {
-        self.application_ids.len()
+        self.applications.len()
     }
 
     /// 登録済みのインデックス一覧を取得
     pub fn registered_indices(&self) -> Vec<u32> {
-        self.application_ids.keys().copied().collect()
+        self.applications.keys().copied().collect()
+    }
+
+    /// プリセットを取得
+    pub fn get_preset(&self, name: &str) -> Result<&Preset> {
+        self.presets
+            .get(name)
+            .ok_or_else(|| Error::PresetNotFound(name.to_string()))
+    }
+
+    /// プリセットを追加（同名のプリセットは置き換える）
+    pub fn add_preset(&mut self, name: impl Into<String>, preset: Preset) {
+        self.presets.insert(name.into(), preset);
+    }
+
+    /// プリセットを削除
+    pub fn remove_preset(&mut self, name: &str) -> Result<Preset> {
+        self.presets
+            .remove(name)
+            .ok_or_else(|| Error::PresetNotFound(name.to_string()))
     }
 
-    /// ファイルから設定を読み込む
+    /// プリセットの定義元ファイルを取得（設定ファイル自身で定義されている場合は `None`）
+    pub fn preset_source(&self, name: &str) -> Option<&Path> {
+        self.preset_sources.get(name).map(PathBuf::as_path)
+    }
+
+    /// 継承の連鎖を取得（最も基底のプリセットが先頭）
+    pub fn preset_chain(&self, name: &str) -> Result<Vec<&str>> {
+        let mut chain: Vec<&str> = Vec::new();
+        let mut current = name;
+
+        loop {
+            let (key, preset) = self
+                .presets
+                .get_key_value(current)
+                .ok_or_else(|| match chain.last() {
+                    Some(child) => Error::InvalidPreset(t!(
+                        "config.extends_not_found",
+                        child = child,
+                        parent = current
+                    )),
+                    None => Error::PresetNotFound(current.to_string()),
+                })?;
+
+            if chain.contains(&key.as_str()) {
+                chain.push(key);
+                chain.reverse();
+                return Err(Error::InvalidPreset(t!(
+                    "config.extends_cycle",
+                    chain = chain.join(" → ")
+                )));
+            }
+            chain.push(key);
+
+            match preset.extends {
+                Some(ref parent) => current = parent,
+                None => break,
+            }
+        }
+
+        chain.reverse();
+        Ok(chain)
+    }
+
+    /// 継承を解決したプリセットを取得
+    ///
+    /// 基底のプリセットから順に設定を重ねる
+    pub fn resolve_preset(&self, name: &str) -> Result<Preset> {
+        let mut resolved = Preset::default();
+        for preset_name in self.preset_chain(name)? {
+            resolved.merge(&self.presets[preset_name]);
+        }
+        Ok(resolved)
+    }
+
+    /// スケジュールを取得
+    pub fn schedule(&self) -> Schedule<'_> {
+        Schedule::new(&self.schedule, self.timezone)
+    }
+
+    /// `preset_dirs` のディレクトリにあるプリセットファイルを読み込み順に取得
+    ///
+    /// 使用できる形式（[`ConfigFormat::is_enabled`]）の拡張子のファイルのみを対象とする
+    pub fn preset_files(&self, base_dir: &Path) -> Result<Vec<PathBuf>> {
+        let mut all_files = Vec::new();
+
+        for dir in &self.preset_dirs {
+            let dir = base_dir.join(dir);
+            let entries = std::fs::read_dir(&dir)
+                .map_err(|e| Error::config_load(e).in_file(&dir))?;
+
+            let mut files = entries
+                .filter_map(|entry| entry.ok().map(|e| e.path()))
+                .filter(|path| {
+                    path.is_file()
+                        && ConfigFormat::is_config_file(path)
+                        && ConfigFormat::from_path(path).is_enabled()
+                })
+                .collect::<Vec<_>>();
+            files.sort();
+            all_files.extend(files);
+        }
+
+        Ok(all_files)
+    }
+
+    /// `preset_dirs` のディレクトリからプリセットを読み込む
+    ///
+    /// ディレクトリは記載順、ディレクトリ内のファイルはファイル名順に読み込み、
+    /// 同名のプリセットは後から読み込んだものが優先される。
+    /// 設定ファイル自身で定義されたプリセットが最も優先される
+    pub fn load_preset_dirs(&mut self, base_dir: &Path) -> Result<()> {
+        self.load_preset_dirs_with_vars(base_dir, env_var)
+    }
+
+    /// 変数の参照方法を指定して `preset_dirs` のディレクトリからプリセットを読み込む
+    ///
+    /// 読み込み順は [`load_preset_dirs`](Self::load_preset_dirs) と同じ
+    pub fn load_preset_dirs_with_vars(
+        &mut self,
+        base_dir: &Path,
+        var: impl Fn(&str) -> Option<String>,
+    ) -> Result<()> {
+        let mut preset_files = Vec::new();
+
+        for file in self.preset_files(base_dir)? {
+            let with_file = |e: Error| e.in_file(&file);
+            let content =
+                std::fs::read_to_string(&file).map_err(|e| Error::config_load(e).in_file(&file))?;
+            let format = ConfigFormat::from_path(&file);
+
+            let mut table = format.parse(&content).map_err(with_file)?;
+            let (references, diagnostics) = expand::expand(&mut table, parent_dir(&file), &var);
+            if let Some(diagnostic) = diagnostics.first() {
+                return Err(with_file(Error::config_load(diagnostic.to_string())));
+            }
+
+            let preset_file: PresetFile =
+                deserialize_table(&content, format, table, !references.is_empty())
+                    .map_err(with_file)?;
+            preset_files.push((file, preset_file));
+        }
+
+        self.insert_preset_files(preset_files);
+        Ok(())
+    }
+
+    /// `preset_dirs` のプリセットファイルを検査しながら読み込む
+    ///
+    /// 問題のないファイルのプリセットは [`load_preset_dirs`](Self::load_preset_dirs) と同じ順序で読み込む
+    pub fn check_preset_dirs(&mut self, base_dir: &Path) -> Vec<Diagnostic> {
+        let files = match self.preset_files(base_dir) {
+            Ok(files) => files,
+            Err(e) => return vec![Diagnostic::new(["preset_dirs"], e.to_string())],
+        };
+
+        let mut diagnostics = Vec::new();
+        let mut preset_files = Vec::new();
+        for file in files {
+            let (preset_file, found) = match std::fs::read_to_string(&file) {
+                Ok(content) => {
+                    let format = ConfigFormat::from_path(&file);
+                    match format.parse(&content) {
+                        Ok(table) => check_table::<PresetFile>(
+                            &content,
+                            format,
+                            table,
+                            false,
+                            parent_dir(&file),
+                        ),
+                        Err(_) => check_format(&content, format),
+                    }
+                }
+                Err(e) => (
+                    None,
+                    vec![Diagnostic::new(Vec::<String>::new(), e.to_string())],
+                ),
+            };
+            diagnostics.extend(found.into_iter().map(|d| d.file(&file)));
+            preset_files.extend(preset_file.map(|preset_file| (file, preset_file)));
+        }
+
+        self.insert_preset_files(preset_files);
+        diagnostics
+    }
+
+    /// プリセットファイルの内容を読み込み順に反映（設定ファイル自身の定義を優先）
+    fn insert_preset_files(&mut self, preset_files: Vec<(PathBuf, PresetFile)>) {
+        let mut loaded: BTreeMap<String, Preset> = BTreeMap::new();
+        let mut sources: BTreeMap<String, PathBuf> = BTreeMap::new();
+
+        for (file, preset_file) in preset_files {
+            for (name, preset) in preset_file.presets {
+                sources.insert(name.clone(), file.clone());
+                loaded.insert(name, preset);
+            }
+        }
+
+        for (name, preset) in loaded {
+            if !self.presets.contains_key(&name) {
+                self.presets.insert(name.clone(), preset);
+                if let Some(source) = sources.remove(&name) {
+                    self.preset_sources.insert(name, source);
+                }
+            }
+        }
+    }
+
+    /// TOML の設定ファイルの内容を検査して読み込む（構文、形式のバージョン、参照、未知のキー、型）
+    ///
+    /// 問題のキーのファイル内の位置は設定済み。
+    /// 古い形式の場合はその旨を報告し、現在の形式に移行した内容を検査する。
+    /// `file:` の相対パスはカレントディレクトリを基準とする
+    pub fn check_source(source: &str) -> (Option<Self>, Vec<Diagnostic>) {
+        Self::check_source_as(source, ConfigFormat::Toml, Path::new(""))
+    }
+
+    /// 指定した形式の設定ファイルの内容を検査して読み込む
+    ///
+    /// 検査の内容は [`check_source`](Self::check_source) と同じ。
+    /// TOML 以外の形式では構文エラーの位置のみを設定する。
+    /// `file:` の相対パスは `base_dir` を基準とする
+    pub fn check_source_as(
+        source: &str,
+        format: ConfigFormat,
+        base_dir: &Path,
+    ) -> (Option<Self>, Vec<Diagnostic>) {
+        let Ok(mut table) = format.parse(source) else {
+            return check_format(source, format);
+        };
+
+        let located = |key: &[&str], message: String| {
+            let mut diagnostic = Diagnostic::new(key.iter().copied(), message);
+            if format == ConfigFormat::Toml {
+                diagnostic.locate(source);
+            }
+            diagnostic
+        };
+
+        let from = match migrate::version(&table) {
+            Ok(from) => from,
+            Err(e) => return (None, vec![located(&["version"], e.to_string())]),
+        };
+        if let Err(e) = migrate::migrate(&mut table) {
+            // 新しすぎるバージョンは version の問題として報告する
+            let key: &[&str] = if from > CURRENT_VERSION {
+                &["version"]
+            } else {
+                &[]
+            };
+            return (None, vec![located(key, e.to_string())]);
+        }
+        if from == CURRENT_VERSION {
+            return check_table(source, format, table, false, base_dir);
+        }
+
+        // 移行後の内容には元のファイルの位置がないため、キーから探す
+        let (config, mut diagnostics) = check_table(source, format, table, true, base_dir);
+        diagnostics.insert(
+            0,
+            located(
+                &["version"],
+                t!("config.outdated", from = from, current = CURRENT_VERSION),
+            ),
+        );
+        (config, diagnostics)
+    }
+
+    /// 読み込んだ設定の内容を検査
+    ///
+    /// 継承、テンプレートと変数、プリセットが参照するApplication ID、Discord の制限を検査する。
+    /// ディレクトリから読み込んだプリセットの問題には定義元ファイルを設定する
+    pub fn validate(&self) -> Vec<Diagnostic> {
+        let mut diagnostics = self.activity.check_templates(BuiltinVariables::is_builtin);
+        diagnostics.extend(self.activity.check_limits());
+        let mut diagnostics = diagnostics
+            .into_iter()
+            .map(|diagnostic| diagnostic.prefixed(["activity"]))
+            .collect::<Vec<_>>();
+
+        for (name, preset) in &self.presets {
+            // 継承を解決できない場合もプリセット自身の項目は検査する
+            let mut found = match self.resolve_preset(name) {
+                Ok(resolved) => preset.check(&resolved),
+                Err(e) => {
+                    let mut found = vec![Diagnostic::new(["extends"], e.to_string())];
+                    found.extend(preset.check(preset));
+                    found
+                }
+            };
+
+            if let Some(app) = preset.app
+                && !self.applications.contains_key(&app)
+            {
+                found.push(Diagnostic::new(
+                    ["app"],
+                    t!("config.app_not_registered", index = app),
+                ));
+            }
+
+            diagnostics.extend(found.into_iter().map(|diagnostic| {
+                let diagnostic = diagnostic.prefixed(["presets", name.as_str()]);
+                match self.preset_source(name) {
+                    Some(source) => diagnostic.file(source),
+                    None => diagnostic,
+                }
+            }));
+        }
+
+        for (index, rule) in self.schedule.iter().enumerate() {
+            let key = |name: &str| ["schedule".to_string(), index.to_string(), name.to_string()];
+            match rule.preset {
+                Some(ref name) => {
+                    let result = self
+                        .resolve_preset(name)
+                        .and_then(|preset| preset.to_template(&rule.vars));
+                    if let Err(e) = result {
+                        diagnostics.push(Diagnostic::new(
+                            key("preset"),
+                            t!("schedule.preset_failed", name = name, error = e),
+                        ));
+                    }
+                }
+                None if !rule.vars.is_empty() => {
+                    diagnostics.push(Diagnostic::new(
+                        key("vars"),
+                        t!("schedule.vars_without_preset"),
+                    ));
+                }
+                None => {}
+            }
+        }
+
+        let pomodoro = &self.pomodoro;
+        for (name, value) in [
+            ("work", pomodoro.work),
+            ("break", pomodoro.short_break),
+            ("long_break", pomodoro.long_break),
+            ("cycles", pomodoro.cycles),
+        ] {
+            if value == 0 {
+                diagnostics.push(Diagnostic::new(["pomodoro", name], t!("pomodoro.zero")));
+            }
+        }
+        if let Some(ref name) = pomodoro.preset
+            && let Err(e) = self.resolve_preset(name)
+        {
+            diagnostics.push(Diagnostic::new(
+                ["pomodoro", "preset"],
+                t!("pomodoro.preset_failed", name = name, error = e),
+            ));
+        }
+
+        diagnostics
+    }
+
+    /// ファイルから設定を読み込む（形式は拡張子から判定）
+    ///
+    /// 値の参照は環境変数と、設定ファイルのディレクトリを基準としたファイルから展開する
     pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        Self::load_with_vars(path, env_var)
+    }
+
+    /// 変数の参照方法を指定してファイルから設定を読み込む
+    pub fn load_with_vars(
+        path: impl AsRef<Path>,
+        var: impl Fn(&str) -> Option<String>,
+    ) -> Result<Self> {
+        let path = path.as_ref();
+        let content =
+            std::fs::read_to_string(path).map_err(|e| Error::config_load(e).in_file(path))?;
+
+        let format = ConfigFormat::from_path(path);
+        let mut config = Self::parse_with(&content, format, parent_dir(path), &var)
+            .map_err(|e| e.in_file(path))?;
+        config.sources = vec![path.to_path_buf()];
+        Ok(config)
+    }
+
+    /// TOML の設定を読み込む
+    ///
+    /// 古い形式の場合は現在の形式に移行してから読み込む（元のファイルは変更しない）
+    pub fn parse(content: &str) -> Result<Self> {
+        Self::parse_as(content, ConfigFormat::Toml)
+    }
+
+    /// 指定した形式の設定を読み込む
+    ///
+    /// 古い形式の場合は現在の形式に移行してから読み込む（元のファイルは変更しない）。
+    /// `file:` の相対パスはカレントディレクトリを基準とする
+    pub fn parse_as(content: &str, format: ConfigFormat) -> Result<Self> {
+        Self::parse_with(content, format, Path::new(""), &env_var)
+    }
+
+    /// 値の表から設定を作成
+    ///
+    /// 移行と参照の展開は [`load`](Self::load) と同じ。`file:` の相対パスは `base_dir` を基準とする
+    pub fn from_table(mut table: toml::Table, base_dir: &Path) -> Result<Self> {
+        let (migrated_from, references) = prepare_table(&mut table, base_dir, &env_var)?;
+
+        let mut config = Self::deserialize(table).map_err(|e| {
+            Error::config_load(ConfigError::Parse {
+                format: ConfigFormat::Toml,
+                span: None,
+                source: Box::new(e),
+            })
+        })?;
+        config.migrated_from = migrated_from;
+        config.references = references;
+        Ok(config)
+    }
+
+    /// 移行と参照の展開をしてから読み込む
+    fn parse_with(
+        content: &str,
+        format: ConfigFormat,
+        base_dir: &Path,
+        var: &dyn Fn(&str) -> Option<String>,
+    ) -> Result<Self> {
+        let mut table = format.parse(content)?;
+        let (migrated_from, references) = prepare_table(&mut table, base_dir, var)?;
+
+        let modified = migrated_from.is_some() || !references.is_empty();
+        let mut config: Self = deserialize_table(content, format, table, modified)?;
+        config.migrated_from = migrated_from;
+        config.references = references;
+        Ok(config)
+    }
+
+    /// 指定したディレクトリから親へ順に、プロジェクトの設定ファイル（`.discord-rp.toml` など）を探す
+    ///
+    /// 拡張子は使用できる形式のもの（`.toml`, `.json`, `.yaml`）を対象とし、最初に見つかったものを返す
+    pub fn find_project_file(start: &Path) -> Option<PathBuf> {
+        start.ancestors().find_map(|dir| {
+            ConfigFormat::ALL
+                .into_iter()
+                .filter(|format| format.is_enabled())
+                .map(|format| dir.join(format!("{}.{}", PROJECT_FILE_STEM, format.extension())))
+                .find(|path| path.is_file())
+        })
+    }
+
+    /// 別の設定ファイル（プロジェクトの設定ファイルなど）の内容を重ねる
+    ///
+    /// 重ねたファイルに記述された値が優先される。テーブルはキーごとに重ね、
+    /// プリセットは同名のものを、Application IDは同じインデックスのものを置き換える。
+    ///
+    /// 重ねるファイルはリポジトリなどから自動で見つかるため信頼しない。
+    /// 参照（`${VAR}`, `file:`）は展開せずそのままの文字列として扱い、
+    /// コマンドを実行する `pomodoro.hooks` と、ファイルを読み込む `preset_dirs` は無視する（警告を出力）
+    pub fn layer_file(&mut self, path: impl AsRef<Path>) -> Result<()> {
         let path = path.as_ref();
-        let content = std::fs::read_to_string(path)
-            .map_err(|e| Error::ConfigLoadFailed(format!("{}: {}", path.display(), e)))?;
+        let with_file = |e: Error| e.in_file(path);
+        let content =
+            std::fs::read_to_string(path).map_err(|e| Error::config_load(e).in_file(path))?;
+        let format = ConfigFormat::from_path(path);
+
+        let mut table = format.parse(&content).map_err(with_file)?;
+        // 重ねるファイルは新しく作られるため、version を省略した場合は現在の形式とみなす
+        table
+            .entry("version")
+            .or_insert_with(|| toml::Value::Integer(CURRENT_VERSION.into()));
+        migrate_table(&mut table).map_err(with_file)?;
+        restrict_untrusted(&mut table, path);
+        // 重ねる前に、ファイル単体で設定として読み込めることを確認する
+        deserialize_table::<Self>(&content, format, table.clone(), true).map_err(with_file)?;
+
+        let mut layered = toml::Table::try_from(&*self).map_err(|e| {
+            Error::config_load(ConfigError::Serialize {
+                format: ConfigFormat::Toml,
+                message: e.to_string(),
+            })
+        })?;
+        layer_table(&mut layered, table);
+        let mut config: Self =
+            deserialize_table(&content, format, layered, true).map_err(with_file)?;
+
+        config.preset_sources = std::mem::take(&mut self.preset_sources);
+        config.migrated_from = self.migrated_from;
+        config.references = std::mem::take(&mut self.references);
+        config.sources = std::mem::take(&mut self.sources);
+        config.sources.push(path.to_path_buf());
+        *self = config;
+        Ok(())
+    }
+
+    /// 設定を読み込んだファイル（優先度の低い順）
+    ///
+    /// [`load`](Self::load) したファイルと [`layer_file`](Self::layer_file) で重ねたファイル。
+    /// `preset_dirs` のプリセットファイルは含まない（[`preset_files`](Self::preset_files) を参照）
+    pub fn sources(&self) -> &[PathBuf] {
+        &self.sources
+    }
+
+    /// 読み込み時に古い形式から移行した場合、元の形式のバージョンを取得
+    ///
+    /// 保存すると現在の形式で書き込まれるため、必要に応じて先に [`backup`](Self::backup) する
+    pub fn migrated_from(&self) -> Option<u32> {
+        self.migrated_from
+    }
 
-        toml::from_str(&content)
-            .map_err(|e| Error::ConfigLoadFailed(format!("TOML パースエラー: {}", e)))
+    /// 設定を TOML に変換
+    pub fn to_toml_string(&self) -> Result<String> {
+        self.to_string_as(ConfigFormat::Toml)
     }
 
-    /// 設定をファイルに保存
+    /// 設定を指定した形式に変換
+    ///
+    /// 読み込み時に展開した値は、変更されていなければ元の参照（`${VAR}`, `file:`）に戻して書き込む
+    pub fn to_string_as(&self, format: ConfigFormat) -> Result<String> {
+        if self.references.is_empty() {
+            return format.to_string(self);
+        }
+
+        let mut table = toml::Table::try_from(self).map_err(|e| {
+            Error::config_save(ConfigError::Serialize {
+                format,
+                message: e.to_string(),
+            })
+        })?;
+        expand::restore(&mut table, &self.references);
+        format.to_string(&table)
+    }
+
+    /// 設定をファイルに保存（形式は拡張子から判定）
     pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
         let path = path.as_ref();
 
         // 親ディレクトリを作成
         if let Some(parent) = path.parent() {
-            std::fs::create_dir_all(parent)
-                .map_err(|e| Error::ConfigSaveFailed(format!("ディレクトリ作成失敗: {}", e)))?;
+            std::fs::create_dir_all(parent).map_err(|e| Error::config_save(e).in_file(parent))?;
         }
 
-        let content = toml::to_string_pretty(self)
-            .map_err(|e| Error::ConfigSaveFailed(format!("TOML シリアライズエラー: {}", e)))?;
+        let content = self
+            .to_string_as(ConfigFormat::from_path(path))
+            .map_err(|e| e.in_file(path))?;
 
-        std::fs::write(path, content)
-            .map_err(|e| Error::ConfigSaveFailed(format!("{}: {}", path.display(), e)))?;
+        std::fs::write(path, content).map_err(|e| Error::config_save(e).in_file(path))?;
 
         Ok(())
     }
 
+    /// TOML の設定ファイルにプリセットを追加（同名のプリセットは置き換える）
+    ///
+    /// `presets` テーブルのみを書き換え、それ以外の記述（コメント、キーの順序、`${VAR}` や `file:` の参照）はそのまま残す
+    pub fn save_preset(path: impl AsRef<Path>, name: &str, preset: &Preset) -> Result<()> {
+        let path = path.as_ref();
+        let mut item = preset_item(name, preset).map_err(|e| e.in_file(path))?;
+        edit_toml_file(path, |document| {
+            let presets = presets_table(document);
+            // 置き換える場合は元のプリセットの前にあるコメントを引き継ぎ、追加する場合は空行で区切る
+            if let Item::Table(ref mut new) = item {
+                match presets.get(name) {
+                    Some(Item::Table(old)) => *new.decor_mut() = old.decor().clone(),
+                    _ => new.decor_mut().set_prefix("\n"),
+                }
+            }
+            presets.insert(name, item);
+            Ok(())
+        })
+    }
+
+    /// TOML の設定ファイルからプリセットを削除
+    ///
+    /// [`save_preset`](Self::save_preset) と同様に、`presets` テーブル以外の記述はそのまま残す
+    pub fn delete_preset(path: impl AsRef<Path>, name: &str) -> Result<()> {
+        edit_toml_file(path.as_ref(), |document| {
+            document
+                .get_mut("presets")
+                .and_then(Item::as_table_like_mut)
+                .and_then(|presets| presets.remove(name))
+                .map(|_| ())
+                .ok_or_else(|| Error::PresetNotFound(name.to_string()))
+        })
+    }
+
+    /// 設定ファイルの内容を現在の形式に移行
+    ///
+    /// 適用した手順の説明を順に返す（最新の形式の場合は空）
+    pub fn migrate(table: &mut toml::Table) -> Result<Vec<String>> {
+        migrate::migrate(table)
+    }
+
+    /// TOML の設定ファイルの内容を現在の形式に移行
+    ///
+    /// [`migrate`](Self::migrate) と同じ移行を、変更する部分以外の記述（コメント、キーの順序、参照）を保ったまま適用する。
+    /// 移行後の内容と適用した手順の説明を返す（最新の形式の場合は手順が空で、内容は変わらない）
+    pub fn migrate_toml(source: &str) -> Result<(String, Vec<String>)> {
+        migrate::migrate_toml(source)
+    }
+
+    /// ファイルのバックアップを同じディレクトリに作成（例: `config.toml.20260101-120000.bak`）
+    pub fn backup(path: impl AsRef<Path>) -> Result<PathBuf> {
+        migrate::backup(path.as_ref())
+    }
+
+    /// 設定ファイルの JSON Schema を取得
+    ///
+    /// serde の定義から生成するため、設定の項目と常に一致する
+    pub fn json_schema() -> String {
+        // 設定ファイルの形式には null がないため、省略可能な項目も null を許可しない
+        let generator = SchemaSettings::draft2020_12()
+            .with_transform(RecursiveTransform(remove_null_type))
+            .into_generator();
+        let schema = generator.into_root_schema_for::<Config>();
+        format!("{:#}", schema.as_value())
+    }
+
     /// デフォルトの設定ファイルパスを取得
+    ///
+    /// 設定ディレクトリに `config.toml`, `config.json`, `config.yaml`, `config.yml` の
+    /// いずれかがあれば最初に見つかったもの（使用できる形式のみ）、なければ `config.toml`
     pub fn default_path() -> Option<std::path::PathBuf> {
-        dirs::config_dir().map(|p| p.join("discord-rp").join("config.toml"))
+        let dir = dirs::config_dir()?.join("discord-rp");
+        let found = ["config.toml", "config.json", "config.yaml", "config.yml"]
+            .into_iter()
+            .map(|name| dir.join(name))
+            .find(|path| ConfigFormat::from_path(path).is_enabled() && path.is_file());
+        Some(found.unwrap_or_else(|| dir.join("config.toml")))
     }
 
     /// 環境変数からApplication IDを読み込む
     /// DISCORD_APPLICATION_ID_1, DISCORD_APPLICATION_ID_2, ... の形式
-    pub fn load_from_env(&mut self) {
+    pub fn load_from_env(&mut self) -> Result<()> {
+        self.load_from_vars(|key| std::env::var(key).ok())
+    }
+
+    /// 変数の参照方法を指定してApplication IDを読み込む
+    ///
+    /// 形式は [`load_from_env`](Self::load_from_env) と同じ
+    pub fn load_from_vars(&mut self, var: impl Fn(&str) -> Option<String>) -> Result<()> {
         for i in 1..=100 {
             let key = format!("DISCORD_APPLICATION_ID_{}", i);
-            if let Ok(value) = std::env::var(&key) {
-                if !value.is_empty() {
-                    self.add_application_id(i, value);
+            let file_key = format!("{}_FILE", key);
+
+            let value = match (
+                var(&key).filter(|v| !v.is_empty()),
+                var(&file_key).filter(|v| !v.is_empty()),
+            ) {
+                (Some(_), Some(_)) => {
+                    return Err(Error::config_load(t!(
+                        "config.env_conflict",
+                        key = key,
+                        file_key = file_key
+                    )));
                 }
+                (Some(value), None) => Some((&key, value)),
+                (None, Some(path)) => {
+                    let value = expand::read_file(&path, Path::new(""))
+                        .map_err(|e| Error::config_load(format!("{}: {}", file_key, e)))?;
+                    Some((&file_key, value.trim().to_string()))
+                }
+                (None, None) => None,
+            };
+
+            if let Some((key, value)) = value {
+                let app_id = ApplicationId::parse(&value).map_err(|e| match e {
+                    Error::InvalidApplicationId(msg) => {
+                        Error::InvalidApplicationId(format!("{}: {}", key, msg))
+                    }
+                    e => e,
+                })?;
+                self.add_application_id(i, app_id);
             }
         }
+        Ok(())
     }
 
     /// 環境変数から設定を作成
-    pub fn from_env() -> Self {
+    pub fn from_env() -> Result<Self> {
         let mut config = Self::new();
-        config.load_from_env();
-        config
+        config.load_from_env()?;
+        Ok(config)
+    }
+}
+
+/// スキーマから `null` 型を取り除く（`Option` の項目は省略のみ許可する）
+fn remove_null_type(schema: &mut Schema) {
+    let Some(object) = schema.as_object_mut() else {
+        return;
+    };
+    if let Some(serde_json::Value::Array(types)) = object.get_mut("type") {
+        types.retain(|t| t != "null");
+        if let [single] = types.as_slice() {
+            let single = single.clone();
+            object.insert("type".to_string(), single);
+        }
+    }
+    if let Some(serde_json::Value::Array(variants)) = object.get("anyOf")
+        && let Some(rest) = non_null_variant(variants)
+    {
+        object.remove("anyOf");
+        if let serde_json::Value::Object(rest) = rest {
+            object.extend(rest);
+        }
+    }
+}
+
+/// `null` とそれ以外の1つの型の組み合わせから、`null` 以外の型を取り出す
+fn non_null_variant(variants: &[serde_json::Value]) -> Option<serde_json::Value> {
+    let is_null = |v: &serde_json::Value| v.get("type").is_some_and(|t| t == "null");
+    match variants {
+        [a, b] if is_null(b) => Some(a.clone()),
+        [a, b] if is_null(a) => Some(b.clone()),
+        _ => None,
+    }
+}
+
+/// 環境変数の値を取得
+fn env_var(key: &str) -> Option<String> {
+    std::env::var(key).ok()
+}
+
+/// ファイルのあるディレクトリ（`file:` の相対パスの基準）
+fn parent_dir(path: &Path) -> &Path {
+    path.parent().unwrap_or(Path::new(""))
+}
+
+/// 値の表を現在の形式に移行し、参照を展開する
+///
+/// 移行した場合は元の形式のバージョンと、展開した参照を返す
+fn prepare_table(
+    table: &mut toml::Table,
+    base_dir: &Path,
+    var: &dyn Fn(&str) -> Option<String>,
+) -> Result<(Option<u32>, Vec<Reference>)> {
+    let migrated_from = migrate_table(table)?;
+
+    let (references, diagnostics) = expand::expand(table, base_dir, var);
+    if let Some(diagnostic) = diagnostics.first() {
+        return Err(Error::config_load(diagnostic.to_string()));
+    }
+
+    Ok((migrated_from, references))
+}
+
+/// 値の表を現在の形式に移行する（移行した場合は元の形式のバージョンを返す）
+fn migrate_table(table: &mut toml::Table) -> Result<Option<u32>> {
+    let from = migrate::version(table)?;
+    let migrated = !migrate::migrate(table)?.is_empty();
+    if migrated {
+        warn!(
+            "{}",
+            t!(
+                "config.outdated_warning",
+                from = from,
+                current = CURRENT_VERSION
+            )
+        );
+    }
+    Ok(migrated.then_some(from))
+}
+
+/// 信頼しないファイルの値の表から、使用できない項目を取り除く（[`Config::layer_file`] を参照）
+fn restrict_untrusted(table: &mut toml::Table, path: &Path) {
+    for key in expand::find(table) {
+        warn!(
+            "{}",
+            t!(
+                "config.untrusted_reference",
+                path = path.display(),
+                key = key.join(".")
+            )
+        );
+    }
+
+    let preset_dirs = table.remove("preset_dirs");
+    let hooks = match table.get_mut("pomodoro") {
+        Some(toml::Value::Table(pomodoro)) => pomodoro.remove("hooks"),
+        _ => None,
+    };
+    let ignored = [("preset_dirs", preset_dirs), ("pomodoro.hooks", hooks)];
+    for (key, _) in ignored.iter().filter(|(_, value)| value.is_some()) {
+        warn!(
+            "{}",
+            t!("config.untrusted_ignored", path = path.display(), key = key)
+        );
+    }
+}
+
+/// TOML の設定ファイルを記述を保ったまま編集して書き込む
+fn edit_toml_file(path: &Path, edit: impl FnOnce(&mut DocumentMut) -> Result<()>) -> Result<()> {
+    let content = std::fs::read_to_string(path).map_err(|e| Error::config_load(e).in_file(path))?;
+    let mut document: DocumentMut = content
+        .parse()
+        .map_err(|e: TomlError| Error::config_load(e).in_file(path))?;
+    edit(&mut document)?;
+    std::fs::write(path, document.to_string()).map_err(|e| Error::config_save(e).in_file(path))
+}
+
+/// 文書の `presets` テーブルを取得（ない場合は末尾に作成）
+fn presets_table(document: &mut DocumentMut) -> &mut dyn TableLike {
+    let presets = document.entry("presets").or_insert_with(|| {
+        let mut table = Table::new();
+        table.set_implicit(true);
+        Item::Table(table)
+    });
+    if presets.as_table_like().is_none() {
+        *presets = Item::Table(Table::new());
+    }
+    presets.as_table_like_mut().expect("テーブルに置き換え済み")
+}
+
+/// プリセットを `[presets.<name>]` のテーブルに変換
+fn preset_item(name: &str, preset: &Preset) -> Result<Item> {
+    let presets = BTreeMap::from([("presets", BTreeMap::from([(name, preset)]))]);
+    let content = ConfigFormat::Toml.to_string(&presets)?;
+    let mut document: DocumentMut = content
+        .parse()
+        .map_err(|e: TomlError| Error::config_save(e))?;
+    let item = document
+        .remove("presets")
+        .and_then(|mut presets| presets.as_table_like_mut()?.remove(name))
+        .expect("変換したプリセットが含まれる");
+    Ok(detach_item(item))
+}
+
+/// 別の文書で作成したテーブルの位置を消す（挿入先の文書では直前のテーブルの後に書き込まれる）
+fn detach_item(item: Item) -> Item {
+    match item {
+        Item::Table(table) => Item::Table(detach_table(table)),
+        Item::ArrayOfTables(array) => {
+            let mut detached = ArrayOfTables::new();
+            for table in array {
+                detached.push(detach_table(table));
+            }
+            Item::ArrayOfTables(detached)
+        }
+        item => item,
+    }
+}
+
+fn detach_table(table: Table) -> Table {
+    let mut detached = Table::new();
+    detached.set_implicit(table.is_implicit());
+    detached.set_dotted(table.is_dotted());
+    *detached.decor_mut() = table.decor().clone();
+    for (key, item) in table {
+        detached.insert(&key, detach_item(item));
+    }
+    detached
+}
+
+/// 設定の値の表に別の値の表を重ねる（[`Config::layer_file`] を参照）
+fn layer_table(base: &mut toml::Table, over: toml::Table) {
+    use toml::Value;
+
+    for (key, value) in over {
+        match (key.as_str(), base.get_mut(&key), value) {
+            ("presets", Some(Value::Table(presets)), Value::Table(over)) => presets.extend(over),
+            ("preset_dirs", Some(Value::Array(dirs)), Value::Array(over)) => dirs.extend(over),
+            ("applications", Some(Value::Array(applications)), Value::Array(over)) => {
+                for application in over {
+                    let index = application.get("index").cloned();
+                    let existing = applications
+                        .iter_mut()
+                        .find(|a| a.get("index").cloned() == index);
+                    match existing {
+                        Some(existing) => *existing = application,
+                        None => applications.push(application),
+                    }
+                }
+            }
+            (_, Some(Value::Table(table)), Value::Table(over)) => merge_table(table, over),
+            (_, _, value) => {
+                base.insert(key, value);
+            }
+        }
+    }
+}
+
+/// テーブルをキーごとに再帰的に重ねる
+fn merge_table(base: &mut toml::Table, over: toml::Table) {
+    for (key, value) in over {
+        match (base.get_mut(&key), value) {
+            (Some(toml::Value::Table(table)), toml::Value::Table(over)) => merge_table(table, over),
+            (_, value) => {
+                base.insert(key, value);
+            }
+        }
+    }
+}
+
+/// 値の表を読み込む
+///
+/// 内容を変更していない場合は、エラーメッセージに元のファイルの位置を含めるため文字列から直接読み込む
+fn deserialize_table<T: DeserializeOwned>(
+    content: &str,
+    format: ConfigFormat,
+    table: toml::Table,
+    modified: bool,
+) -> Result<T> {
+    if !modified {
+        return format.deserialize(content);
+    }
+    T::deserialize(table).map_err(|e| {
+        Error::config_load(ConfigError::Parse {
+            format,
+            span: None,
+            source: Box::new(e),
+        })
+    })
+}
+
+/// 値の表の参照を展開してから内容を検査
+///
+/// 内容を変更していない場合は、キーの位置を得るため元の文字列を検査する
+fn check_table<T: DeserializeOwned>(
+    source: &str,
+    format: ConfigFormat,
+    mut table: toml::Table,
+    modified: bool,
+    base_dir: &Path,
+) -> (Option<T>, Vec<Diagnostic>) {
+    let (references, mut diagnostics) = expand::expand(&mut table, base_dir, &env_var);
+    let (value, found) = if !diagnostics.is_empty() {
+        (None, Vec::new())
+    } else if !modified && references.is_empty() {
+        return check_format(source, format);
+    } else {
+        check_value(toml::Value::Table(table))
+    };
+    diagnostics.extend(found);
+
+    if format == ConfigFormat::Toml {
+        for diagnostic in &mut diagnostics {
+            diagnostic.locate(source);
+        }
+    }
+    (value, diagnostics)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// テスト用の一時ディレクトリ
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("rp-core-{}-{}", name, std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn layer_file_does_not_trust_project_file() {
+        let dir = temp_dir("layer-untrusted");
+        std::fs::write(dir.join("secret.txt"), "token").unwrap();
+        let project = dir.join(".discord-rp.toml");
+        std::fs::write(
+            &project,
+            r#"
+preset_dirs = ["presets"]
+
+[activity]
+details = "${HOME}"
+state = "file:secret.txt"
+
+[pomodoro]
+work = 50
+
+[pomodoro.hooks]
+work = "touch pwned"
+"#,
+        )
+        .unwrap();
+
+        let mut config = Config::parse("[pomodoro.hooks]\nfinish = \"notify-send done\"\n").unwrap();
+        let result = config.layer_file(&project);
+        std::fs::remove_dir_all(&dir).unwrap();
+        result.unwrap();
+
+        // 参照は展開しない
+        assert_eq!(config.activity.details.as_deref(), Some("${HOME}"));
+        assert_eq!(config.activity.state.as_deref(), Some("file:secret.txt"));
+        // フックとプリセットディレクトリは無視し、それ以外は重ねる
+        assert_eq!(config.pomodoro.work, 50);
+        assert_eq!(config.pomodoro.hooks.work, None);
+        assert_eq!(
+            config.pomodoro.hooks.finish.as_deref(),
+            Some("notify-send done")
+        );
+        assert!(config.preset_dirs.is_empty());
+        assert_eq!(config.sources(), [project]);
+    }
+
+    #[test]
+    fn resolve_preset_merges_from_base() {
+        let config = Config::parse(
+            r#"
+[presets.base]
+app = 1
+elapsed = true
+activity = { details = "Base", state = "base state", assets = { large_image = "logo" } }
+
+[presets.team]
+extends = "base"
+app = 2
+activity = { details = "Team" }
+
+[presets.mine]
+extends = "team"
+activity = { assets = { large_image = "me" } }
+
+[presets.loop-a]
+extends = "loop-b"
+
+[presets.loop-b]
+extends = "loop-a"
+
+[presets.orphan]
+extends = "missing"
+"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            config.preset_chain("mine").unwrap(),
+            ["base", "team", "mine"]
+        );
+        let mine = config.resolve_preset("mine").unwrap();
+        assert_eq!(mine.app, Some(2));
+        assert_eq!(mine.elapsed, Some(true));
+        assert_eq!(mine.activity.details.as_deref(), Some("Team"));
+        assert_eq!(mine.activity.state.as_deref(), Some("base state"));
+        assert_eq!(
+            mine.activity.assets.unwrap().large_image.as_deref(),
+            Some("me")
+        );
+
+        assert!(matches!(
+            config.resolve_preset("loop-a"),
+            Err(Error::InvalidPreset(_))
+        ));
+        assert!(matches!(
+            config.resolve_preset("orphan"),
+            Err(Error::InvalidPreset(_))
+        ));
+        assert!(matches!(
+            config.resolve_preset("missing"),
+            Err(Error::PresetNotFound(_))
+        ));
+    }
+
+    #[test]
+    fn preset_dirs_load_in_order() {
+        let dir = temp_dir("preset-dirs");
+        for (path, content) in [
+            (
+                "team/a.toml",
+                "[presets.shared]\nactivity = { details = \"team a\" }\n",
+            ),
+            (
+                "team/b.toml",
+                "[presets.shared]\nactivity = { details = \"team b\" }\n[presets.own]\nactivity = { details = \"team\" }\n",
+            ),
+            ("team/notes.txt", "[presets.ignored]\n"),
+            ("local/a.toml", "[presets.local]\nextends = \"shared\"\n"),
+        ] {
+            let path = dir.join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, content).unwrap();
+        }
+
+        let mut config = Config::parse(
+            r#"
+preset_dirs = ["team", "local"]
+
+[presets.own]
+activity = { details = "config" }
+"#,
+        )
+        .unwrap();
+        let result = config.load_preset_dirs(&dir);
+        std::fs::remove_dir_all(&dir).unwrap();
+        result.unwrap();
+
+        // ファイル名順で後のファイルが優先され、設定ファイル自身の定義が最も優先される
+        let details = |name: &str| config.resolve_preset(name).unwrap().activity.details;
+        assert_eq!(details("shared").as_deref(), Some("team b"));
+        assert_eq!(details("local").as_deref(), Some("team b"));
+        assert_eq!(details("own").as_deref(), Some("config"));
+        assert_eq!(
+            config.preset_source("shared"),
+            Some(dir.join("team/b.toml").as_path())
+        );
+        assert_eq!(config.preset_source("own"), None);
+        assert!(config.get_preset("ignored").is_err());
+    }
+
+    #[test]
+    fn save_and_delete_preset_keep_file_layout() {
+        let dir = temp_dir("preset-edit");
+        let path = dir.join("config.toml");
+        let original = r#"# 共通の設定
+version = 2
+reconnect_interval = 10
+
+[activity]
+details = "${RP_TEST_UNSET:-coding}" # 参照は展開しない
+
+# 朝のプリセット
+[presets.morning]
+app = 1
+activity = { details = "Morning" }
+"#;
+        std::fs::write(&path, original).unwrap();
+
+        let preset = Preset::new(Activity::new().details("Studying").state("math")).app(2);
+        Config::save_preset(&path, "study", &preset).unwrap();
+        let saved = std::fs::read_to_string(&path).unwrap();
+        let config = Config::load(&path).unwrap();
+
+        Config::delete_preset(&path, "study").unwrap();
+        let deleted = std::fs::read_to_string(&path).unwrap();
+        let missing = Config::delete_preset(&path, "study");
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert!(saved.starts_with(original), "{saved}");
+        assert_eq!(config.get_preset("study").unwrap().app, Some(2));
+        assert_eq!(
+            config
+                .get_preset("study")
+                .unwrap()
+                .activity
+                .state
+                .as_deref(),
+            Some("math")
+        );
+        assert_eq!(deleted, original);
+        assert!(matches!(missing, Err(Error::PresetNotFound(_))));
+    }
+
+    #[test]
+    fn parse_errors_keep_parser_error_as_source() {
+        use std::error::Error as _;
+
+        let error = Config::parse_as("[activity]\ndetails = \n", ConfigFormat::Toml).unwrap_err();
+        let Error::ConfigLoadFailed {
+            source: ConfigError::Parse { span, source, .. },
+            ..
+        } = &error
+        else {
+            panic!("{}", error.full_message());
+        };
+        assert!(span.is_some());
+        assert!(source.downcast_ref::<toml::de::Error>().is_some());
+        assert!(
+            error
+                .source()
+                .and_then(|e| e.source())
+                .is_some_and(|e| e.is::<toml::de::Error>())
+        );
+
+        let dir = temp_dir("broken-preset-file");
+        let path = dir.join("config.toml");
+        std::fs::write(&path, "[presets.work\n").unwrap();
+        let error = Config::delete_preset(&path, "work").unwrap_err();
+        let Error::ConfigLoadFailed {
+            source: ConfigError::Parse { source, .. },
+            ..
+        } = error
+        else {
+            panic!("{}", error.full_message());
+        };
+        assert!(source.is::<toml_edit::TomlError>());
     }
 }
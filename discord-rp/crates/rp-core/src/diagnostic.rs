@@ -0,0 +1,256 @@
+//! 設定ファイルの検査結果（診断）
+
+use crate::format::ConfigFormat;
+use serde::Deserialize;
+use serde::de::{DeserializeOwned, Deserializer};
+use std::fmt;
+use std::ops::Range;
+use std::path::PathBuf;
+use toml_edit::{ImDocument, Item, TableLike};
+
+/// 設定ファイルの問題
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    /// 問題のあるファイル（環境変数など、ファイルに由来しない場合は `None`）
+    pub file: Option<PathBuf>,
+    /// 問題のあるキーのパス（例: `["presets", "work", "app"]`）
+    pub key: Vec<String>,
+    /// 問題の内容
+    pub message: String,
+    /// ファイル内の位置（バイト単位の範囲）
+    pub span: Option<Range<usize>>,
+}
+
+impl Diagnostic {
+    /// キーのパスと内容を指定して作成
+    pub fn new<K: Into<String>>(
+        key: impl IntoIterator<Item = K>,
+        message: impl Into<String>,
+    ) -> Self {
+        Self {
+            file: None,
+            key: key.into_iter().map(Into::into).collect(),
+            message: message.into(),
+            span: None,
+        }
+    }
+
+    /// 問題のあるファイルを設定
+    pub fn file(mut self, path: impl Into<PathBuf>) -> Self {
+        self.file = Some(path.into());
+        self
+    }
+
+    /// ファイル内の位置を設定
+    pub fn span(mut self, span: Range<usize>) -> Self {
+        self.span = Some(span);
+        self
+    }
+
+    /// キーのパスの前に親のキーを付ける
+    pub fn prefixed<K: Into<String>>(mut self, prefix: impl IntoIterator<Item = K>) -> Self {
+        let mut key: Vec<String> = prefix.into_iter().map(Into::into).collect();
+        key.append(&mut self.key);
+        self.key = key;
+        self
+    }
+
+    /// キーのパスを TOML のドット区切り形式で取得（例: `presets.work.app`）
+    pub fn key_path(&self) -> String {
+        self.key
+            .iter()
+            .map(|key| {
+                toml_edit::Key::new(key.as_str())
+                    .display_repr()
+                    .into_owned()
+            })
+            .collect::<Vec<_>>()
+            .join(".")
+    }
+
+    /// ファイルの内容から位置を探して設定（位置が設定済みの場合は何もしない）
+    pub fn locate(&mut self, source: &str) {
+        if self.span.is_none() {
+            self.span = find_key_span(source, &self.key);
+        }
+    }
+
+    /// 問題の位置の行番号と列番号（1始まり、列は文字単位）
+    pub fn line_col(&self, source: &str) -> Option<(usize, usize)> {
+        self.span.as_ref().map(|span| line_col(source, span.start))
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(ref file) = self.file {
+            write!(f, "{}: ", file.display())?;
+        }
+        if !self.key.is_empty() {
+            write!(f, "{}: ", self.key_path())?;
+        }
+        write!(f, "{}", self.message)
+    }
+}
+
+/// TOML をデシリアライズし、構文エラー・未知のキー・型の誤りを診断として収集
+///
+/// 型の誤りはデシリアライズが中断するため最初の1件のみ報告する
+pub(crate) fn check_toml<T: DeserializeOwned>(source: &str) -> (Option<T>, Vec<Diagnostic>) {
+    if let Err(e) = ImDocument::parse(source) {
+        let mut diagnostic = Diagnostic::new(Vec::<String>::new(), e.message().trim_end());
+        diagnostic.span = e.span();
+        return (None, vec![diagnostic]);
+    }
+
+    let (value, mut diagnostics) = check_deserialize(toml::Deserializer::new(source));
+    for diagnostic in &mut diagnostics {
+        diagnostic.locate(source);
+    }
+    (value, diagnostics)
+}
+
+/// 指定した形式の内容をデシリアライズし、構文エラー・未知のキー・型の誤りを診断として収集
+///
+/// TOML 以外の形式では構文エラーの位置のみを設定する
+pub(crate) fn check_format<T: DeserializeOwned>(
+    source: &str,
+    format: ConfigFormat,
+) -> (Option<T>, Vec<Diagnostic>) {
+    if format == ConfigFormat::Toml {
+        return check_toml(source);
+    }
+    if let Err(e) = format.ensure_enabled() {
+        return (
+            None,
+            vec![Diagnostic::new(Vec::<String>::new(), e.to_string())],
+        );
+    }
+
+    match format.parse_located(source) {
+        Ok(table) => check_value(toml::Value::Table(table)),
+        Err(e) => {
+            let mut diagnostic = Diagnostic::new(Vec::<String>::new(), e.message);
+            diagnostic.span = e.span;
+            (None, vec![diagnostic])
+        }
+    }
+}
+
+/// パース済みの値をデシリアライズし、未知のキー・型の誤りを診断として収集
+///
+/// 位置は設定しないため、TOML の場合は必要に応じて [`Diagnostic::locate`] で探す
+pub(crate) fn check_value<T: DeserializeOwned>(value: toml::Value) -> (Option<T>, Vec<Diagnostic>) {
+    check_deserialize(value)
+}
+
+/// デシリアライズ中の未知のキーと最初の型の誤りを診断として収集
+fn check_deserialize<'de, T, D>(deserializer: D) -> (Option<T>, Vec<Diagnostic>)
+where
+    T: Deserialize<'de>,
+    D: Deserializer<'de, Error = toml::de::Error>,
+{
+    let mut diagnostics = Vec::new();
+    let mut on_ignored = |path: serde_ignored::Path<'_>| {
+        diagnostics.push(Diagnostic::new(
+            ignored_path(&path),
+            t!("diagnostic.unknown_key"),
+        ));
+    };
+    let deserializer = serde_ignored::Deserializer::new(deserializer, &mut on_ignored);
+
+    let result = serde_path_to_error::deserialize(deserializer);
+    let value = match result {
+        Ok(value) => Some(value),
+        Err(e) => {
+            let key = e
+                .path()
+                .iter()
+                .filter_map(|segment| match segment {
+                    serde_path_to_error::Segment::Seq { index } => Some(index.to_string()),
+                    serde_path_to_error::Segment::Map { key } => Some(key.clone()),
+                    serde_path_to_error::Segment::Enum { variant } => Some(variant.clone()),
+                    serde_path_to_error::Segment::Unknown => None,
+                })
+                .collect::<Vec<_>>();
+            let mut diagnostic = Diagnostic::new(key, e.inner().message().trim_end());
+            diagnostic.span = e.inner().span();
+            diagnostics.push(diagnostic);
+            None
+        }
+    };
+    (value, diagnostics)
+}
+
+/// 未知のキーのパスをキーの列に変換
+fn ignored_path(path: &serde_ignored::Path<'_>) -> Vec<String> {
+    use serde_ignored::Path;
+
+    match path {
+        Path::Root => Vec::new(),
+        Path::Seq { parent, index } => {
+            let mut key = ignored_path(parent);
+            key.push(index.to_string());
+            key
+        }
+        Path::Map { parent, key: name } => {
+            let mut key = ignored_path(parent);
+            key.push(name.clone());
+            key
+        }
+        Path::Some { parent }
+        | Path::NewtypeStruct { parent }
+        | Path::NewtypeVariant { parent } => ignored_path(parent),
+    }
+}
+
+/// キーのパスに対応するファイル内の位置を取得
+///
+/// パスの途中までしか見つからない場合は、見つかった最も深いキーの位置を返す
+fn find_key_span(source: &str, key: &[String]) -> Option<Range<usize>> {
+    let document = ImDocument::parse(source).ok()?;
+    let mut table: &dyn TableLike = document.as_table();
+    let mut span = None;
+
+    let mut segments = key.iter();
+    while let Some(segment) = segments.next() {
+        let Some((key, item)) = table.get_key_value(segment) else {
+            break;
+        };
+        span = key.span().or(span);
+
+        table = match item {
+            // 配列のテーブルは次のセグメントを要素の番号として扱う
+            Item::ArrayOfTables(array) => {
+                let Some(element) = segments
+                    .next()
+                    .and_then(|index| index.parse().ok())
+                    .and_then(|index| array.get(index))
+                else {
+                    break;
+                };
+                span = element.span().or(span);
+                element
+            }
+            item => match item.as_table_like() {
+                Some(table) => table,
+                None => break,
+            },
+        };
+    }
+
+    span
+}
+
+/// バイト位置を1始まりの行番号と列番号（文字単位）に変換
+fn line_col(source: &str, offset: usize) -> (usize, usize) {
+    let mut offset = offset.min(source.len());
+    while !source.is_char_boundary(offset) {
+        offset -= 1;
+    }
+    let before = &source[..offset];
+    let line = before.matches('\n').count() + 1;
+    let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+    let column = before[line_start..].chars().count() + 1;
+    (line, column)
+}
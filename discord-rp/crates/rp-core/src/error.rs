@@ -1,41 +1,425 @@
 //! エラー型の定義
 
+use crate::Diagnostic;
+use crate::format::ConfigFormat;
+use std::fmt;
+use std::io;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::string::FromUtf8Error;
 use thiserror::Error;
 
 /// Rich Presenceクライアントのエラー型
+///
+/// 下位のエラー（入出力、パースなど）は [`source`](std::error::Error::source) からたどれる。
+/// 原因を含めたメッセージは [`full_message`](Error::full_message) で取得できる。
+/// メッセージは現在の言語（[`Locale::current`](crate::Locale::current)）で表示する。
+/// 言語に依存しない識別には [`code`](Error::code) を使用する
 #[derive(Debug, Error)]
+#[non_exhaustive]
 pub enum Error {
-    /// Discord IPCへの接続に失敗
-    #[error("Discord への接続に失敗しました: {0}")]
-    ConnectionFailed(String),
+    /// Discord IPCとの通信に失敗（ハンドシェイクの失敗や接続の拒否など）
+    ConnectionFailed {
+        #[source]
+        source: IpcError,
+    },
 
     /// Discordクライアントが起動していない
-    #[error("Discord が起動していません")]
     DiscordNotRunning,
 
     /// アクティビティの更新に失敗
-    #[error("アクティビティの更新に失敗しました: {0}")]
-    ActivityUpdateFailed(String),
+    ActivityUpdateFailed {
+        #[source]
+        source: IpcError,
+    },
 
     /// 設定ファイルの読み込みに失敗
-    #[error("設定ファイルの読み込みに失敗しました: {0}")]
-    ConfigLoadFailed(String),
+    ConfigLoadFailed {
+        /// 読み込んだファイル（環境変数など、ファイルに由来しない場合は `None`）
+        path: Option<PathBuf>,
+        #[source]
+        source: ConfigError,
+    },
 
     /// 設定ファイルの保存に失敗
-    #[error("設定ファイルの保存に失敗しました: {0}")]
-    ConfigSaveFailed(String),
+    ConfigSaveFailed {
+        /// 書き込んだファイル（ファイルに由来しない場合は `None`）
+        path: Option<PathBuf>,
+        #[source]
+        source: ConfigError,
+    },
 
     /// 無効なApplication ID
-    #[error("無効な Application ID です: {0}")]
     InvalidApplicationId(String),
 
+    /// 指定されたプリセットが存在しない
+    PresetNotFound(String),
+
+    /// プリセットの定義が不正（継承の循環など）
+    InvalidPreset(String),
+
+    /// 必須の変数が指定されていない
+    MissingVariable(String),
+
+    /// テンプレートの書式が不正
+    InvalidTemplate(String),
+
+    /// アクティビティが Discord の制限（文字数、タイムスタンプの順序）を満たしていない
+    InvalidActivity(Vec<Diagnostic>),
+
+    /// スケジュールの指定が不正（時刻の書式など）
+    InvalidSchedule(String),
+
+    /// ポモドーロタイマーの設定が不正（長さが 0 分など）
+    InvalidPomodoro(String),
+
     /// 接続が切断された
-    #[error("Discord との接続が切断されました")]
     Disconnected,
 
     /// Rate limit超過
-    #[error("更新頻度が高すぎます。15秒以上の間隔を空けてください")]
     RateLimited,
+
+    /// デーモンが起動していない
+    DaemonNotRunning,
+
+    /// デーモンのソケットとの通信に失敗
+    DaemonConnectionFailed {
+        /// デーモンのソケットのパス
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+
+    /// デーモンとの間のメッセージが不正
+    InvalidMessage {
+        #[source]
+        source: serde_json::Error,
+    },
+
+    /// デーモンとプロトコルのバージョンが異なる
+    ProtocolMismatch {
+        /// 受け取ったメッセージのバージョン（ない場合は `None`）
+        version: Option<u64>,
+        /// 対応しているバージョン
+        expected: u32,
+    },
+
+    /// デーモンでの処理に失敗（デーモンが返したエラー）
+    DaemonFailed(String),
+}
+
+/// Discord IPCのエラー
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum IpcError {
+    /// ソケットの読み書きに失敗
+    Io(io::Error),
+
+    /// Discord がエラーを返した（存在しないApplication IDなど）
+    Rejected {
+        /// Discord のエラーコード
+        code: Option<i64>,
+        /// Discord のエラーメッセージ
+        message: String,
+    },
+
+    /// Discord の応答の形式が不正（JSON として読めないなど）
+    InvalidResponse {
+        /// 応答を読み取れなかった原因
+        source: BoxError,
+    },
+
+    /// Discord が想定と異なるオペコードで応答した
+    UnexpectedOpcode {
+        /// 想定していたオペコード
+        expected: u32,
+        /// 受け取ったオペコード
+        actual: u32,
+    },
+}
+
+/// 下位のライブラリのエラー（原因として保持する）
+pub type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+impl IpcError {
+    /// discord-rich-presence のエラーから変換
+    ///
+    /// 入出力と応答の読み取りのエラーはそのまま保持する。
+    /// それ以外のエラーはスレッド間で送れないため、メッセージのみを保持する
+    pub(crate) fn from_boxed(error: Box<dyn std::error::Error>) -> Self {
+        let error = match error.downcast::<io::Error>() {
+            Ok(e) => return IpcError::Io(*e),
+            Err(e) => e,
+        };
+        let source: BoxError = match error.downcast::<serde_json::Error>() {
+            Ok(e) => e,
+            Err(e) => match e.downcast::<FromUtf8Error>() {
+                Ok(e) => e,
+                Err(e) => e.to_string().into(),
+            },
+        };
+        IpcError::InvalidResponse { source }
+    }
+
+    /// 入出力のエラーの種類（入出力のエラーでない場合は `None`）
+    pub fn io_kind(&self) -> Option<io::ErrorKind> {
+        match self {
+            IpcError::Io(e) => Some(e.kind()),
+            _ => None,
+        }
+    }
+}
+
+/// 設定ファイルのエラー
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum ConfigError {
+    /// ファイルやディレクトリの読み書きに失敗
+    Io(io::Error),
+
+    /// 構文やデータ型の誤り
+    Parse {
+        /// ファイルの形式
+        format: ConfigFormat,
+        /// ファイル内の位置（バイト単位の範囲、分かる場合のみ）
+        span: Option<Range<usize>>,
+        /// パーサーのエラー
+        source: BoxError,
+    },
+
+    /// 値の変換に失敗
+    Serialize {
+        /// ファイルの形式
+        format: ConfigFormat,
+        /// シリアライザーのエラーメッセージ
+        message: String,
+    },
+
+    /// 形式に対応する機能が無効
+    FormatDisabled(ConfigFormat),
+
+    /// 内容の誤り（バージョン、移行、値の参照など）
+    Invalid(String),
+}
+
+impl Error {
+    /// エラーの種類を表すコード（機械可読な出力用。バリアントごとに固定で、変更しない）
+    pub fn code(&self) -> &'static str {
+        match self {
+            Error::ConnectionFailed { .. } => "connection_failed",
+            Error::DiscordNotRunning => "discord_not_running",
+            Error::ActivityUpdateFailed { .. } => "activity_update_failed",
+            Error::ConfigLoadFailed { .. } => "config_load_failed",
+            Error::ConfigSaveFailed { .. } => "config_save_failed",
+            Error::InvalidApplicationId(_) => "invalid_application_id",
+            Error::PresetNotFound(_) => "preset_not_found",
+            Error::InvalidPreset(_) => "invalid_preset",
+            Error::MissingVariable(_) => "missing_variable",
+            Error::InvalidTemplate(_) => "invalid_template",
+            Error::InvalidActivity(_) => "invalid_activity",
+            Error::InvalidSchedule(_) => "invalid_schedule",
+            Error::InvalidPomodoro(_) => "invalid_pomodoro",
+            Error::Disconnected => "disconnected",
+            Error::RateLimited => "rate_limited",
+            Error::DaemonNotRunning => "daemon_not_running",
+            Error::DaemonConnectionFailed { .. } => "daemon_connection_failed",
+            Error::InvalidMessage { .. } => "invalid_message",
+            Error::ProtocolMismatch { .. } => "protocol_mismatch",
+            Error::DaemonFailed(_) => "daemon_failed",
+        }
+    }
+
+    /// 原因を含めたメッセージ（例: `設定ファイルの読み込みに失敗しました: config.toml: No such file or directory`）
+    pub fn full_message(&self) -> String {
+        let mut message = self.to_string();
+        let mut source = std::error::Error::source(self);
+        while let Some(e) = source {
+            message.push_str(": ");
+            message.push_str(e.to_string().trim_end());
+            source = e.source();
+        }
+        message
+    }
+
+    /// Discord との接続に関するエラーか（再接続すると回復する可能性がある）
+    ///
+    /// Discord が要求を拒否した場合や、テンプレートの展開に失敗した場合などは `false`
+    pub fn is_connection_error(&self) -> bool {
+        match self {
+            Error::ConnectionFailed { .. } | Error::DiscordNotRunning | Error::Disconnected => true,
+            Error::ActivityUpdateFailed { source } => !matches!(source, IpcError::Rejected { .. }),
+            _ => false,
+        }
+    }
+
+    /// 設定ファイルの読み込みのエラー（ファイルは [`in_file`](Self::in_file) で設定する）
+    pub(crate) fn config_load(source: impl Into<ConfigError>) -> Self {
+        Error::ConfigLoadFailed {
+            path: None,
+            source: source.into(),
+        }
+    }
+
+    /// 設定ファイルの保存のエラー（ファイルは [`in_file`](Self::in_file) で設定する）
+    pub(crate) fn config_save(source: impl Into<ConfigError>) -> Self {
+        Error::ConfigSaveFailed {
+            path: None,
+            source: source.into(),
+        }
+    }
+
+    /// 設定ファイルのエラーに、まだ設定されていなければファイルを設定する
+    pub(crate) fn in_file(mut self, file: &Path) -> Self {
+        if let Error::ConfigLoadFailed { ref mut path, .. }
+        | Error::ConfigSaveFailed { ref mut path, .. } = self
+        {
+            path.get_or_insert_with(|| file.to_path_buf());
+        }
+        self
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let message = match self {
+            Error::ConfigLoadFailed {
+                path: Some(path), ..
+            } => t!("error.config_load_failed_in", path = path.display()),
+            Error::ConfigSaveFailed {
+                path: Some(path), ..
+            } => t!("error.config_save_failed_in", path = path.display()),
+            Error::InvalidApplicationId(message)
+            | Error::InvalidPreset(message)
+            | Error::InvalidTemplate(message)
+            | Error::InvalidSchedule(message)
+            | Error::InvalidPomodoro(message)
+            | Error::DaemonFailed(message) => {
+                t!(&format!("error.{}", self.code()), message = message)
+            }
+            Error::InvalidActivity(diagnostics) => t!(
+                "error.invalid_activity",
+                problems = diagnostics
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect::<Vec<_>>()
+                    .join("; ")
+            ),
+            Error::PresetNotFound(name) | Error::MissingVariable(name) => {
+                t!(&format!("error.{}", self.code()), name = name)
+            }
+            Error::RateLimited => t!(
+                "error.rate_limited",
+                seconds = crate::client::MIN_UPDATE_INTERVAL.as_secs()
+            ),
+            Error::DaemonConnectionFailed { path, .. } => {
+                t!("error.daemon_connection_failed", path = path.display())
+            }
+            Error::ProtocolMismatch { version, expected } => t!(
+                "error.protocol_mismatch",
+                version = version.map_or_else(|| t!("error.unknown_version"), |v| v.to_string()),
+                expected = expected
+            ),
+            _ => t!(&format!("error.{}", self.code())),
+        };
+        f.write_str(&message)
+    }
+}
+
+impl fmt::Display for IpcError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let message = match self {
+            IpcError::Io(e) => return e.fmt(f),
+            IpcError::Rejected {
+                code: Some(code),
+                message,
+            } => t!(
+                "ipc_error.rejected_with_code",
+                code = code,
+                message = message
+            ),
+            IpcError::Rejected {
+                code: None,
+                message,
+            } => t!("ipc_error.rejected", message = message),
+            IpcError::InvalidResponse { .. } => t!("ipc_error.invalid_response"),
+            IpcError::UnexpectedOpcode { expected, actual } => t!(
+                "ipc_error.unexpected_opcode",
+                expected = expected,
+                actual = actual
+            ),
+        };
+        f.write_str(&message)
+    }
+}
+
+/// 入出力のエラーは、メッセージと原因をそのまま使う
+impl std::error::Error for IpcError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            IpcError::Io(e) => e.source(),
+            IpcError::InvalidResponse { source } => Some(source.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for IpcError {
+    fn from(error: io::Error) -> Self {
+        IpcError::Io(error)
+    }
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let message = match self {
+            ConfigError::Io(e) => return e.fmt(f),
+            ConfigError::Parse { format, .. } => t!("config_error.parse", format = format),
+            ConfigError::Serialize { format, message } => {
+                t!("config_error.serialize", format = format, message = message)
+            }
+            ConfigError::FormatDisabled(format) => t!(
+                "config_error.format_disabled",
+                format = format,
+                feature = format.extension()
+            ),
+            ConfigError::Invalid(message) => return f.write_str(message),
+        };
+        f.write_str(&message)
+    }
+}
+
+/// 入出力のエラーは、メッセージと原因をそのまま使う
+impl std::error::Error for ConfigError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ConfigError::Io(e) => e.source(),
+            ConfigError::Parse { source, .. } => Some(source.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for ConfigError {
+    fn from(error: io::Error) -> Self {
+        ConfigError::Io(error)
+    }
+}
+
+/// TOML の文書としてのパースエラー（記述を保ったまま書き換える場合）
+impl From<toml_edit::TomlError> for ConfigError {
+    fn from(error: toml_edit::TomlError) -> Self {
+        ConfigError::Parse {
+            format: ConfigFormat::Toml,
+            span: error.span(),
+            source: Box::new(error),
+        }
+    }
+}
+
+impl From<String> for ConfigError {
+    fn from(message: String) -> Self {
+        ConfigError::Invalid(message)
+    }
 }
 
 /// Result型のエイリアス
@@ -0,0 +1,321 @@
+//! 設定値の参照（`${VAR}`, `${VAR:-default}`, `file:`）の展開
+
+use crate::Diagnostic;
+use std::path::Path;
+use toml::{Table, Value};
+
+/// 参照を展開した値
+///
+/// 保存時に元の参照へ戻し、展開した値（秘密の値など）をファイルに書き込まないために記録する
+#[derive(Debug, Clone)]
+pub(crate) struct Reference {
+    key: Vec<String>,
+    original: String,
+    expanded: String,
+}
+
+/// 表の文字列の値に含まれる参照を展開
+///
+/// `file:` の相対パスは `base_dir` を基準とする。
+/// 展開できなかった値は変更せず、キーのパスとともに診断として返す
+pub(crate) fn expand(
+    table: &mut Table,
+    base_dir: &Path,
+    var: &dyn Fn(&str) -> Option<String>,
+) -> (Vec<Reference>, Vec<Diagnostic>) {
+    let mut expansion = Expansion {
+        base_dir,
+        var,
+        key: Vec::new(),
+        references: Vec::new(),
+        diagnostics: Vec::new(),
+    };
+    expansion.table(table);
+    (expansion.references, expansion.diagnostics)
+}
+
+/// 参照を含む値のキーのパスを列挙（展開はしない）
+///
+/// 展開を許可しないファイル（プロジェクトの設定ファイル）の検査に使う
+pub(crate) fn find(table: &Table) -> Vec<Vec<String>> {
+    fn walk(value: &Value, key: &mut Vec<String>, found: &mut Vec<Vec<String>>) {
+        match value {
+            Value::String(s) if s.contains("${") || s.starts_with("file:") => {
+                found.push(key.clone());
+            }
+            Value::Array(array) => {
+                for (index, value) in array.iter().enumerate() {
+                    key.push(index.to_string());
+                    walk(value, key, found);
+                    key.pop();
+                }
+            }
+            Value::Table(table) => {
+                for (name, value) in table {
+                    key.push(name.clone());
+                    walk(value, key, found);
+                    key.pop();
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let mut found = Vec::new();
+    for (name, value) in table {
+        walk(value, &mut vec![name.clone()], &mut found);
+    }
+    found
+}
+
+/// 展開した値を元の参照に戻す（展開後に値が変更されていない場合のみ）
+pub(crate) fn restore(table: &mut Table, references: &[Reference]) {
+    for reference in references {
+        if let Some(Value::String(value)) = get_mut(table, &reference.key)
+            && *value == reference.expanded
+        {
+            *value = reference.original.clone();
+        }
+    }
+}
+
+/// ファイルの内容を読み込む（末尾の改行は除く）
+///
+/// `~/` で始まるパスはホームディレクトリ、相対パスは `base_dir` を基準とする
+pub(crate) fn read_file(path: &str, base_dir: &Path) -> Result<String, String> {
+    let resolved = match path.strip_prefix("~/") {
+        Some(rest) => dirs::home_dir()
+            .ok_or_else(|| t!("expand.no_home"))?
+            .join(rest),
+        None => base_dir.join(path),
+    };
+
+    std::fs::read_to_string(&resolved)
+        .map(|content| content.trim_end_matches(['\r', '\n']).to_string())
+        .map_err(|e| t!("expand.read_failed", path = resolved.display(), error = e))
+}
+
+/// 展開の状態
+struct Expansion<'a> {
+    base_dir: &'a Path,
+    var: &'a dyn Fn(&str) -> Option<String>,
+    /// 処理中の値のキーのパス
+    key: Vec<String>,
+    references: Vec<Reference>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Expansion<'_> {
+    fn table(&mut self, table: &mut Table) {
+        for (key, value) in table.iter_mut() {
+            self.key.push(key.clone());
+            self.value(value);
+            self.key.pop();
+        }
+    }
+
+    fn value(&mut self, value: &mut Value) {
+        match value {
+            Value::String(original) => match self.string(original) {
+                Ok(Some(expanded)) => {
+                    self.references.push(Reference {
+                        key: self.key.clone(),
+                        original: std::mem::replace(original, expanded.clone()),
+                        expanded,
+                    });
+                }
+                Ok(None) => {}
+                Err(message) => self
+                    .diagnostics
+                    .push(Diagnostic::new(self.key.clone(), message)),
+            },
+            Value::Array(array) => {
+                for (index, value) in array.iter_mut().enumerate() {
+                    self.key.push(index.to_string());
+                    self.value(value);
+                    self.key.pop();
+                }
+            }
+            Value::Table(table) => self.table(table),
+            _ => {}
+        }
+    }
+
+    /// 文字列の参照を展開（参照を含まない場合は `None`）
+    ///
+    /// 変数を展開した結果が `file:` で始まる場合は、そのファイルの内容に置き換える
+    fn string(&self, value: &str) -> Result<Option<String>, String> {
+        let vars = expand_vars(value, self.var)?;
+        let expanded = vars.as_deref().unwrap_or(value);
+
+        match expanded.strip_prefix("file:") {
+            Some(path) => read_file(path, self.base_dir).map(Some),
+            None => Ok(vars),
+        }
+    }
+}
+
+/// `${VAR}` と `${VAR:-default}` を展開（`$${` は `${` そのもの）
+///
+/// `${VAR:-default}` は変数が未設定または空の場合にデフォルト値を使う
+fn expand_vars(
+    value: &str,
+    var: &dyn Fn(&str) -> Option<String>,
+) -> Result<Option<String>, String> {
+    if !value.contains("${") {
+        return Ok(None);
+    }
+
+    let mut result = String::new();
+    let mut rest = value;
+    while let Some(start) = rest.find("${") {
+        // $${ はエスケープ
+        if rest[..start].ends_with('$') {
+            result.push_str(&rest[..start - 1]);
+            result.push_str("${");
+            rest = &rest[start + 2..];
+            continue;
+        }
+
+        result.push_str(&rest[..start]);
+        let body_start = start + 2;
+        let end = rest[body_start..]
+            .find('}')
+            .map(|i| body_start + i)
+            .ok_or_else(|| t!("expand.unclosed", value = value))?;
+        let body = &rest[body_start..end];
+
+        let (name, default) = match body.split_once(":-") {
+            Some((name, default)) => (name, Some(default)),
+            None => (body, None),
+        };
+        if !is_valid_name(name) {
+            return Err(t!("expand.invalid_name", body = body));
+        }
+
+        match (var(name).filter(|v| !v.is_empty()), default) {
+            (Some(v), _) => result.push_str(&v),
+            (None, Some(default)) => result.push_str(default),
+            (None, None) if var(name).is_some() => {}
+            (None, None) => {
+                return Err(t!("expand.unset", name = name));
+            }
+        }
+        rest = &rest[end + 1..];
+    }
+    result.push_str(rest);
+
+    Ok(Some(result))
+}
+
+/// 環境変数名として使える文字列か（英字または `_` で始まり、英数字と `_` のみ）
+fn is_valid_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// キーのパスに対応する値を取得（配列は要素の番号で指定）
+fn get_mut<'a>(table: &'a mut Table, key: &[String]) -> Option<&'a mut Value> {
+    let (first, rest) = key.split_first()?;
+    let mut value = table.get_mut(first)?;
+    for segment in rest {
+        value = match value {
+            Value::Table(table) => table.get_mut(segment)?,
+            Value::Array(array) => array.get_mut(segment.parse::<usize>().ok()?)?,
+            _ => return None,
+        };
+    }
+    Some(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn var(name: &str) -> Option<String> {
+        match name {
+            "USER" => Some("alice".to_string()),
+            "EMPTY" => Some(String::new()),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn expand_vars_replaces_references() {
+        let cases = [
+            ("${USER}", "alice"),
+            ("hi ${USER}!", "hi alice!"),
+            ("${USER}-${USER}", "alice-alice"),
+            ("${MISSING:-guest}", "guest"),
+            ("${EMPTY:-guest}", "guest"),
+            ("${USER:-guest}", "alice"),
+            ("${MISSING:-}", ""),
+            ("[${EMPTY}]", "[]"),
+            ("${MISSING:-a:-b}", "a:-b"),
+            ("$${USER}", "${USER}"),
+            ("cost: $$${USER}", "cost: $${USER}"),
+            ("$${USER} ${USER}", "${USER} alice"),
+        ];
+        for (value, expected) in cases {
+            assert_eq!(
+                expand_vars(value, &var).unwrap().as_deref(),
+                Some(expected),
+                "{value}"
+            );
+        }
+        assert_eq!(expand_vars("plain $USER", &var).unwrap(), None);
+    }
+
+    #[test]
+    fn expand_vars_rejects_invalid_references() {
+        for value in [
+            "${MISSING}",
+            "${USER",
+            "${}",
+            "${1ST}",
+            "${USER NAME}",
+            "${:-x}",
+        ] {
+            assert!(expand_vars(value, &var).is_err(), "{value}");
+        }
+    }
+
+    #[test]
+    fn expand_and_restore_references() {
+        let dir = std::env::temp_dir().join(format!("rp-core-expand-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("id"), "123456789012345678\n").unwrap();
+
+        let mut table: Table = toml::from_str(
+            r#"
+plain = "text"
+user = "${USER}"
+id = "file:id"
+list = ["${MISSING:-a}", "b"]
+missing = "${MISSING}"
+"#,
+        )
+        .unwrap();
+        let (references, diagnostics) = expand(&mut table, &dir, &var);
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(table["user"].as_str(), Some("alice"));
+        assert_eq!(table["id"].as_str(), Some("123456789012345678"));
+        assert_eq!(table["list"][0].as_str(), Some("a"));
+        // 展開できなかった値は変更せず、診断として返す
+        assert_eq!(table["missing"].as_str(), Some("${MISSING}"));
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(find(&table), [vec!["missing".to_string()]]);
+
+        // 変更されていない値のみ元の参照に戻す
+        table.insert("user".to_string(), Value::from("bob"));
+        restore(&mut table, &references);
+        assert_eq!(table["plain"].as_str(), Some("text"));
+        assert_eq!(table["user"].as_str(), Some("bob"));
+        assert_eq!(table["id"].as_str(), Some("file:id"));
+        assert_eq!(table["list"][0].as_str(), Some("${MISSING:-a}"));
+    }
+}
@@ -0,0 +1,227 @@
+//! 設定ファイルの形式（TOML / JSON / YAML）
+//!
+//! どの形式も TOML の値（[`toml::Table`]）を共通の表現として扱い、
+//! 形式の移行や検査は同じ処理を使う
+
+use crate::error::{BoxError, ConfigError};
+use crate::{Error, Result};
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use std::fmt;
+use std::ops::Range;
+use std::path::Path;
+
+/// 設定ファイルの形式
+///
+/// JSON と YAML はそれぞれ `json`, `yaml` 機能を有効にした場合のみ使用できる
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfigFormat {
+    /// TOML（`.toml`）
+    Toml,
+    /// JSON（`.json`）
+    Json,
+    /// YAML（`.yaml`, `.yml`）
+    Yaml,
+}
+
+/// パースエラーの内容と位置
+pub(crate) struct ParseError {
+    /// 位置を含まない短いメッセージ（検査結果の表示用）
+    pub message: String,
+    pub span: Option<Range<usize>>,
+    /// パーサーのエラー
+    pub source: BoxError,
+}
+
+impl ConfigFormat {
+    /// 全ての形式
+    pub const ALL: [ConfigFormat; 3] = [ConfigFormat::Toml, ConfigFormat::Json, ConfigFormat::Yaml];
+
+    /// 拡張子から判定（`.json` は JSON、`.yaml` と `.yml` は YAML、それ以外は TOML）
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("json") => ConfigFormat::Json,
+            Some("yaml" | "yml") => ConfigFormat::Yaml,
+            _ => ConfigFormat::Toml,
+        }
+    }
+
+    /// 拡張子が設定ファイルの形式のいずれかか（`.toml`, `.json`, `.yaml`, `.yml`）
+    pub fn is_config_file(path: &Path) -> bool {
+        path.extension()
+            .and_then(|ext| ext.to_str())
+            .is_some_and(|ext| matches!(ext, "toml" | "json" | "yaml" | "yml"))
+    }
+
+    /// 形式の名前
+    pub fn name(self) -> &'static str {
+        match self {
+            ConfigFormat::Toml => "TOML",
+            ConfigFormat::Json => "JSON",
+            ConfigFormat::Yaml => "YAML",
+        }
+    }
+
+    /// 標準の拡張子
+    pub fn extension(self) -> &'static str {
+        match self {
+            ConfigFormat::Toml => "toml",
+            ConfigFormat::Json => "json",
+            ConfigFormat::Yaml => "yaml",
+        }
+    }
+
+    /// この形式を使用できるか（対応する機能が有効か）
+    pub fn is_enabled(self) -> bool {
+        match self {
+            ConfigFormat::Toml => true,
+            ConfigFormat::Json => cfg!(feature = "json"),
+            ConfigFormat::Yaml => cfg!(feature = "yaml"),
+        }
+    }
+
+    /// 使用できない形式の場合のエラー
+    pub(crate) fn ensure_enabled(self) -> Result<()> {
+        if self.is_enabled() {
+            return Ok(());
+        }
+        Err(Error::config_load(ConfigError::FormatDisabled(self)))
+    }
+
+    /// 文字列をパースして値の表に変換
+    pub fn parse(self, content: &str) -> Result<toml::Table> {
+        self.ensure_enabled()?;
+        self.parse_located(content)
+            .map_err(|e| Error::config_load(e.into_config_error(self)))
+    }
+
+    /// 文字列を直接デシリアライズ（エラーメッセージに位置が含まれる）
+    pub(crate) fn deserialize<T: DeserializeOwned>(self, content: &str) -> Result<T> {
+        self.ensure_enabled()?;
+        let result = match self {
+            ConfigFormat::Toml => toml::from_str(content).map_err(|e| ParseError {
+                message: e.message().trim_end().to_string(),
+                span: e.span(),
+                source: Box::new(e),
+            }),
+            #[cfg(feature = "json")]
+            ConfigFormat::Json => serde_json::from_str(content).map_err(|e| ParseError {
+                span: offset_of(content, e.line(), e.column()).map(|offset| offset..offset),
+                message: e.to_string(),
+                source: Box::new(e),
+            }),
+            #[cfg(feature = "yaml")]
+            ConfigFormat::Yaml => serde_yaml_ng::from_str(content).map_err(|e| ParseError {
+                span: e
+                    .location()
+                    .map(|location| location.index()..location.index()),
+                message: e.to_string(),
+                source: Box::new(e),
+            }),
+            #[allow(unreachable_patterns)]
+            _ => unreachable!("無効な形式は ensure_enabled で除外済み"),
+        };
+        result.map_err(|e| Error::config_load(e.into_config_error(self)))
+    }
+
+    /// 値を文字列に変換
+    pub fn to_string<T: Serialize>(self, value: &T) -> Result<String> {
+        if !self.is_enabled() {
+            return Err(Error::config_save(ConfigError::FormatDisabled(self)));
+        }
+        let result = match self {
+            ConfigFormat::Toml => toml::to_string_pretty(value).map_err(|e| e.to_string()),
+            #[cfg(feature = "json")]
+            ConfigFormat::Json => serde_json::to_string_pretty(value)
+                .map(|json| json + "\n")
+                .map_err(|e| e.to_string()),
+            #[cfg(feature = "yaml")]
+            ConfigFormat::Yaml => serde_yaml_ng::to_string(value).map_err(|e| e.to_string()),
+            #[allow(unreachable_patterns)]
+            _ => unreachable!("無効な形式は ensure_enabled で除外済み"),
+        };
+        result.map_err(|message| {
+            Error::config_save(ConfigError::Serialize {
+                format: self,
+                message,
+            })
+        })
+    }
+
+    /// 文字列をパースして値の表に変換（エラーの位置を含む）
+    ///
+    /// 形式が有効であることは呼び出し側で確認する
+    pub(crate) fn parse_located(
+        self,
+        content: &str,
+    ) -> std::result::Result<toml::Table, ParseError> {
+        match self {
+            ConfigFormat::Toml => toml::from_str(content).map_err(|e| ParseError {
+                message: e.message().trim_end().to_string(),
+                span: e.span(),
+                source: Box::new(e),
+            }),
+            #[cfg(feature = "json")]
+            ConfigFormat::Json => serde_json::from_str(content).map_err(|e| ParseError {
+                span: offset_of(content, e.line(), e.column()).map(|offset| offset..offset),
+                message: e.to_string(),
+                source: Box::new(e),
+            }),
+            #[cfg(feature = "yaml")]
+            ConfigFormat::Yaml => serde_yaml_ng::from_str(content).map_err(|e| ParseError {
+                span: e
+                    .location()
+                    .map(|location| location.index()..location.index()),
+                message: e.to_string(),
+                source: Box::new(e),
+            }),
+            #[allow(unreachable_patterns)]
+            _ => {
+                let message = t!("config_error.format_invalid", format = self.name());
+                Err(ParseError {
+                    source: message.clone().into(),
+                    message,
+                    span: None,
+                })
+            }
+        }
+    }
+}
+
+impl ParseError {
+    /// 設定ファイルのエラーに変換
+    pub(crate) fn into_config_error(self, format: ConfigFormat) -> ConfigError {
+        ConfigError::Parse {
+            format,
+            span: self.span,
+            source: self.source,
+        }
+    }
+}
+
+impl fmt::Display for ConfigFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// 1始まりの行番号と列番号（文字単位）をバイト位置に変換
+#[cfg(feature = "json")]
+fn offset_of(content: &str, line: usize, column: usize) -> Option<usize> {
+    let line_start = if line <= 1 {
+        0
+    } else {
+        content
+            .match_indices('\n')
+            .nth(line - 2)
+            .map(|(i, _)| i + 1)?
+    };
+    let rest = &content[line_start..];
+    Some(
+        line_start
+            + rest
+                .char_indices()
+                .nth(column.saturating_sub(1))
+                .map_or(rest.len(), |(i, _)| i),
+    )
+}
@@ -2,12 +2,60 @@
 //!
 //! Discord IPCへの接続、アクティビティ更新、設定管理を提供する
 
+/// rp-core のメッセージカタログからメッセージを取得（`t!("key", name = value)` で `{name}` を置き換える）
+macro_rules! t {
+    ($key:expr) => {
+        $crate::locale::LIBRARY.message($key)
+    };
+    ($key:expr, $($name:ident = $value:expr),+ $(,)?) => {
+        $crate::locale::LIBRARY.format(
+            $key,
+            &[$((stringify!($name), &$value as &dyn std::fmt::Display)),+],
+        )
+    };
+}
+
 mod activity;
+mod app_id;
 mod client;
 mod config;
+mod diagnostic;
 mod error;
+mod expand;
+mod format;
+mod locale;
+mod migrate;
+mod pomodoro;
+mod preset;
+mod protocol;
+mod rotation;
+mod schedule;
+mod session;
+mod sysinfo;
+mod template;
+mod variables;
+mod watch;
 
-pub use activity::{Activity, ActivityAssets, ActivityTimestamps};
-pub use client::RichPresenceClient;
-pub use config::Config;
-pub use error::{Error, Result};
+pub use activity::{Activity, ActivityAssets, ActivityParty, ActivityTemplate, ActivityTimestamps};
+pub use app_id::ApplicationId;
+pub use client::{DiscordUser, RichPresenceClient};
+pub use config::{Application, Config};
+pub use diagnostic::Diagnostic;
+pub use error::{BoxError, ConfigError, Error, IpcError, Result};
+pub use format::ConfigFormat;
+pub use locale::{Catalog, Locale};
+pub use pomodoro::{Pomodoro, PomodoroHooks, PomodoroLabels, PomodoroPhase, PomodoroStep};
+pub use preset::{Preset, PresetVar};
+#[cfg(unix)]
+pub use protocol::DaemonClient;
+pub use protocol::{
+    DaemonStatus, PROTOCOL_VERSION, ReconnectStatus, Request, Response, SOCKET_ENV, SessionStatus,
+    daemon_socket_path, decode_message, encode_message,
+};
+pub use rotation::{Rotation, RotationTimer};
+pub use schedule::{Days, Schedule, ScheduleRule, ScheduleSlot, TimeOfDay};
+pub use session::PresenceSession;
+pub use sysinfo::SystemInfo;
+pub use template::{Template, Variables};
+pub use variables::{Builtin, BuiltinVariables};
+pub use watch::FileWatcher;
@@ -0,0 +1,200 @@
+//! メッセージの言語とメッセージカタログ
+//!
+//! メッセージはキー（例: `error.discord_not_running`）で参照し、言語ごとの TOML ファイルから取得する。
+//! 言語を追加する場合は [`Locale`] にバリアントを追加し、各クレートの `locales/` にファイルを追加する
+
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::LazyLock;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// メッセージの言語
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Locale {
+    /// 日本語
+    #[default]
+    Ja,
+    /// 英語
+    En,
+}
+
+/// 現在の言語（[`Locale::ALL`] のインデックス）
+static CURRENT: AtomicUsize = AtomicUsize::new(0);
+
+impl Locale {
+    /// 全ての言語（先頭はデフォルトの言語）
+    pub const ALL: [Locale; 2] = [Locale::Ja, Locale::En];
+
+    /// 言語コード（`ja`, `en`）
+    pub fn code(self) -> &'static str {
+        match self {
+            Locale::Ja => "ja",
+            Locale::En => "en",
+        }
+    }
+
+    /// ロケール名から判定（`ja`, `ja_JP.UTF-8`, `en-US` など。対応していない言語は `None`）
+    pub fn parse(name: &str) -> Option<Self> {
+        let language = name
+            .split(['_', '-', '.', '@'])
+            .next()
+            .unwrap_or_default()
+            .to_ascii_lowercase();
+        Self::ALL
+            .into_iter()
+            .find(|locale| locale.code() == language)
+    }
+
+    /// 環境変数から判定
+    ///
+    /// `LC_ALL`, `LC_MESSAGES`, `LANG` の順に、最初に設定されている変数を参照する。
+    /// 設定されていない場合や対応していない言語の場合は `None`
+    pub fn from_env() -> Option<Self> {
+        ["LC_ALL", "LC_MESSAGES", "LANG"]
+            .into_iter()
+            .find_map(|key| std::env::var(key).ok().filter(|value| !value.is_empty()))
+            .and_then(|value| Self::parse(&value))
+    }
+
+    /// 現在の言語（[`set_current`](Self::set_current) で設定していない場合はデフォルトの言語）
+    pub fn current() -> Self {
+        Self::ALL[CURRENT.load(Ordering::Relaxed)]
+    }
+
+    /// 現在の言語を設定（メッセージやエラーの表示に使用する）
+    pub fn set_current(locale: Self) {
+        let index = Self::ALL.iter().position(|l| *l == locale).unwrap_or(0);
+        CURRENT.store(index, Ordering::Relaxed);
+    }
+}
+
+impl fmt::Display for Locale {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.code())
+    }
+}
+
+impl FromStr for Locale {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse(s).ok_or_else(|| {
+            let codes = Self::ALL.map(Locale::code);
+            LIBRARY.format(
+                "locale.unsupported",
+                &[("name", &s), ("supported", &codes.join(", "))],
+            )
+        })
+    }
+}
+
+/// メッセージカタログ
+///
+/// 言語ごとに TOML のファイルを追加する。テーブルはキーを `.` でつなげて参照する
+///
+/// ```toml
+/// [error]
+/// preset_not_found = "プリセット {name} が見つかりません"
+/// ```
+///
+/// 現在の言語にメッセージがない場合はデフォルトの言語のメッセージを使用する
+#[derive(Debug, Default)]
+pub struct Catalog {
+    messages: HashMap<Locale, HashMap<String, String>>,
+}
+
+impl Catalog {
+    /// 空のカタログを作成
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 言語のメッセージを追加（同じキーは後から追加したものが優先される）
+    ///
+    /// # Panics
+    ///
+    /// `source` が TOML として不正な場合（カタログはビルド時に埋め込むため、誤りは開発時に検出する）
+    pub fn add(mut self, locale: Locale, source: &str) -> Self {
+        let table: toml::Table = toml::from_str(source)
+            .unwrap_or_else(|e| panic!("{} のメッセージカタログが不正です: {}", locale, e));
+        flatten("", table, self.messages.entry(locale).or_default());
+        self
+    }
+
+    /// 現在の言語のメッセージ（デフォルトの言語にもない場合は `None`）
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.get_in(Locale::current(), key)
+            .or_else(|| self.get_in(Locale::default(), key))
+    }
+
+    /// 指定した言語のメッセージ（デフォルトの言語にはフォールバックしない）
+    pub fn get_in(&self, locale: Locale, key: &str) -> Option<&str> {
+        self.messages.get(&locale)?.get(key).map(String::as_str)
+    }
+
+    /// 現在の言語のメッセージ（見つからない場合はキーをそのまま返す）
+    pub fn message(&self, key: &str) -> String {
+        self.get(key).unwrap_or(key).to_string()
+    }
+
+    /// 現在の言語のメッセージの `{name}` を値に置き換える（`{{`, `}}` は `{`, `}` になる）
+    pub fn format(&self, key: &str, args: &[(&str, &dyn fmt::Display)]) -> String {
+        let Some(message) = self.get(key) else {
+            return key.to_string();
+        };
+
+        let mut output = String::with_capacity(message.len());
+        let mut rest = message;
+        while let Some(start) = rest.find(['{', '}']) {
+            output.push_str(&rest[..start]);
+            let brace = &rest[start..];
+            if brace.starts_with("{{") || brace.starts_with("}}") {
+                output.push_str(&brace[..1]);
+                rest = &brace[2..];
+                continue;
+            }
+            let Some(end) = brace.find('}').filter(|_| brace.starts_with('{')) else {
+                output.push_str(&brace[..1]);
+                rest = &brace[1..];
+                continue;
+            };
+            let name = &brace[1..end];
+            match args.iter().find(|(arg, _)| *arg == name) {
+                Some((_, value)) => output.push_str(&value.to_string()),
+                None => output.push_str(&brace[..=end]),
+            }
+            rest = &brace[end + 1..];
+        }
+        output.push_str(rest);
+        output
+    }
+}
+
+/// テーブルのキーを `.` でつなげて平坦にする
+fn flatten(prefix: &str, table: toml::Table, messages: &mut HashMap<String, String>) {
+    for (key, value) in table {
+        let key = if prefix.is_empty() {
+            key
+        } else {
+            format!("{}.{}", prefix, key)
+        };
+        match value {
+            toml::Value::Table(table) => flatten(&key, table, messages),
+            toml::Value::String(message) => {
+                messages.insert(key, message);
+            }
+            value => {
+                messages.insert(key, value.to_string());
+            }
+        }
+    }
+}
+
+/// rp-core のメッセージカタログ
+pub(crate) static LIBRARY: LazyLock<Catalog> = LazyLock::new(|| {
+    Catalog::new()
+        .add(Locale::Ja, include_str!("../locales/ja.toml"))
+        .add(Locale::En, include_str!("../locales/en.toml"))
+});
@@ -0,0 +1,427 @@
+//! 設定ファイルの形式の移行
+
+use crate::error::ConfigError;
+use crate::{ConfigFormat, Error, Result};
+use chrono::Local;
+use std::path::{Path, PathBuf};
+use toml::{Table, Value};
+use toml_edit::{ArrayOfTables, DocumentMut, Item, RawString};
+
+/// 現在の設定ファイルの形式のバージョン
+pub(crate) const CURRENT_VERSION: u32 = 2;
+
+/// `version` がない設定ファイルのバージョン
+const LEGACY_VERSION: u32 = 1;
+
+/// 1つ前のバージョンから移行する手順
+struct Migration {
+    /// 移行後のバージョン
+    to: u32,
+    /// 変更内容の説明（メッセージカタログのキー）
+    description: &'static str,
+    apply: fn(&mut Table) -> Result<()>,
+    /// TOML の文書を記述（コメント、キーの順序）を保ったまま移行する手順（`apply` と同じ内容になる）
+    edit: fn(&mut DocumentMut) -> Result<()>,
+}
+
+/// 移行手順の一覧（バージョン順）
+const MIGRATIONS: &[Migration] = &[Migration {
+    to: 2,
+    description: "migrate.v1_to_v2",
+    apply: v1_to_v2,
+    edit: v1_to_v2_document,
+}];
+
+impl Migration {
+    /// 移行の失敗を、移行後のバージョンを含むエラーに変換
+    fn failed(&self, e: Error) -> Error {
+        match e {
+            Error::ConfigLoadFailed {
+                path,
+                source: ConfigError::Invalid(msg),
+            } => Error::ConfigLoadFailed {
+                path,
+                source: ConfigError::Invalid(t!(
+                    "migrate.failed",
+                    version = self.to,
+                    message = msg
+                )),
+            },
+            e => e,
+        }
+    }
+}
+
+/// 設定ファイルの形式のバージョンを取得（`version` がない場合は1）
+pub(crate) fn version(table: &Table) -> Result<u32> {
+    match table.get("version") {
+        None => Ok(LEGACY_VERSION),
+        Some(Value::Integer(version)) => u32::try_from(*version)
+            .map_err(|_| Error::config_load(t!("migrate.invalid_version", version = version))),
+        Some(value) => Err(Error::config_load(t!(
+            "migrate.version_not_integer",
+            value = value
+        ))),
+    }
+}
+
+/// 適用する移行手順を取得（現在より新しいバージョンの場合はエラー）
+fn pending(table: &Table) -> Result<Vec<&'static Migration>> {
+    let from = version(table)?;
+    if from > CURRENT_VERSION {
+        return Err(Error::config_load(t!(
+            "migrate.unsupported_version",
+            version = from,
+            current = CURRENT_VERSION
+        )));
+    }
+    Ok(MIGRATIONS.iter().filter(|m| m.to > from).collect())
+}
+
+/// 設定ファイルの内容を現在の形式に移行（適用した手順の説明を返す）
+pub(crate) fn migrate(table: &mut Table) -> Result<Vec<String>> {
+    let mut applied = Vec::new();
+    for migration in pending(table)? {
+        (migration.apply)(table).map_err(|e| migration.failed(e))?;
+        applied.push(t!(migration.description));
+    }
+
+    // version を先頭に置く
+    if !applied.is_empty() {
+        let rest = std::mem::take(table);
+        table.insert(
+            "version".to_string(),
+            Value::Integer(CURRENT_VERSION.into()),
+        );
+        table.extend(rest.into_iter().filter(|(key, _)| key != "version"));
+    }
+
+    Ok(applied)
+}
+
+/// TOML の設定ファイルの内容を、変更する部分以外の記述を保ったまま現在の形式に移行
+///
+/// 移行後の内容と適用した手順の説明を返す（最新の形式の場合は手順が空で、内容は変わらない）
+pub(crate) fn migrate_toml(source: &str) -> Result<(String, Vec<String>)> {
+    let table = ConfigFormat::Toml.parse(source)?;
+    let mut document: DocumentMut = source
+        .parse()
+        .map_err(|e: toml_edit::TomlError| Error::config_load(e))?;
+
+    let mut applied = Vec::new();
+    for migration in pending(&table)? {
+        (migration.edit)(&mut document).map_err(|e| migration.failed(e))?;
+        applied.push(t!(migration.description));
+    }
+    if !applied.is_empty() {
+        set_version(&mut document);
+    }
+
+    Ok((document.to_string(), applied))
+}
+
+/// 文書の `version` を現在のバージョンにする（ない場合は先頭に追加）
+fn set_version(document: &mut DocumentMut) {
+    if let Some(version) = document.get_mut("version").and_then(Item::as_value_mut) {
+        let decor = version.decor().clone();
+        *version = i64::from(CURRENT_VERSION).into();
+        *version.decor_mut() = decor;
+        return;
+    }
+
+    // ファイルの先頭のコメント（空行までの部分）は、先頭に追加する version の前に移す
+    let first = document.iter().next().map(|(key, _)| key.to_string());
+    let header = first.and_then(|first| {
+        let mut key = document.key_mut(&first)?;
+        let prefix = key.leaf_decor().prefix()?.as_str()?.to_string();
+        let end = prefix.rfind("\n\n")? + 2;
+        key.leaf_decor_mut().set_prefix(&prefix[end..]);
+        Some(prefix[..end].to_string())
+    });
+
+    let root = document.as_table_mut();
+    root.insert("version", toml_edit::value(i64::from(CURRENT_VERSION)));
+    if let Some(mut key) = root.key_mut("version") {
+        key.leaf_decor_mut().set_prefix(header.unwrap_or_default());
+    }
+    root.sort_values_by(|a, _, b, _| (a.get() != "version").cmp(&(b.get() != "version")));
+}
+
+/// ファイルのバックアップを同じディレクトリに作成
+pub(crate) fn backup(path: &Path) -> Result<PathBuf> {
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| Error::config_save(t!("migrate.no_file_name")).in_file(path))?;
+    let backup = path.with_file_name(format!(
+        "{}.{}.bak",
+        file_name.to_string_lossy(),
+        Local::now().format("%Y%m%d-%H%M%S")
+    ));
+
+    std::fs::copy(path, &backup).map_err(|e| Error::config_save(e).in_file(&backup))?;
+    Ok(backup)
+}
+
+/// バージョン1 → 2: `[application_ids]` の `1 = "..."` を `[[applications]]` の `index`, `id` に変換
+///
+/// TOML のキーは文字列のため、数値キーのテーブルは読み込めなかった
+fn v1_to_v2(table: &mut Table) -> Result<()> {
+    if table.contains_key("applications") && table.contains_key("application_ids") {
+        return Err(Error::config_load(t!("migrate.both_application_ids")));
+    }
+
+    // キーの順序を保つため、application_ids の位置に applications を挿入する
+    for (key, value) in std::mem::take(table) {
+        if key != "application_ids" {
+            table.insert(key, value);
+            continue;
+        }
+
+        let Value::Table(ids) = value else {
+            return Err(Error::config_load(t!("migrate.application_ids_not_table")));
+        };
+
+        let mut applications = Vec::new();
+        for (key, id) in ids {
+            let index = key.parse::<u32>().map_err(|_| {
+                Error::config_load(t!(
+                    "migrate.application_ids_key",
+                    key = format!("{:?}", key)
+                ))
+            })?;
+
+            let mut application = Table::new();
+            application.insert("index".to_string(), Value::Integer(index.into()));
+            application.insert("id".to_string(), id);
+            applications.push((index, application));
+        }
+        applications.sort_by_key(|(index, _)| *index);
+
+        table.insert(
+            "applications".to_string(),
+            Value::Array(
+                applications
+                    .into_iter()
+                    .map(|(_, a)| Value::Table(a))
+                    .collect(),
+            ),
+        );
+    }
+
+    Ok(())
+}
+
+/// [`v1_to_v2`] と同じ移行を TOML の文書に適用する
+///
+/// `[application_ids]` のあった位置に `[[applications]]` を書き込み、
+/// 各 Application ID の前のコメントと後ろのコメントは対応する `[[applications]]` に移す
+fn v1_to_v2_document(document: &mut DocumentMut) -> Result<()> {
+    if document.contains_key("applications") && document.contains_key("application_ids") {
+        return Err(Error::config_load(t!("migrate.both_application_ids")));
+    }
+    let first = document
+        .iter()
+        .next()
+        .is_some_and(|(key, _)| key == "application_ids");
+    let key_prefix = document
+        .key("application_ids")
+        .and_then(|key| key.leaf_decor().prefix())
+        .map(decor_str)
+        .unwrap_or_default();
+    let Some(item) = document.remove("application_ids") else {
+        return Ok(());
+    };
+    let Some(ids) = item.as_table_like() else {
+        return Err(Error::config_load(t!("migrate.application_ids_not_table")));
+    };
+
+    // 元のテーブルの位置（インラインテーブルやドット区切りのキーの場合は最初のテーブルの前）に書き込む
+    let (position, header) = match item {
+        Item::Table(ref table) if !table.is_dotted() => (
+            table.position(),
+            table.decor().prefix().map(decor_str).unwrap_or_default(),
+        ),
+        // ファイルの先頭のキーだった場合、その前のコメントはファイルの先頭に残す
+        _ if first => {
+            document.decor_mut().set_prefix(key_prefix);
+            (Some(0), "\n".to_string())
+        }
+        _ => (Some(0), format!("\n{}", key_prefix.trim_start())),
+    };
+
+    let mut applications = Vec::new();
+    for (key, id) in ids.iter() {
+        let index = key.parse::<u32>().map_err(|_| {
+            Error::config_load(t!(
+                "migrate.application_ids_key",
+                key = format!("{:?}", key)
+            ))
+        })?;
+        let Some(id) = id.as_value() else {
+            return Err(Error::config_load(t!("migrate.application_ids_not_table")));
+        };
+
+        // キーの前のコメント行のみを残す（インラインテーブルの空白やインデントは除く）
+        let prefix = ids
+            .key(key)
+            .and_then(|key| key.leaf_decor().prefix())
+            .map(decor_str)
+            .unwrap_or_default();
+        let comment = prefix[..prefix.rfind('\n').map_or(0, |i| i + 1)].trim_start();
+        let mut id = id.clone();
+        let suffix = id.decor().suffix().map(decor_str).unwrap_or_default();
+        id.decor_mut().set_prefix(" ");
+        id.decor_mut().set_suffix(if suffix.contains('#') {
+            suffix.as_str()
+        } else {
+            ""
+        });
+
+        let mut application = toml_edit::Table::new();
+        if let Some(position) = position {
+            application.set_position(position);
+        }
+        application.decor_mut().set_prefix(comment);
+        application.insert("index", toml_edit::value(i64::from(index)));
+        application.insert("id", Item::Value(id));
+        applications.push((index, application));
+    }
+    applications.sort_by_key(|(index, _)| *index);
+
+    // 最初のテーブルは元のテーブルの前の記述を引き継ぎ、以降のテーブルは空行で区切る
+    let mut array = ArrayOfTables::new();
+    for (i, (_, mut application)) in applications.into_iter().enumerate() {
+        let separator = if i == 0 { header.as_str() } else { "\n" };
+        let comment = application
+            .decor()
+            .prefix()
+            .map(decor_str)
+            .unwrap_or_default();
+        application
+            .decor_mut()
+            .set_prefix(format!("{}{}", separator, comment));
+        array.push(application);
+    }
+    document.insert("applications", Item::ArrayOfTables(array));
+    Ok(())
+}
+
+/// 装飾（コメントと空白）の文字列を取得
+fn decor_str(raw: &RawString) -> String {
+    raw.as_str().unwrap_or_default().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn v1_to_v2_converts_application_ids() {
+        let mut table: Table = toml::from_str(
+            r#"
+auto_connect = false
+
+[application_ids]
+2 = "223456789012345678"
+1 = "123456789012345678"
+
+[activity]
+details = "coding"
+"#,
+        )
+        .unwrap();
+        let steps = migrate(&mut table).unwrap();
+
+        let expected: Table = toml::from_str(
+            r#"
+version = 2
+auto_connect = false
+
+[[applications]]
+index = 1
+id = "123456789012345678"
+
+[[applications]]
+index = 2
+id = "223456789012345678"
+
+[activity]
+details = "coding"
+"#,
+        )
+        .unwrap();
+        assert_eq!(steps, [t!("migrate.v1_to_v2")]);
+        assert_eq!(table, expected);
+        // キーの順序を保つ
+        assert_eq!(
+            table.keys().collect::<Vec<_>>(),
+            ["version", "auto_connect", "applications", "activity"]
+        );
+    }
+
+    #[test]
+    fn v1_to_v2_rejects_invalid_application_ids() {
+        for source in [
+            "application_ids = \"123456789012345678\"",
+            "[application_ids]\nwork = \"123456789012345678\"",
+            "[application_ids]\n1 = \"123456789012345678\"\n[[applications]]\nindex = 2\nid = \"223456789012345678\"",
+        ] {
+            let mut table: Table = toml::from_str(source).unwrap();
+            assert!(migrate(&mut table).is_err(), "{source}");
+            assert!(migrate_toml(source).is_err(), "{source}");
+        }
+    }
+
+    #[test]
+    fn migrate_toml_keeps_comments() {
+        let source = r#"# discord-rp の設定
+
+auto_connect = true # 起動時に接続
+
+# アプリケーション
+[application_ids]
+# 作業用
+1 = "123456789012345678" # work
+2 = "${RP_APP2:-223456789012345678}"
+
+# 既定のアクティビティ
+[activity]
+details = "coding"
+"#;
+        let (migrated, steps) = migrate_toml(source).unwrap();
+
+        assert_eq!(steps, [t!("migrate.v1_to_v2")]);
+        assert_eq!(
+            migrated,
+            r#"# discord-rp の設定
+
+version = 2
+auto_connect = true # 起動時に接続
+
+# アプリケーション
+# 作業用
+[[applications]]
+index = 1
+id = "123456789012345678" # work
+
+[[applications]]
+index = 2
+id = "${RP_APP2:-223456789012345678}"
+
+# 既定のアクティビティ
+[activity]
+details = "coding"
+"#
+        );
+        // 移行後の内容は表の移行と同じ
+        let mut table: Table = toml::from_str(source).unwrap();
+        migrate(&mut table).unwrap();
+        assert_eq!(toml::from_str::<Table>(&migrated).unwrap(), table);
+
+        // 最新の形式の場合は変更しない
+        assert_eq!(
+            migrate_toml(&migrated).unwrap(),
+            (migrated.clone(), Vec::new())
+        );
+    }
+}
@@ -0,0 +1,248 @@
+//! ポモドーロタイマー（作業と休憩の繰り返し）
+
+use crate::{Activity, ActivityTimestamps};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// ポモドーロタイマーの設定（設定ファイルの `[pomodoro]`）
+///
+/// 作業と短い休憩を `cycles` 回繰り返し、最後の作業の後は長い休憩にする
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
+#[schemars(deny_unknown_fields)]
+pub struct Pomodoro {
+    /// 作業の長さ（分）
+    #[schemars(range(min = 1))]
+    pub work: u32,
+
+    /// 短い休憩の長さ（分）
+    #[serde(rename = "break")]
+    #[schemars(range(min = 1))]
+    pub short_break: u32,
+
+    /// 長い休憩の長さ（分）
+    #[schemars(range(min = 1))]
+    pub long_break: u32,
+
+    /// 長い休憩までの作業の回数（パーティーの最大人数として表示する）
+    #[schemars(range(min = 1))]
+    pub cycles: u32,
+
+    /// 表示の元にするプリセット（画像や状態テキストなど。詳細テキストはフェーズの名前で置き換える）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub preset: Option<String>,
+
+    /// 詳細テキストに表示するフェーズの名前
+    #[serde(skip_serializing_if = "PomodoroLabels::is_empty")]
+    pub labels: PomodoroLabels,
+
+    /// フェーズが切り替わったときに実行するコマンド
+    #[serde(skip_serializing_if = "PomodoroHooks::is_empty")]
+    pub hooks: PomodoroHooks,
+}
+
+/// フェーズの名前（省略した場合は言語に応じた名前）
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[schemars(deny_unknown_fields)]
+pub struct PomodoroLabels {
+    /// 作業中の名前
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub work: Option<String>,
+
+    /// 短い休憩中の名前
+    #[serde(rename = "break", skip_serializing_if = "Option::is_none")]
+    pub short_break: Option<String>,
+
+    /// 長い休憩中の名前
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub long_break: Option<String>,
+}
+
+/// フェーズが切り替わったときに実行するコマンド（シェルで実行する）
+///
+/// コマンドには環境変数 `DISCORD_RP_PHASE`（work, break, long_break, finish）、
+/// `DISCORD_RP_CYCLE`、`DISCORD_RP_CYCLES` を渡す
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[schemars(deny_unknown_fields)]
+pub struct PomodoroHooks {
+    /// 作業を始めるときに実行するコマンド
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub work: Option<String>,
+
+    /// 短い休憩を始めるときに実行するコマンド
+    #[serde(rename = "break", skip_serializing_if = "Option::is_none")]
+    pub short_break: Option<String>,
+
+    /// 長い休憩を始めるときに実行するコマンド
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub long_break: Option<String>,
+
+    /// 長い休憩が終わったときに実行するコマンド
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub finish: Option<String>,
+}
+
+/// フェーズの種類
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PomodoroPhase {
+    /// 作業
+    Work,
+    /// 短い休憩
+    Break,
+    /// 長い休憩
+    LongBreak,
+}
+
+/// 1回分の作業または休憩
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PomodoroStep {
+    /// フェーズの種類
+    pub phase: PomodoroPhase,
+    /// 何回目の作業か（休憩は直前の作業の回数、1始まり）
+    pub cycle: u32,
+    /// 長さ
+    pub length: Duration,
+}
+
+impl Default for Pomodoro {
+    fn default() -> Self {
+        Self {
+            work: 25,
+            short_break: 5,
+            long_break: 15,
+            cycles: 4,
+            preset: None,
+            labels: PomodoroLabels::default(),
+            hooks: PomodoroHooks::default(),
+        }
+    }
+}
+
+impl Pomodoro {
+    /// デフォルトの長さ（作業 25 分、短い休憩 5 分、長い休憩 15 分、4 回）で作成
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 作業の長さ（分）を設定
+    pub fn work(mut self, minutes: u32) -> Self {
+        self.work = minutes;
+        self
+    }
+
+    /// 短い休憩の長さ（分）を設定
+    pub fn short_break(mut self, minutes: u32) -> Self {
+        self.short_break = minutes;
+        self
+    }
+
+    /// 長い休憩の長さ（分）を設定
+    pub fn long_break(mut self, minutes: u32) -> Self {
+        self.long_break = minutes;
+        self
+    }
+
+    /// 長い休憩までの作業の回数を設定
+    pub fn cycles(mut self, cycles: u32) -> Self {
+        self.cycles = cycles;
+        self
+    }
+
+    /// 表示の元にするプリセットを設定
+    pub fn preset(mut self, name: impl Into<String>) -> Self {
+        self.preset = Some(name.into());
+        self
+    }
+
+    /// フェーズの長さ
+    pub fn length(&self, phase: PomodoroPhase) -> Duration {
+        let minutes = match phase {
+            PomodoroPhase::Work => self.work,
+            PomodoroPhase::Break => self.short_break,
+            PomodoroPhase::LongBreak => self.long_break,
+        };
+        Duration::from_secs(u64::from(minutes) * 60)
+    }
+
+    /// 1セット（作業と短い休憩を繰り返し、最後に長い休憩）のフェーズを順に列挙
+    pub fn steps(&self) -> impl Iterator<Item = PomodoroStep> + '_ {
+        (1..=self.cycles).flat_map(move |cycle| {
+            let rest = if cycle == self.cycles {
+                PomodoroPhase::LongBreak
+            } else {
+                PomodoroPhase::Break
+            };
+            [PomodoroPhase::Work, rest].map(|phase| PomodoroStep {
+                phase,
+                cycle,
+                length: self.length(phase),
+            })
+        })
+    }
+
+    /// 詳細テキストに表示するフェーズの名前
+    pub fn label(&self, phase: PomodoroPhase) -> String {
+        let label = match phase {
+            PomodoroPhase::Work => &self.labels.work,
+            PomodoroPhase::Break => &self.labels.short_break,
+            PomodoroPhase::LongBreak => &self.labels.long_break,
+        };
+        label
+            .clone()
+            .unwrap_or_else(|| t!(&format!("pomodoro.{}", phase.name())))
+    }
+
+    /// フェーズを始めるときに実行するコマンド
+    pub fn hook(&self, phase: PomodoroPhase) -> Option<&str> {
+        match phase {
+            PomodoroPhase::Work => self.hooks.work.as_deref(),
+            PomodoroPhase::Break => self.hooks.short_break.as_deref(),
+            PomodoroPhase::LongBreak => self.hooks.long_break.as_deref(),
+        }
+    }
+
+    /// フェーズの表示内容
+    ///
+    /// `base` の詳細テキストをフェーズの名前に、タイムスタンプを終了時刻 `end`（Unixタイムスタンプ、秒）にし、
+    /// 作業の回数をパーティーの人数として表示する。
+    /// 人数は状態テキストの後に表示されるため、`base` に状態テキストがない場合は既定のテキストを設定する
+    pub fn activity(&self, base: &Activity, step: &PomodoroStep, end: i64) -> Activity {
+        let mut activity = base.clone().details(self.label(step.phase));
+        if activity.state.is_none() {
+            activity.state = Some(t!("pomodoro.state"));
+        }
+        activity
+            .timestamps(ActivityTimestamps {
+                start: None,
+                end: Some(end),
+            })
+            .party_size(step.cycle, self.cycles)
+    }
+}
+
+impl PomodoroLabels {
+    /// どのフェーズの名前も指定していないか
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+impl PomodoroHooks {
+    /// どのコマンドも指定していないか
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+impl PomodoroPhase {
+    /// 名前（work, break, long_break。フックの環境変数 `DISCORD_RP_PHASE` の値）
+    pub fn name(self) -> &'static str {
+        match self {
+            PomodoroPhase::Work => "work",
+            PomodoroPhase::Break => "break",
+            PomodoroPhase::LongBreak => "long_break",
+        }
+    }
+}
@@ -0,0 +1,234 @@
+//! 名前付きアクティビティプリセットの定義
+
+use crate::{
+    Activity, ActivityTemplate, BuiltinVariables, Diagnostic, Error, Result, Rotation, Template,
+};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// 名前付きアクティビティプリセット
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+#[schemars(deny_unknown_fields)]
+pub struct Preset {
+    /// 継承元のプリセット名
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub extends: Option<String>,
+
+    /// 使用するApplication IDのインデックス（1始まり）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schemars(range(min = 1))]
+    pub app: Option<u32>,
+
+    /// 適用時に経過時間を表示するか
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub elapsed: Option<bool>,
+
+    /// テキスト中のテンプレートで参照できる変数（キー: 変数名）
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub vars: BTreeMap<String, PresetVar>,
+
+    /// 表示するアクティビティ
+    #[serde(default)]
+    pub activity: Activity,
+
+    /// 一定間隔で切り替えて `activity` に重ねるアクティビティ
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rotation: Option<Rotation>,
+}
+
+/// プリセット変数の宣言
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+#[schemars(deny_unknown_fields)]
+pub struct PresetVar {
+    /// デフォルト値（指定しない場合は適用時に必須）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub default: Option<String>,
+
+    /// 変数の説明
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+}
+
+impl PresetVar {
+    /// デフォルト値のない（必須の）変数を作成
+    pub fn required() -> Self {
+        Self::default()
+    }
+
+    /// デフォルト値のある変数を作成
+    pub fn with_default(default: impl Into<String>) -> Self {
+        Self {
+            default: Some(default.into()),
+            description: None,
+        }
+    }
+
+    /// 適用時に値の指定が必須か
+    pub fn is_required(&self) -> bool {
+        self.default.is_none()
+    }
+}
+
+impl Preset {
+    /// 新しいプリセットを作成
+    pub fn new(activity: Activity) -> Self {
+        Self {
+            activity,
+            ..Self::default()
+        }
+    }
+
+    /// 継承元のプリセットを設定
+    pub fn extends(mut self, name: impl Into<String>) -> Self {
+        self.extends = Some(name.into());
+        self
+    }
+
+    /// 使用するApplication IDのインデックスを設定
+    pub fn app(mut self, index: u32) -> Self {
+        self.app = Some(index);
+        self
+    }
+
+    /// 経過時間の表示を設定
+    pub fn elapsed(mut self, elapsed: bool) -> Self {
+        self.elapsed = Some(elapsed);
+        self
+    }
+
+    /// ローテーションを設定
+    pub fn rotation(mut self, rotation: Rotation) -> Self {
+        self.rotation = Some(rotation);
+        self
+    }
+
+    /// 変数を宣言
+    pub fn var(mut self, name: impl Into<String>, var: PresetVar) -> Self {
+        self.vars.insert(name.into(), var);
+        self
+    }
+
+    /// 継承先のプリセットの設定を重ねる
+    ///
+    /// `overlay` で指定された項目が優先され、変数は名前ごとに上書きされる。
+    /// ローテーションは個々のアクティビティを重ねず、まとめて置き換える。
+    /// `extends` は引き継がない
+    pub fn merge(&mut self, overlay: &Preset) {
+        self.app = overlay.app.or(self.app);
+        self.elapsed = overlay.elapsed.or(self.elapsed);
+        self.vars
+            .extend(overlay.vars.iter().map(|(k, v)| (k.clone(), v.clone())));
+        self.activity.merge(&overlay.activity);
+        if overlay.rotation.is_some() {
+            self.rotation = overlay.rotation.clone();
+        }
+    }
+
+    /// アクティビティとローテーションのアクティビティのテキスト項目を順に走査
+    fn texts(&self) -> impl Iterator<Item = &str> {
+        let rotation = self.rotation.iter().flat_map(|r| &r.activities);
+        self.activity
+            .texts()
+            .chain(rotation.flat_map(Activity::texts))
+    }
+
+    /// テンプレートの書式と、参照する変数が宣言済みまたは組み込み変数であるかを検証
+    ///
+    /// 継承元で宣言された変数も参照するため、継承を解決してから呼び出す
+    pub fn validate(&self) -> Result<()> {
+        for text in self.texts() {
+            for name in Template::parse(text)?.variables() {
+                if !self.vars.contains_key(name) && !BuiltinVariables::is_builtin(name) {
+                    return Err(Error::InvalidTemplate(t!(
+                        "preset.undeclared_variable",
+                        text = format!("{:?}", text),
+                        name = name
+                    )));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// プリセット自身で定義した項目を検査（テンプレート、変数の宣言、Discord の制限）
+    ///
+    /// 変数の宣言は継承を解決したプリセット `resolved` から参照する。
+    /// 問題のキーのパスはプリセットからの相対パス（例: `activity.details`）
+    pub fn check(&self, resolved: &Preset) -> Vec<Diagnostic> {
+        let mut diagnostics = self
+            .activity
+            .check_templates(|name| resolved.vars.contains_key(name));
+        diagnostics.extend(self.activity.check_limits());
+        let mut diagnostics = diagnostics
+            .into_iter()
+            .map(|diagnostic| diagnostic.prefixed(["activity"]))
+            .collect::<Vec<_>>();
+
+        if let Some(ref rotation) = self.rotation {
+            if rotation.activities.is_empty() {
+                diagnostics.push(Diagnostic::new(
+                    ["rotation", "activities"],
+                    t!("preset.empty_rotation"),
+                ));
+            }
+            for (i, activity) in rotation.activities.iter().enumerate() {
+                let mut found = activity.check_templates(|name| resolved.vars.contains_key(name));
+                found.extend(activity.check_limits());
+                diagnostics.extend(found.into_iter().map(|diagnostic| {
+                    diagnostic.prefixed([
+                        "rotation".to_string(),
+                        "activities".to_string(),
+                        i.to_string(),
+                    ])
+                }));
+            }
+        }
+        diagnostics
+    }
+
+    /// 変数の値を指定して適用するテンプレートを作成
+    ///
+    /// 値が指定されていない変数はデフォルト値を使用する。
+    /// `elapsed` が有効な場合は開始時刻を現在時刻に設定する。
+    /// ローテーションがある場合は現在時刻から切り替えを始める。
+    /// 継承は解決済みである必要がある（[`Config::resolve_preset`](crate::Config::resolve_preset)）
+    pub fn to_template(&self, values: &BTreeMap<String, String>) -> Result<ActivityTemplate> {
+        if let Some(name) = values.keys().find(|name| !self.vars.contains_key(*name)) {
+            return Err(Error::InvalidTemplate(t!(
+                "preset.unknown_variable",
+                name = name,
+                declared = format!("{:?}", self.vars.keys().collect::<Vec<_>>())
+            )));
+        }
+
+        let missing = self
+            .vars
+            .iter()
+            .filter(|(name, var)| var.is_required() && !values.contains_key(*name))
+            .map(|(name, _)| name.as_str())
+            .collect::<Vec<_>>();
+        if !missing.is_empty() {
+            return Err(Error::MissingVariable(missing.join(", ")));
+        }
+
+        let mut vars = self
+            .vars
+            .iter()
+            .filter_map(|(name, var)| Some((name.clone(), var.default.clone()?)))
+            .collect::<BTreeMap<_, _>>();
+        vars.extend(values.iter().map(|(k, v)| (k.clone(), v.clone())));
+
+        let activity = if self.elapsed.unwrap_or(false) {
+            self.activity.clone().start_timestamp()
+        } else {
+            self.activity.clone()
+        };
+
+        let template = ActivityTemplate::new(activity).with_vars(vars);
+        Ok(match self.rotation {
+            Some(ref rotation) => template.with_rotation(rotation.clone()),
+            None => template,
+        })
+    }
+}
@@ -0,0 +1,287 @@
+//! デーモンの制御プロトコル
+//!
+//! Unix ソケット上で、1行に1つの JSON としてリクエストとレスポンスを1往復ずつ交換する。
+//! 各メッセージは `version` にプロトコルのバージョンを持ち、バージョンの異なるメッセージは拒否する
+//!
+//! ```json
+//! {"version":2,"type":"clear","application_id":"123456789012345678"}
+//! {"version":2,"type":"ok"}
+//! ```
+
+use crate::{Activity, ActivityTemplate, ApplicationId, DiscordUser, Error, Result};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+/// プロトコルのバージョン（互換性のない変更をしたときに上げる）
+pub const PROTOCOL_VERSION: u32 = 2;
+
+/// ソケットのパスを指定する環境変数
+pub const SOCKET_ENV: &str = "DISCORD_RP_SOCKET";
+
+/// ソケットのファイル名
+const SOCKET_FILE: &str = "discord-rp.sock";
+
+/// デーモンへのリクエスト
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Request {
+    /// アクティビティを表示（同じApplication IDで表示中のアクティビティは置き換える）
+    Set {
+        /// Application IDのインデックス（表示用）
+        index: u32,
+        /// 使用するApplication ID
+        application_id: ApplicationId,
+        /// アプリケーションの表示用の名前
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        name: Option<String>,
+        /// 表示するアクティビティのテンプレート（デーモンが定期的に再評価する）
+        template: Box<ActivityTemplate>,
+        /// 表示を維持する秒数（`None` の場合はクリアするまで）
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        duration: Option<u64>,
+    },
+
+    /// アクティビティをクリアして接続を閉じる
+    Clear {
+        /// クリアするApplication ID
+        application_id: ApplicationId,
+    },
+
+    /// デーモンの状態を取得
+    Status,
+
+    /// デーモンを終了（表示中のアクティビティはすべてクリアされる）
+    Shutdown,
+}
+
+/// デーモンからのレスポンス
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Response {
+    /// 成功
+    Ok,
+
+    /// デーモンの状態
+    Status(DaemonStatus),
+
+    /// 失敗
+    Error {
+        /// エラーの内容
+        message: String,
+    },
+}
+
+/// デーモンの状態
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DaemonStatus {
+    /// デーモンのプロセスID
+    pub pid: u32,
+    /// 表示中のアクティビティ（Application IDごと）
+    pub sessions: Vec<SessionStatus>,
+}
+
+/// デーモンが表示中のアクティビティの状態
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SessionStatus {
+    /// Application IDのインデックス
+    pub index: u32,
+    /// Application ID
+    pub application_id: ApplicationId,
+    /// アプリケーションの表示用の名前
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// Discord に接続しているか
+    pub connected: bool,
+    /// 接続先の Discord にログインしているユーザー
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub user: Option<DiscordUser>,
+    /// 最後に Discord が受け付けたアクティビティ（まだ受け付けていない場合は `None`）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub activity: Option<Activity>,
+    /// 表示内容の変更が送信待ちか（更新間隔の制限中や切断中）
+    #[serde(default)]
+    pub pending: bool,
+    /// 次にアクティビティを更新できるまでの秒数（更新可能な場合は 0）
+    #[serde(default)]
+    pub next_update_secs: u64,
+    /// 表示を終了するまでの秒数（クリアするまで表示する場合は `None`）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub remaining_secs: Option<u64>,
+    /// 接続を維持したまま更新に失敗した場合のエラー（テンプレートの展開の失敗など）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_error: Option<String>,
+    /// 再接続の状態（接続中の場合や、再接続に失敗していない場合は `None`）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reconnect: Option<ReconnectStatus>,
+}
+
+/// 再接続の状態
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReconnectStatus {
+    /// 失敗した再接続の回数
+    pub attempts: u32,
+    /// 次に再接続を試みるまでの秒数（自動再接続が無効な場合は `None`）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub next_attempt_secs: Option<u64>,
+    /// 最後のエラー
+    pub last_error: String,
+}
+
+/// バージョン付きのメッセージ
+#[derive(Serialize, Deserialize)]
+struct Message<T> {
+    version: u32,
+    #[serde(flatten)]
+    body: T,
+}
+
+/// デーモンのソケットのパス
+///
+/// 環境変数 `DISCORD_RP_SOCKET` で指定できる。
+/// 指定しない場合は `$XDG_RUNTIME_DIR/discord-rp.sock`。
+/// `XDG_RUNTIME_DIR` がない場合は、一時ディレクトリ内のユーザーごとのディレクトリ
+/// （`discord-rp-<UID>`、デーモンが所有者のみ読み書きできる権限で作成する）に置く
+pub fn daemon_socket_path() -> PathBuf {
+    if let Some(path) = std::env::var_os(SOCKET_ENV).filter(|path| !path.is_empty()) {
+        return PathBuf::from(path);
+    }
+    match dirs::runtime_dir() {
+        Some(dir) => dir.join(SOCKET_FILE),
+        None => std::env::temp_dir().join(user_dir_name()).join(SOCKET_FILE),
+    }
+}
+
+/// 一時ディレクトリに作成する、ソケットを置くユーザーごとのディレクトリ名
+#[cfg(unix)]
+fn user_dir_name() -> String {
+    format!("discord-rp-{}", current_uid())
+}
+
+#[cfg(not(unix))]
+fn user_dir_name() -> String {
+    "discord-rp".to_string()
+}
+
+/// 現在のユーザーのUID
+#[cfg(unix)]
+fn current_uid() -> u32 {
+    // SAFETY: getuid は常に成功し、副作用もない
+    unsafe { libc::getuid() }
+}
+
+/// メッセージをバージョン付きの1行の JSON に変換（末尾に改行を含む）
+pub fn encode_message<T: Serialize>(body: &T) -> Result<String> {
+    let message = Message {
+        version: PROTOCOL_VERSION,
+        body,
+    };
+    serde_json::to_string(&message)
+        .map(|json| json + "\n")
+        .map_err(|source| Error::InvalidMessage { source })
+}
+
+/// 1行の JSON をメッセージに変換（バージョンが異なる場合はエラー）
+pub fn decode_message<T: DeserializeOwned>(line: &str) -> Result<T> {
+    let value: serde_json::Value =
+        serde_json::from_str(line).map_err(|source| Error::InvalidMessage { source })?;
+
+    let version = value.get("version").and_then(serde_json::Value::as_u64);
+    if version != Some(u64::from(PROTOCOL_VERSION)) {
+        return Err(Error::ProtocolMismatch {
+            version,
+            expected: PROTOCOL_VERSION,
+        });
+    }
+
+    serde_json::from_value::<Message<T>>(value)
+        .map(|message| message.body)
+        .map_err(|source| Error::InvalidMessage { source })
+}
+
+#[cfg(unix)]
+pub use client::DaemonClient;
+
+#[cfg(unix)]
+mod client {
+    use super::{Request, Response, current_uid, decode_message, encode_message};
+    use crate::{Error, Result};
+    use std::io::{BufRead, BufReader, ErrorKind, Write};
+    use std::os::unix::fs::MetadataExt;
+    use std::os::unix::net::UnixStream;
+    use std::path::{Path, PathBuf};
+    use std::time::Duration;
+
+    /// レスポンスを待つ時間
+    const RESPONSE_TIMEOUT: Duration = Duration::from_secs(10);
+
+    /// デーモンへの接続
+    pub struct DaemonClient {
+        stream: UnixStream,
+        path: PathBuf,
+    }
+
+    impl DaemonClient {
+        /// デーモンに接続（起動していない場合は [`Error::DaemonNotRunning`]）
+        ///
+        /// 他のユーザーが作成したソケットには接続しない（表示内容やリクエストを横取りされないように）
+        pub fn connect(path: &Path) -> Result<Self> {
+            let failed = |source| Error::DaemonConnectionFailed {
+                path: path.to_path_buf(),
+                source,
+            };
+            match std::fs::metadata(path) {
+                Ok(metadata) if metadata.uid() != current_uid() => {
+                    return Err(failed(std::io::Error::new(
+                        ErrorKind::PermissionDenied,
+                        t!("daemon.not_owned"),
+                    )));
+                }
+                Ok(_) => {}
+                Err(e) if e.kind() == ErrorKind::NotFound => return Err(Error::DaemonNotRunning),
+                Err(e) => return Err(failed(e)),
+            }
+            let stream = UnixStream::connect(path).map_err(|e| match e.kind() {
+                ErrorKind::NotFound | ErrorKind::ConnectionRefused => Error::DaemonNotRunning,
+                _ => failed(e),
+            })?;
+            stream
+                .set_read_timeout(Some(RESPONSE_TIMEOUT))
+                .map_err(failed)?;
+            Ok(Self {
+                stream,
+                path: path.to_path_buf(),
+            })
+        }
+
+        /// リクエストを送信してレスポンスを受け取る
+        ///
+        /// デーモンが [`Response::Error`] を返した場合は [`Error::DaemonFailed`] とする
+        pub fn request(mut self, request: &Request) -> Result<Response> {
+            let failed = |source| Error::DaemonConnectionFailed {
+                path: self.path.clone(),
+                source,
+            };
+
+            self.stream
+                .write_all(encode_message(request)?.as_bytes())
+                .map_err(failed)?;
+
+            let mut line = String::new();
+            BufReader::new(&self.stream)
+                .read_line(&mut line)
+                .map_err(failed)?;
+            if line.is_empty() {
+                return Err(failed(std::io::Error::new(
+                    ErrorKind::UnexpectedEof,
+                    t!("daemon.closed"),
+                )));
+            }
+
+            match decode_message(&line)? {
+                Response::Error { message } => Err(Error::DaemonFailed(message)),
+                response => Ok(response),
+            }
+        }
+    }
+}
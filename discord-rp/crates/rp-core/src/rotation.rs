@@ -0,0 +1,148 @@
+//! 複数のアクティビティを一定間隔で切り替えるローテーション
+
+use crate::Activity;
+use crate::client::MIN_UPDATE_INTERVAL;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+use std::time::Duration;
+
+/// 切り替える間隔のデフォルト（秒）
+const DEFAULT_INTERVAL_SECS: u64 = 60;
+
+/// アクティビティのローテーション（プリセットの `rotation`）
+///
+/// `activities` を順に表示し、最後まで表示したら最初に戻る。
+/// 各アクティビティはプリセットの `activity` に重ねるため、共通の項目（画像など）は `activity` に記述する
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[schemars(deny_unknown_fields)]
+pub struct Rotation {
+    /// 切り替える間隔（秒）。更新間隔の制限（15秒）より短い場合は15秒
+    #[serde(default = "default_interval")]
+    #[schemars(range(min = 1))]
+    pub interval: u64,
+
+    /// 切り替えたときの経過時間の扱い
+    #[serde(default)]
+    pub timer: RotationTimer,
+
+    /// 順に表示するアクティビティ
+    #[serde(default)]
+    pub activities: Vec<Activity>,
+}
+
+/// 切り替えたときの経過時間の扱い
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum RotationTimer {
+    /// 開始時刻を引き継ぐ（ローテーション全体の経過時間を表示）
+    #[default]
+    Keep,
+    /// 切り替えるたびに開始時刻を切り替えた時刻にする
+    Reset,
+}
+
+fn default_interval() -> u64 {
+    DEFAULT_INTERVAL_SECS
+}
+
+impl Default for Rotation {
+    fn default() -> Self {
+        Self::new(Vec::new())
+    }
+}
+
+impl Rotation {
+    /// 順に表示するアクティビティからローテーションを作成
+    pub fn new(activities: Vec<Activity>) -> Self {
+        Self {
+            interval: DEFAULT_INTERVAL_SECS,
+            timer: RotationTimer::default(),
+            activities,
+        }
+    }
+
+    /// 切り替える間隔（秒）を設定
+    pub fn interval(mut self, secs: u64) -> Self {
+        self.interval = secs;
+        self
+    }
+
+    /// 切り替えたときの経過時間の扱いを設定
+    pub fn timer(mut self, timer: RotationTimer) -> Self {
+        self.timer = timer;
+        self
+    }
+
+    /// 実際に切り替える間隔（更新間隔の制限より短い場合は制限の間隔）
+    pub fn effective_interval(&self) -> Duration {
+        Duration::from_secs(self.interval).max(MIN_UPDATE_INTERVAL)
+    }
+
+    /// 開始からの経過秒数に対応する順番（0始まり、切り替えた回数）
+    ///
+    /// 表示するアクティビティは `activities[round % activities.len()]`
+    pub fn round_at(&self, elapsed: u64) -> u64 {
+        elapsed / self.effective_interval().as_secs()
+    }
+}
+
+impl fmt::Display for RotationTimer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            RotationTimer::Keep => "keep",
+            RotationTimer::Reset => "reset",
+        })
+    }
+}
+
+impl FromStr for RotationTimer {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "keep" => Ok(RotationTimer::Keep),
+            "reset" => Ok(RotationTimer::Reset),
+            _ => Err(t!("rotation.invalid_timer", value = s)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn effective_interval_is_clamped_to_update_limit() {
+        for (interval, expected) in [(0, 15), (1, 15), (14, 15), (15, 15), (60, 60)] {
+            assert_eq!(
+                Rotation::default().interval(interval).effective_interval(),
+                Duration::from_secs(expected),
+                "{interval}"
+            );
+        }
+    }
+
+    #[test]
+    fn round_at_counts_switches_since_start() {
+        let rotation = Rotation::default().interval(60);
+        for (elapsed, round) in [(0, 0), (59, 0), (60, 1), (125, 2)] {
+            assert_eq!(rotation.round_at(elapsed), round, "{elapsed}");
+        }
+
+        // 制限より短い間隔は15秒ごとに切り替える
+        let rotation = Rotation::default().interval(5);
+        for (elapsed, round) in [(5, 0), (14, 0), (15, 1), (30, 2)] {
+            assert_eq!(rotation.round_at(elapsed), round, "{elapsed}");
+        }
+    }
+
+    #[test]
+    fn rotation_timer_round_trips() {
+        for timer in [RotationTimer::Keep, RotationTimer::Reset] {
+            assert_eq!(timer.to_string().parse::<RotationTimer>(), Ok(timer));
+        }
+        assert!("restart".parse::<RotationTimer>().is_err());
+    }
+}
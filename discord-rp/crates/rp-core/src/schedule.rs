@@ -0,0 +1,394 @@
+//! 時刻によるプリセットの自動切り替え（スケジュール）
+
+use crate::{Error, Result};
+use chrono::{
+    DateTime, Datelike, Duration, Local, NaiveDate, NaiveDateTime, NaiveTime, Timelike, Utc,
+    Weekday,
+};
+use chrono_tz::Tz;
+use schemars::{JsonSchema, Schema, SchemaGenerator, json_schema};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+
+/// 1日の分数
+const MINUTES_PER_DAY: u16 = 24 * 60;
+
+/// 次の切り替えを探す範囲（分）。ルールは曜日ごとに繰り返すため、8日分探せば十分
+const SEARCH_MINUTES: i64 = 8 * MINUTES_PER_DAY as i64;
+
+/// スケジュールのルール（設定ファイルの `[[schedule]]`）
+///
+/// ルールは記載順に評価し、最初に一致したルールを適用する。
+/// どのルールにも一致しない時間はアクティビティをクリアする
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[schemars(deny_unknown_fields)]
+pub struct ScheduleRule {
+    /// 曜日（mon〜sun, weekdays, weekends。省略した場合は毎日）
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub days: Vec<Days>,
+
+    /// 開始時刻（HH:MM、省略した場合は 00:00）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub start: Option<TimeOfDay>,
+
+    /// 終了時刻（HH:MM、省略した場合は 24:00）。開始時刻以前の場合は翌日の終了時刻まで
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub end: Option<TimeOfDay>,
+
+    /// 適用するプリセット名（省略した場合はアクティビティをクリア）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub preset: Option<String>,
+
+    /// プリセットの変数の値（キー: 変数名）
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub vars: BTreeMap<String, String>,
+}
+
+impl ScheduleRule {
+    /// 曜日を指定しない（毎日の）ルールを作成
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 曜日を追加
+    pub fn day(mut self, days: Days) -> Self {
+        self.days.push(days);
+        self
+    }
+
+    /// 時間帯を設定
+    pub fn between(mut self, start: TimeOfDay, end: TimeOfDay) -> Self {
+        self.start = Some(start);
+        self.end = Some(end);
+        self
+    }
+
+    /// 適用するプリセットを設定
+    pub fn preset(mut self, name: impl Into<String>) -> Self {
+        self.preset = Some(name.into());
+        self
+    }
+
+    /// 指定した曜日がルールの曜日に含まれるか
+    pub fn is_on(&self, weekday: Weekday) -> bool {
+        self.days.is_empty() || self.days.iter().any(|days| days.contains(weekday))
+    }
+
+    /// 指定した現地時刻に一致するか
+    ///
+    /// 日をまたぐルール（例: 22:00〜02:00）の翌日の部分は、前日の曜日で判定する
+    pub fn matches(&self, time: NaiveDateTime) -> bool {
+        let minute = (time.hour() * 60 + time.minute()) as u16;
+        let start = self.start.map_or(0, TimeOfDay::minutes);
+        let end = self.end.map_or(MINUTES_PER_DAY, TimeOfDay::minutes);
+        let weekday = time.weekday();
+
+        if start < end {
+            self.is_on(weekday) && (start..end).contains(&minute)
+        } else {
+            (self.is_on(weekday) && minute >= start) || (self.is_on(weekday.pred()) && minute < end)
+        }
+    }
+}
+
+/// ルールの曜日の指定
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Days {
+    /// 月曜日
+    Mon,
+    /// 火曜日
+    Tue,
+    /// 水曜日
+    Wed,
+    /// 木曜日
+    Thu,
+    /// 金曜日
+    Fri,
+    /// 土曜日
+    Sat,
+    /// 日曜日
+    Sun,
+    /// 平日（月曜日〜金曜日）
+    Weekdays,
+    /// 週末（土曜日と日曜日）
+    Weekends,
+}
+
+impl Days {
+    /// 指定した曜日を含むか
+    pub fn contains(self, weekday: Weekday) -> bool {
+        match self {
+            Days::Mon => weekday == Weekday::Mon,
+            Days::Tue => weekday == Weekday::Tue,
+            Days::Wed => weekday == Weekday::Wed,
+            Days::Thu => weekday == Weekday::Thu,
+            Days::Fri => weekday == Weekday::Fri,
+            Days::Sat => weekday == Weekday::Sat,
+            Days::Sun => weekday == Weekday::Sun,
+            Days::Weekdays => !matches!(weekday, Weekday::Sat | Weekday::Sun),
+            Days::Weekends => matches!(weekday, Weekday::Sat | Weekday::Sun),
+        }
+    }
+}
+
+/// 1日の中の時刻（分単位、00:00〜24:00）
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct TimeOfDay(u16);
+
+impl TimeOfDay {
+    /// 時と分から作成（24:00 まで）
+    pub fn new(hour: u32, minute: u32) -> Result<Self> {
+        let minutes = hour * 60 + minute;
+        if minute >= 60 || minutes > u32::from(MINUTES_PER_DAY) {
+            return Err(Error::InvalidSchedule(t!(
+                "schedule.invalid_time",
+                value = format!("{:02}:{:02}", hour, minute)
+            )));
+        }
+        Ok(Self(minutes as u16))
+    }
+
+    /// 00:00 からの分数
+    pub fn minutes(self) -> u16 {
+        self.0
+    }
+}
+
+impl fmt::Display for TimeOfDay {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:02}:{:02}", self.0 / 60, self.0 % 60)
+    }
+}
+
+impl FromStr for TimeOfDay {
+    type Err = Error;
+
+    /// `HH:MM` 形式（例: `09:00`, `24:00`）をパース
+    fn from_str(s: &str) -> Result<Self> {
+        let invalid =
+            || Error::InvalidSchedule(t!("schedule.invalid_time", value = format!("{:?}", s)));
+        let (hour, minute) = s.split_once(':').ok_or_else(invalid)?;
+        if hour.is_empty() || hour.len() > 2 || minute.len() != 2 {
+            return Err(invalid());
+        }
+        let hour = hour.parse().map_err(|_| invalid())?;
+        let minute = minute.parse().map_err(|_| invalid())?;
+        Self::new(hour, minute).map_err(|_| invalid())
+    }
+}
+
+impl Serialize for TimeOfDay {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for TimeOfDay {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        let value = String::deserialize(deserializer)?;
+        value.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+impl JsonSchema for TimeOfDay {
+    fn schema_name() -> Cow<'static, str> {
+        "TimeOfDay".into()
+    }
+
+    fn json_schema(_generator: &mut SchemaGenerator) -> Schema {
+        json_schema!({
+            "type": "string",
+            "description": "時刻（HH:MM、00:00〜24:00）",
+            "pattern": r"^([01]?[0-9]|2[0-3]):[0-5][0-9]$|^24:00$",
+        })
+    }
+}
+
+/// タイムラインの区間（同じルールが続く時間）
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ScheduleSlot {
+    /// 開始時刻
+    pub start: TimeOfDay,
+    /// 終了時刻
+    pub end: TimeOfDay,
+    /// 適用するルールの番号（0始まり、どのルールにも一致しない場合は `None`）
+    pub rule: Option<usize>,
+}
+
+/// 設定のスケジュールとタイムゾーン
+#[derive(Debug, Clone, Copy)]
+pub struct Schedule<'a> {
+    rules: &'a [ScheduleRule],
+    timezone: Option<Tz>,
+}
+
+impl<'a> Schedule<'a> {
+    /// ルールとタイムゾーン（`None` の場合はシステムのタイムゾーン）から作成
+    pub fn new(rules: &'a [ScheduleRule], timezone: Option<Tz>) -> Self {
+        Self { rules, timezone }
+    }
+
+    /// ルール
+    pub fn rules(&self) -> &'a [ScheduleRule] {
+        self.rules
+    }
+
+    /// タイムゾーン（システムのタイムゾーンの場合は `None`）
+    pub fn timezone(&self) -> Option<Tz> {
+        self.timezone
+    }
+
+    /// 指定した時刻の現地時刻
+    pub fn local_time(&self, time: DateTime<Utc>) -> NaiveDateTime {
+        match self.timezone {
+            Some(tz) => time.with_timezone(&tz).naive_local(),
+            None => time.with_timezone(&Local).naive_local(),
+        }
+    }
+
+    /// 指定した現地時刻に適用するルールの番号（0始まり）
+    pub fn rule_at_local(&self, time: NaiveDateTime) -> Option<usize> {
+        self.rules.iter().position(|rule| rule.matches(time))
+    }
+
+    /// 指定した時刻に適用するルールの番号（0始まり）
+    pub fn rule_at(&self, time: DateTime<Utc>) -> Option<usize> {
+        self.rule_at_local(self.local_time(time))
+    }
+
+    /// 指定した時刻の後で、適用するルールが次に変わる時刻（8日以内に変わらない場合は `None`）
+    pub fn next_change(&self, time: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let current = self.rule_at(time);
+        let minute = time.with_second(0)?.with_nanosecond(0)?;
+        (1..=SEARCH_MINUTES)
+            .map(|offset| minute + Duration::minutes(offset))
+            .find(|&time| self.rule_at(time) != current)
+    }
+
+    /// 指定した日（現地時刻）のタイムライン
+    ///
+    /// 同じルールが続く時間を1つの区間にまとめ、00:00 から 24:00 までを順に並べる
+    pub fn timeline(&self, date: NaiveDate) -> Vec<ScheduleSlot> {
+        let mut slots: Vec<ScheduleSlot> = Vec::new();
+        for minute in 0..MINUTES_PER_DAY {
+            let time = date.and_time(NaiveTime::MIN) + Duration::minutes(minute.into());
+            let rule = self.rule_at_local(time);
+            match slots.last_mut() {
+                Some(slot) if slot.rule == rule => slot.end = TimeOfDay(minute + 1),
+                _ => slots.push(ScheduleSlot {
+                    start: TimeOfDay(minute),
+                    end: TimeOfDay(minute + 1),
+                    rule,
+                }),
+            }
+        }
+        slots
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn time(s: &str) -> TimeOfDay {
+        s.parse().unwrap()
+    }
+
+    /// `2026-10-16 23:00` 形式の現地時刻（2026-10-16 は金曜日）
+    fn local(s: &str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M").unwrap()
+    }
+
+    #[test]
+    fn rule_across_midnight_uses_previous_weekday() {
+        let rule = ScheduleRule::new()
+            .day(Days::Weekdays)
+            .between(time("22:00"), time("02:00"));
+
+        for (at, expected) in [
+            ("2026-10-16 21:59", false),
+            ("2026-10-16 22:00", true),
+            ("2026-10-16 23:59", true),
+            // 土曜日の未明は金曜日のルールの続き
+            ("2026-10-17 00:00", true),
+            ("2026-10-17 01:59", true),
+            ("2026-10-17 02:00", false),
+            ("2026-10-17 22:00", false),
+            // 月曜日の未明は日曜日のルールの続きのため一致しない
+            ("2026-10-19 01:00", false),
+            ("2026-10-19 22:00", true),
+        ] {
+            assert_eq!(rule.matches(local(at)), expected, "{at}");
+        }
+    }
+
+    #[test]
+    fn time_of_day_accepts_24_00_as_end_of_day() {
+        assert_eq!(time("24:00").minutes(), MINUTES_PER_DAY);
+        assert_eq!(time("24:00").to_string(), "24:00");
+        assert_eq!(time("9:05").to_string(), "09:05");
+        for invalid in ["24:01", "09:60", "0900", "9:5", ":00", "123:00"] {
+            assert!(invalid.parse::<TimeOfDay>().is_err(), "{invalid}");
+        }
+
+        let evening = ScheduleRule::new().between(time("18:00"), time("24:00"));
+        assert!(evening.matches(local("2026-10-16 23:59")));
+        assert!(!evening.matches(local("2026-10-17 00:00")));
+
+        // 開始時刻と終了時刻が同じ場合は1日中
+        let all_day = ScheduleRule::new().between(time("24:00"), time("24:00"));
+        assert!(all_day.matches(local("2026-10-16 00:00")));
+        assert!(all_day.matches(local("2026-10-16 12:00")));
+    }
+
+    #[test]
+    fn next_change_and_timeline_follow_rules() {
+        let rules = [
+            ScheduleRule::new()
+                .day(Days::Weekdays)
+                .between(time("09:00"), time("18:00"))
+                .preset("work"),
+            ScheduleRule::new()
+                .between(time("22:00"), time("02:00"))
+                .preset("night"),
+        ];
+        let schedule = Schedule::new(&rules, Some(chrono_tz::Asia::Tokyo));
+
+        // 金曜日 17:30:45（日本時間）の次は 18:00 に work が終わる
+        let now = "2026-10-16T08:30:45Z".parse::<DateTime<Utc>>().unwrap();
+        assert_eq!(schedule.rule_at(now), Some(0));
+        assert_eq!(
+            schedule.next_change(now),
+            Some("2026-10-16T09:00:00Z".parse().unwrap())
+        );
+        // 土曜日の 02:00 の次は、週末に work がないため 22:00 の night
+        let saturday = "2026-10-16T17:00:00Z".parse::<DateTime<Utc>>().unwrap();
+        assert_eq!(schedule.rule_at(saturday), None);
+        assert_eq!(
+            schedule.next_change(saturday),
+            Some("2026-10-17T13:00:00Z".parse().unwrap())
+        );
+        assert_eq!(Schedule::new(&[], None).next_change(now), None);
+
+        let slots: Vec<_> = schedule
+            .timeline(NaiveDate::from_ymd_opt(2026, 10, 16).unwrap())
+            .into_iter()
+            .map(|slot| (slot.start.to_string(), slot.end.to_string(), slot.rule))
+            .collect();
+        let expected = [
+            ("00:00", "02:00", Some(1)),
+            ("02:00", "09:00", None),
+            ("09:00", "18:00", Some(0)),
+            ("18:00", "22:00", None),
+            ("22:00", "24:00", Some(1)),
+        ];
+        assert_eq!(
+            slots,
+            expected.map(|(start, end, rule)| (start.to_string(), end.to_string(), rule))
+        );
+    }
+}
@@ -0,0 +1,118 @@
+//! 表示中のアクティビティを維持するセッション
+
+use crate::{Activity, ActivityTemplate, Result, RichPresenceClient};
+use tracing::debug;
+
+/// テンプレートを定期的に再評価し、内容が変わったときだけ更新するセッション
+pub struct PresenceSession {
+    client: RichPresenceClient,
+    template: ActivityTemplate,
+    last_sent: Option<Activity>,
+}
+
+impl PresenceSession {
+    /// 接続済みのクライアントとテンプレートからセッションを作成
+    pub fn new(client: RichPresenceClient, template: ActivityTemplate) -> Self {
+        Self {
+            client,
+            template,
+            last_sent: None,
+        }
+    }
+
+    /// クライアントを取得
+    pub fn client(&self) -> &RichPresenceClient {
+        &self.client
+    }
+
+    /// クライアントを可変で取得
+    pub fn client_mut(&mut self) -> &mut RichPresenceClient {
+        &mut self.client
+    }
+
+    /// 表示中のテンプレートを取得
+    pub fn template(&self) -> &ActivityTemplate {
+        &self.template
+    }
+
+    /// 最後に送信したアクティビティを取得
+    pub fn last_sent(&self) -> Option<&Activity> {
+        self.last_sent.as_ref()
+    }
+
+    /// テンプレートの展開結果が最後に送信したアクティビティと異なり、送信待ちか
+    pub fn is_pending(&self) -> bool {
+        self.template
+            .render()
+            .is_ok_and(|activity| self.last_sent.as_ref() != Some(&activity))
+    }
+
+    /// テンプレートを差し替える（反映は次回の [`refresh`](Self::refresh)）
+    ///
+    /// 差し替え前後の両方で経過時間を表示する場合は、元の開始時刻を引き継ぐ。
+    /// 差し替え前後の両方がローテーションの場合は、切り替えの順番も引き継ぐ
+    pub fn set_template(&mut self, mut template: ActivityTemplate) {
+        let current_start = self
+            .template
+            .activity
+            .timestamps
+            .as_ref()
+            .and_then(|ts| ts.start);
+        if let Some(start) = current_start
+            && let Some(ref mut timestamps) = template.activity.timestamps
+            && timestamps.start.is_some()
+        {
+            timestamps.start = Some(start);
+        }
+        if self.template.rotation.is_some() && template.rotation.is_some() {
+            template.rotation_start = self.template.rotation_start;
+        }
+        self.template = template;
+    }
+
+    /// テンプレートを置き換える（反映は次回の [`refresh`](Self::refresh)）
+    ///
+    /// [`set_template`](Self::set_template) と異なり、経過時間の開始時刻は引き継がない
+    pub fn replace_template(&mut self, template: ActivityTemplate) {
+        self.template = template;
+    }
+
+    /// テンプレートを展開し、前回の送信内容から変わっていれば更新する
+    ///
+    /// 更新間隔の制限中は送信せず、次回の呼び出しに持ち越す。
+    /// 送信した場合は `true` を返す
+    pub fn refresh(&mut self) -> Result<bool> {
+        let activity = self.template.render()?;
+
+        if self.last_sent.as_ref() == Some(&activity) {
+            return Ok(false);
+        }
+
+        let wait = self.client.next_update_in();
+        if !wait.is_zero() {
+            debug!(
+                "{}",
+                t!(
+                    "session.rate_limited",
+                    seconds = wait.as_secs_f64().ceil() as u64
+                )
+            );
+            return Ok(false);
+        }
+
+        self.client.update_activity(&activity)?;
+        self.last_sent = Some(activity);
+        Ok(true)
+    }
+
+    /// 再接続し、次回の [`refresh`](Self::refresh) で表示内容を送信し直す
+    pub fn reconnect(&mut self) -> Result<()> {
+        self.last_sent = None;
+        self.client.reconnect()
+    }
+
+    /// セッションを終了してクライアントを取り出す
+    pub fn into_client(self) -> RichPresenceClient {
+        self.client
+    }
+}
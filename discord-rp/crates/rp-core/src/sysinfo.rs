@@ -0,0 +1,137 @@
+//! システム情報（/proc と /sys から取得）
+//!
+//! Linux 以外の環境や取得できない項目は `None` になる
+
+use crate::client::MIN_UPDATE_INTERVAL;
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// 前回取得したシステム情報
+static CACHE: Mutex<Option<(Instant, SystemInfo)>> = Mutex::new(None);
+
+/// システム情報のスナップショット
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SystemInfo {
+    /// 起動からの経過時間
+    pub uptime: Option<Duration>,
+    /// ロードアベレージ（1分、5分、15分）
+    pub load: Option<[f64; 3]>,
+    /// 論理CPU数
+    pub cpus: Option<usize>,
+    /// 総メモリ量（バイト）
+    pub mem_total: Option<u64>,
+    /// 使用中のメモリ量（バイト、総量から利用可能量を引いたもの）
+    pub mem_used: Option<u64>,
+    /// バッテリー残量（%）
+    pub battery: Option<u8>,
+    /// カーネルのバージョン
+    pub kernel: Option<String>,
+}
+
+impl SystemInfo {
+    /// 現在のシステム情報を取得
+    pub fn read() -> Self {
+        let (mem_total, mem_used) = read_meminfo().unzip();
+        Self {
+            uptime: read_uptime(),
+            load: read_loadavg(),
+            cpus: std::thread::available_parallelism().ok().map(|n| n.get()),
+            mem_total,
+            mem_used,
+            battery: read_battery(Path::new("/sys/class/power_supply")),
+            kernel: read_trimmed("/proc/sys/kernel/osrelease"),
+        }
+    }
+
+    /// キャッシュしたシステム情報を取得
+    ///
+    /// アクティビティの更新間隔（15秒）より短い間隔では取得し直さない
+    pub fn cached() -> Self {
+        let mut cache = CACHE.lock().unwrap_or_else(|e| e.into_inner());
+        match *cache {
+            Some((read_at, ref info)) if read_at.elapsed() < MIN_UPDATE_INTERVAL => info.clone(),
+            _ => {
+                let info = Self::read();
+                *cache = Some((Instant::now(), info.clone()));
+                info
+            }
+        }
+    }
+
+    /// メモリ使用率（%）
+    pub fn mem_percent(&self) -> Option<f64> {
+        match (self.mem_used, self.mem_total) {
+            (Some(used), Some(total)) if total > 0 => Some(used as f64 * 100.0 / total as f64),
+            _ => None,
+        }
+    }
+}
+
+/// 経過時間を「3d 4h」「4h 12m」「12m」の形式で整形
+pub(crate) fn format_uptime(uptime: Duration) -> String {
+    let minutes = uptime.as_secs() / 60;
+    let (days, hours, minutes) = (minutes / 1440, minutes / 60 % 24, minutes % 60);
+    if days > 0 {
+        format!("{}d {}h", days, hours)
+    } else if hours > 0 {
+        format!("{}h {}m", hours, minutes)
+    } else {
+        format!("{}m", minutes)
+    }
+}
+
+/// バイト数を GiB 単位で整形
+pub(crate) fn format_gib(bytes: u64) -> String {
+    format!("{:.1} GiB", bytes as f64 / (1024.0 * 1024.0 * 1024.0))
+}
+
+fn read_trimmed(path: impl AsRef<Path>) -> Option<String> {
+    std::fs::read_to_string(path)
+        .ok()
+        .map(|s| s.trim().to_string())
+}
+
+fn read_uptime() -> Option<Duration> {
+    let content = read_trimmed("/proc/uptime")?;
+    let secs = content.split_whitespace().next()?.parse::<f64>().ok()?;
+    Some(Duration::from_secs_f64(secs))
+}
+
+fn read_loadavg() -> Option<[f64; 3]> {
+    let content = read_trimmed("/proc/loadavg")?;
+    let mut fields = content
+        .split_whitespace()
+        .map(|field| field.parse::<f64>().ok());
+    Some([fields.next()??, fields.next()??, fields.next()??])
+}
+
+/// /proc/meminfo から総量と使用量（バイト）を取得
+fn read_meminfo() -> Option<(u64, u64)> {
+    let content = std::fs::read_to_string("/proc/meminfo").ok()?;
+    let field = |key: &str| {
+        content.lines().find_map(|line| {
+            let value = line.strip_prefix(key)?.strip_prefix(':')?;
+            let kib = value.trim().trim_end_matches("kB").trim();
+            kib.parse::<u64>().ok().map(|kib| kib * 1024)
+        })
+    };
+
+    let total = field("MemTotal")?;
+    let available = field("MemAvailable")?;
+    Some((total, total.saturating_sub(available)))
+}
+
+/// 最初に見つかったバッテリーの残量を取得
+fn read_battery(power_supply: &Path) -> Option<u8> {
+    let mut entries = std::fs::read_dir(power_supply)
+        .ok()?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| read_trimmed(path.join("type")).as_deref() == Some("Battery"))
+        .collect::<Vec<_>>();
+    entries.sort();
+
+    entries
+        .iter()
+        .find_map(|path| read_trimmed(path.join("capacity"))?.parse().ok())
+}
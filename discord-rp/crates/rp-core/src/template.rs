@@ -0,0 +1,529 @@
+//! アクティビティ文字列のテンプレート
+//!
+//! 書式:
+//! - `{name}`: 変数の値で置換
+//! - `{name | upper}`: フィルタを適用（`upper`, `lower`, `truncate(N)`, `default("値")`、連結可）
+//! - `{if name}...{else}...{end}`: 変数が定義済みかつ空でない場合のみ出力（`{if !name}` で否定）
+//! - `{{` / `}}`: `{` / `}` をそのまま出力
+
+use crate::{Error, Result};
+use std::collections::BTreeMap;
+
+/// テンプレートから参照される変数の値を提供する
+pub trait Variables {
+    /// 変数の値を取得（未定義の場合は `None`）
+    fn get(&self, name: &str) -> Option<String>;
+}
+
+impl Variables for BTreeMap<String, String> {
+    fn get(&self, name: &str) -> Option<String> {
+        BTreeMap::get(self, name).cloned()
+    }
+}
+
+/// 複数の変数を順に検索する（先に指定したものが優先）
+impl<A: Variables, B: Variables> Variables for (A, B) {
+    fn get(&self, name: &str) -> Option<String> {
+        self.0.get(name).or_else(|| self.1.get(name))
+    }
+}
+
+impl<T: Variables + ?Sized> Variables for &T {
+    fn get(&self, name: &str) -> Option<String> {
+        (**self).get(name)
+    }
+}
+
+/// パース済みのテンプレート
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Template {
+    nodes: Vec<Node>,
+}
+
+/// テンプレートの構成要素
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Node {
+    /// そのまま出力する文字列
+    Text(String),
+    /// 変数の参照
+    Var { name: String, filters: Vec<Filter> },
+    /// 条件分岐
+    If {
+        name: String,
+        negate: bool,
+        then: Vec<Node>,
+        otherwise: Vec<Node>,
+    },
+}
+
+/// 変数の値に適用するフィルタ
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Filter {
+    /// 大文字に変換
+    Upper,
+    /// 小文字に変換
+    Lower,
+    /// 指定文字数を超える場合に切り詰めて末尾を `…` にする
+    Truncate(usize),
+    /// 変数が未定義または空の場合の値
+    Default(String),
+}
+
+/// ブロックの終端
+#[derive(Debug, PartialEq, Eq)]
+enum Terminator {
+    Eof,
+    Else,
+    End,
+}
+
+/// フィルタの引数
+enum Arg {
+    Int(usize),
+    Str(String),
+}
+
+impl Template {
+    /// テンプレート文字列をパース
+    pub fn parse(source: &str) -> Result<Self> {
+        let mut parser = Parser { source, pos: 0 };
+        let (nodes, terminator) = parser.parse_block()?;
+        match terminator {
+            Terminator::Eof => Ok(Self { nodes }),
+            Terminator::Else => Err(parser.error(&t!("template.else_without_if"))),
+            Terminator::End => Err(parser.error(&t!("template.end_without_if"))),
+        }
+    }
+
+    /// テンプレートを展開
+    ///
+    /// 未定義の変数を `default` フィルタなしで参照した場合はエラー
+    pub fn render(&self, vars: &dyn Variables) -> Result<String> {
+        let mut output = String::new();
+        render_nodes(&self.nodes, vars, &mut output)?;
+        Ok(output)
+    }
+
+    /// 参照している変数名を出現順に取得（重複なし）
+    pub fn variables(&self) -> Vec<&str> {
+        let mut names = Vec::new();
+        collect_variables(&self.nodes, &mut names);
+        names
+    }
+
+    /// プレースホルダを含まない単なる文字列か
+    pub fn is_static(&self) -> bool {
+        self.nodes.iter().all(|node| matches!(node, Node::Text(_)))
+    }
+}
+
+/// テンプレート文字列をパースして展開
+pub fn render(source: &str, vars: &dyn Variables) -> Result<String> {
+    Template::parse(source)?.render(vars)
+}
+
+fn render_nodes(nodes: &[Node], vars: &dyn Variables, output: &mut String) -> Result<()> {
+    for node in nodes {
+        match node {
+            Node::Text(text) => output.push_str(text),
+            Node::Var { name, filters } => {
+                let mut value = vars.get(name).filter(|v| !v.is_empty());
+                for filter in filters {
+                    value = apply_filter(filter, value);
+                }
+                let value = value
+                    .or_else(|| vars.get(name))
+                    .ok_or_else(|| Error::MissingVariable(name.clone()))?;
+                output.push_str(&value);
+            }
+            Node::If {
+                name,
+                negate,
+                then,
+                otherwise,
+            } => {
+                let defined = vars.get(name).is_some_and(|v| !v.is_empty());
+                let branch = if defined != *negate { then } else { otherwise };
+                render_nodes(branch, vars, output)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn apply_filter(filter: &Filter, value: Option<String>) -> Option<String> {
+    match filter {
+        Filter::Upper => value.map(|v| v.to_uppercase()),
+        Filter::Lower => value.map(|v| v.to_lowercase()),
+        Filter::Truncate(max) => value.map(|v| {
+            if v.chars().count() <= *max {
+                v
+            } else {
+                let mut truncated = v.chars().take(max.saturating_sub(1)).collect::<String>();
+                truncated.push('…');
+                truncated
+            }
+        }),
+        Filter::Default(default) => value.or_else(|| Some(default.clone())),
+    }
+}
+
+fn collect_variables<'a>(nodes: &'a [Node], names: &mut Vec<&'a str>) {
+    for node in nodes {
+        match node {
+            Node::Text(_) => {}
+            Node::Var { name, .. } => {
+                if !names.contains(&name.as_str()) {
+                    names.push(name);
+                }
+            }
+            Node::If {
+                name,
+                then,
+                otherwise,
+                ..
+            } => {
+                if !names.contains(&name.as_str()) {
+                    names.push(name);
+                }
+                collect_variables(then, names);
+                collect_variables(otherwise, names);
+            }
+        }
+    }
+}
+
+/// 変数名として使用できるか
+fn is_valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && !matches!(name, "if" | "else" | "end")
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.'))
+}
+
+struct Parser<'a> {
+    source: &'a str,
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn error(&self, message: &str) -> Error {
+        Error::InvalidTemplate(format!("{:?}: {}", self.source, message))
+    }
+
+    fn rest(&self) -> &str {
+        &self.source[self.pos..]
+    }
+
+    /// 次の終端（{else} / {end} / 文字列の終わり）までをパース
+    fn parse_block(&mut self) -> Result<(Vec<Node>, Terminator)> {
+        let mut nodes = Vec::new();
+        let mut text = String::new();
+
+        loop {
+            let rest = self.rest();
+            let Some(offset) = rest.find(['{', '}']) else {
+                text.push_str(rest);
+                self.pos = self.source.len();
+                break;
+            };
+            text.push_str(&rest[..offset]);
+            self.pos += offset;
+
+            if self.rest().starts_with("{{") {
+                text.push('{');
+                self.pos += 2;
+                continue;
+            }
+            if self.rest().starts_with("}}") {
+                text.push('}');
+                self.pos += 2;
+                continue;
+            }
+            if self.rest().starts_with('}') {
+                return Err(self.error(&t!("template.unmatched_close")));
+            }
+
+            let tag = self.read_tag()?;
+            if !text.is_empty() {
+                nodes.push(Node::Text(std::mem::take(&mut text)));
+            }
+
+            match tag.as_str() {
+                "else" => return Ok((nodes, Terminator::Else)),
+                "end" => return Ok((nodes, Terminator::End)),
+                _ => {}
+            }
+
+            if let Some(condition) = tag.strip_prefix("if ") {
+                nodes.push(self.parse_if(condition.trim())?);
+            } else {
+                nodes.push(self.parse_var(&tag)?);
+            }
+        }
+
+        if !text.is_empty() {
+            nodes.push(Node::Text(text));
+        }
+        Ok((nodes, Terminator::Eof))
+    }
+
+    /// `{` から対応する `}` までを読み、中身を返す（引用符内の `}` は無視）
+    fn read_tag(&mut self) -> Result<String> {
+        let start = self.pos + 1;
+        let mut in_string = false;
+        let mut escaped = false;
+
+        for (offset, c) in self.source[start..].char_indices() {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' if in_string => escaped = true,
+                '"' => in_string = !in_string,
+                '}' if !in_string => {
+                    let tag = self.source[start..start + offset].trim().to_string();
+                    self.pos = start + offset + 1;
+                    return Ok(tag);
+                }
+                _ => {}
+            }
+        }
+
+        Err(self.error(&t!("template.unclosed")))
+    }
+
+    fn parse_if(&mut self, condition: &str) -> Result<Node> {
+        let (negate, name) = match condition.strip_prefix('!') {
+            Some(name) => (true, name.trim()),
+            None => (false, condition),
+        };
+        if !is_valid_name(name) {
+            return Err(self.error(&t!(
+                "template.invalid_condition",
+                name = format!("{:?}", name)
+            )));
+        }
+
+        let (then, terminator) = self.parse_block()?;
+        let otherwise = match terminator {
+            Terminator::End => Vec::new(),
+            Terminator::Else => match self.parse_block()? {
+                (otherwise, Terminator::End) => otherwise,
+                (_, Terminator::Else) => return Err(self.error(&t!("template.duplicate_else"))),
+                (_, Terminator::Eof) => {
+                    return Err(self.error(&t!("template.unclosed_if")));
+                }
+            },
+            Terminator::Eof => return Err(self.error(&t!("template.unclosed_if"))),
+        };
+
+        Ok(Node::If {
+            name: name.to_string(),
+            negate,
+            then,
+            otherwise,
+        })
+    }
+
+    fn parse_var(&self, tag: &str) -> Result<Node> {
+        let mut parts = split_pipes(tag).into_iter();
+        let name = parts.next().unwrap_or_default().trim();
+        if !is_valid_name(name) {
+            return Err(self.error(&t!(
+                "template.invalid_variable",
+                name = format!("{:?}", name)
+            )));
+        }
+
+        let filters = parts
+            .map(|part| self.parse_filter(part.trim()))
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(Node::Var {
+            name: name.to_string(),
+            filters,
+        })
+    }
+
+    fn parse_filter(&self, source: &str) -> Result<Filter> {
+        let (name, arg) = match source.find('(') {
+            Some(open) => {
+                let close = source.strip_suffix(')').ok_or_else(|| {
+                    self.error(&t!(
+                        "template.unclosed_filter",
+                        filter = format!("{:?}", source)
+                    ))
+                })?;
+                (
+                    source[..open].trim(),
+                    Some(self.parse_arg(close[open + 1..].trim())?),
+                )
+            }
+            None => (source, None),
+        };
+
+        match (name, arg) {
+            ("upper", None) => Ok(Filter::Upper),
+            ("lower", None) => Ok(Filter::Lower),
+            ("truncate", Some(Arg::Int(max))) => Ok(Filter::Truncate(max)),
+            ("default", Some(Arg::Str(value))) => Ok(Filter::Default(value)),
+            ("truncate", _) => Err(self.error(&t!("template.truncate_argument"))),
+            ("default", _) => Err(self.error(&t!("template.default_argument"))),
+            _ => Err(self.error(&t!(
+                "template.unknown_filter",
+                filter = format!("{:?}", source)
+            ))),
+        }
+    }
+
+    fn parse_arg(&self, source: &str) -> Result<Arg> {
+        if let Some(quoted) = source.strip_prefix('"').and_then(|s| s.strip_suffix('"')) {
+            let mut value = String::new();
+            let mut chars = quoted.chars();
+            while let Some(c) = chars.next() {
+                match c {
+                    '\\' => value.extend(chars.next()),
+                    c => value.push(c),
+                }
+            }
+            return Ok(Arg::Str(value));
+        }
+
+        source.parse::<usize>().map(Arg::Int).map_err(|_| {
+            self.error(&t!(
+                "template.invalid_filter_argument",
+                filter = format!("{:?}", source)
+            ))
+        })
+    }
+}
+
+/// 引用符の外にある `|` で分割
+fn split_pipes(source: &str) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut start = 0;
+    let mut in_string = false;
+    let mut escaped = false;
+
+    for (i, c) in source.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if in_string => escaped = true,
+            '"' => in_string = !in_string,
+            '|' if !in_string => {
+                parts.push(&source[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    parts.push(&source[start..]);
+    parts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vars(pairs: &[(&str, &str)]) -> BTreeMap<String, String> {
+        pairs
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn renders_variables_and_filters() {
+        let vars = vars(&[
+            ("subject", "math"),
+            ("ticket", ""),
+            ("title", "Rich Presence"),
+        ]);
+        let cases = [
+            ("Studying {subject}", "Studying math"),
+            ("{ subject | upper }", "MATH"),
+            ("{title | lower | truncate(5)}", "rich…"),
+            ("{title | truncate(13)}", "Rich Presence"),
+            (r#"#{ticket | default("none")}"#, "#none"),
+            (r#"{missing | default("a | \"b\" }")}"#, r#"a | "b" }"#),
+            // 空の値は default がなければそのまま出力する
+            ("[{ticket}]", "[]"),
+            ("{{subject}} }}", "{subject} }"),
+        ];
+        for (source, expected) in cases {
+            assert_eq!(render(source, &vars).unwrap(), expected, "{source}");
+        }
+    }
+
+    #[test]
+    fn missing_variable_is_an_error() {
+        let result = render("Studying {subject}", &vars(&[]));
+        assert!(matches!(result, Err(Error::MissingVariable(name)) if name == "subject"));
+    }
+
+    #[test]
+    fn rejects_invalid_syntax() {
+        for source in [
+            "Studying {subject",
+            "Studying subject}",
+            "{subject | default(\"x)}",
+            "{}",
+            "{sub ject}",
+            "{subject | reverse}",
+            "{subject | truncate}",
+            "{subject | truncate(-1)}",
+            "{subject | truncate(\"5\")}",
+            "{subject | default(5)}",
+            "{subject | default(\"x\"}",
+        ] {
+            assert!(
+                matches!(Template::parse(source), Err(Error::InvalidTemplate(_))),
+                "{source}"
+            );
+        }
+    }
+
+    #[test]
+    fn renders_conditionals() {
+        let vars = vars(&[("branch", "main"), ("ticket", "")]);
+        let cases = [
+            ("{if branch}on {branch}{end}", "on main"),
+            ("{if ticket}#{ticket}{else}no ticket{end}", "no ticket"),
+            ("{if !missing}none{end}", "none"),
+            ("{if ! branch}x{else}y{end}", "y"),
+            ("{if branch}{if ticket}a{else}b{end}{end}", "b"),
+            // 選ばれなかった分岐の未定義の変数はエラーにしない
+            ("{if missing}{missing}{end}", ""),
+        ];
+        for (source, expected) in cases {
+            assert_eq!(render(source, &vars).unwrap(), expected, "{source}");
+        }
+    }
+
+    #[test]
+    fn rejects_unbalanced_conditionals() {
+        for source in [
+            "{if branch}on {branch}",
+            "{if branch}a{else}b",
+            "{if branch}a{else}b{else}c{end}",
+            "a{else}b",
+            "a{end}",
+            "{if}a{end}",
+            "{if !}a{end}",
+            "{if a b}a{end}",
+        ] {
+            assert!(
+                matches!(Template::parse(source), Err(Error::InvalidTemplate(_))),
+                "{source}"
+            );
+        }
+    }
+
+    #[test]
+    fn lists_referenced_variables() {
+        let template = Template::parse("{b} {a | upper} {if c}{b}{else}{d}{end}").unwrap();
+        assert_eq!(template.variables(), ["b", "a", "c", "d"]);
+        assert!(!template.is_static());
+        assert!(Template::parse("plain {{text}}").unwrap().is_static());
+    }
+}
@@ -0,0 +1,261 @@
+//! テンプレートから参照できる組み込み変数
+
+use crate::SystemInfo;
+use crate::sysinfo::{format_gib, format_uptime};
+use crate::template::Variables;
+use chrono::Local;
+use std::path::{Path, PathBuf};
+
+/// 組み込み変数の定義
+pub struct Builtin {
+    /// 変数名
+    pub name: &'static str,
+    resolve: fn(&Path) -> Option<String>,
+}
+
+impl Builtin {
+    /// 説明（現在の言語）
+    pub fn description(&self) -> String {
+        t!(&format!("variable.{}", self.name))
+    }
+}
+
+/// 組み込み変数の一覧
+const BUILTINS: &[Builtin] = &[
+    Builtin {
+        name: "hostname",
+        resolve: hostname,
+    },
+    Builtin {
+        name: "user",
+        resolve: user,
+    },
+    Builtin {
+        name: "time",
+        resolve: time,
+    },
+    Builtin {
+        name: "date",
+        resolve: date,
+    },
+    Builtin {
+        name: "cwd",
+        resolve: cwd,
+    },
+    Builtin {
+        name: "cwd.name",
+        resolve: cwd_name,
+    },
+    Builtin {
+        name: "git.branch",
+        resolve: git_branch,
+    },
+    Builtin {
+        name: "git.repo",
+        resolve: git_repo,
+    },
+    Builtin {
+        name: "sys.uptime",
+        resolve: sys_uptime,
+    },
+    Builtin {
+        name: "sys.load",
+        resolve: sys_load,
+    },
+    Builtin {
+        name: "sys.load5",
+        resolve: sys_load5,
+    },
+    Builtin {
+        name: "sys.load15",
+        resolve: sys_load15,
+    },
+    Builtin {
+        name: "sys.cpus",
+        resolve: sys_cpus,
+    },
+    Builtin {
+        name: "sys.mem.used",
+        resolve: sys_mem_used,
+    },
+    Builtin {
+        name: "sys.mem.total",
+        resolve: sys_mem_total,
+    },
+    Builtin {
+        name: "sys.mem.percent",
+        resolve: sys_mem_percent,
+    },
+    Builtin {
+        name: "sys.battery",
+        resolve: sys_battery,
+    },
+    Builtin {
+        name: "sys.kernel",
+        resolve: sys_kernel,
+    },
+];
+
+/// 組み込み変数の値を提供する
+///
+/// 値は参照されるたびに取得し直す。`cwd` と `git.*` は作業ディレクトリ
+/// （指定しない場合はカレントディレクトリ）を基準とする
+#[derive(Debug, Clone, Default)]
+pub struct BuiltinVariables {
+    dir: Option<PathBuf>,
+}
+
+impl BuiltinVariables {
+    /// カレントディレクトリを基準とする組み込み変数
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 指定したディレクトリを作業ディレクトリとする組み込み変数
+    pub fn in_dir(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            dir: Some(dir.into()),
+        }
+    }
+
+    /// 組み込み変数の一覧を取得
+    pub fn list() -> &'static [Builtin] {
+        BUILTINS
+    }
+
+    /// 組み込み変数か
+    pub fn is_builtin(name: &str) -> bool {
+        BUILTINS.iter().any(|builtin| builtin.name == name)
+    }
+}
+
+impl Variables for BuiltinVariables {
+    fn get(&self, name: &str) -> Option<String> {
+        let builtin = BUILTINS.iter().find(|builtin| builtin.name == name)?;
+        match self.dir {
+            Some(ref dir) => (builtin.resolve)(dir),
+            None => (builtin.resolve)(&std::env::current_dir().ok()?),
+        }
+    }
+}
+
+fn hostname(_dir: &Path) -> Option<String> {
+    gethostname::gethostname().into_string().ok()
+}
+
+fn user(_dir: &Path) -> Option<String> {
+    ["USER", "LOGNAME", "USERNAME"]
+        .into_iter()
+        .find_map(|key| std::env::var(key).ok())
+}
+
+fn time(_dir: &Path) -> Option<String> {
+    Some(Local::now().format("%H:%M").to_string())
+}
+
+fn date(_dir: &Path) -> Option<String> {
+    Some(Local::now().format("%Y-%m-%d").to_string())
+}
+
+fn cwd(dir: &Path) -> Option<String> {
+    Some(dir.display().to_string())
+}
+
+fn cwd_name(dir: &Path) -> Option<String> {
+    file_name(dir)
+}
+
+fn git_branch(dir: &Path) -> Option<String> {
+    let (_, git_dir) = find_git_dir(dir)?;
+    let head = std::fs::read_to_string(git_dir.join("HEAD")).ok()?;
+    let head = head.trim();
+
+    match head.strip_prefix("ref: ") {
+        Some(reference) => Some(
+            reference
+                .strip_prefix("refs/heads/")
+                .unwrap_or(reference)
+                .to_string(),
+        ),
+        None => Some(head.chars().take(7).collect()),
+    }
+}
+
+fn git_repo(dir: &Path) -> Option<String> {
+    let (work_tree, _) = find_git_dir(dir)?;
+    file_name(&work_tree)
+}
+
+fn sys_uptime(_dir: &Path) -> Option<String> {
+    SystemInfo::cached().uptime.map(format_uptime)
+}
+
+fn sys_load(_dir: &Path) -> Option<String> {
+    SystemInfo::cached()
+        .load
+        .map(|load| format!("{:.1}", load[0]))
+}
+
+fn sys_load5(_dir: &Path) -> Option<String> {
+    SystemInfo::cached()
+        .load
+        .map(|load| format!("{:.1}", load[1]))
+}
+
+fn sys_load15(_dir: &Path) -> Option<String> {
+    SystemInfo::cached()
+        .load
+        .map(|load| format!("{:.1}", load[2]))
+}
+
+fn sys_cpus(_dir: &Path) -> Option<String> {
+    SystemInfo::cached().cpus.map(|cpus| cpus.to_string())
+}
+
+fn sys_mem_used(_dir: &Path) -> Option<String> {
+    SystemInfo::cached().mem_used.map(format_gib)
+}
+
+fn sys_mem_total(_dir: &Path) -> Option<String> {
+    SystemInfo::cached().mem_total.map(format_gib)
+}
+
+fn sys_mem_percent(_dir: &Path) -> Option<String> {
+    SystemInfo::cached()
+        .mem_percent()
+        .map(|percent| format!("{:.0}", percent))
+}
+
+fn sys_battery(_dir: &Path) -> Option<String> {
+    SystemInfo::cached()
+        .battery
+        .map(|battery| battery.to_string())
+}
+
+fn sys_kernel(_dir: &Path) -> Option<String> {
+    SystemInfo::cached().kernel
+}
+
+fn file_name(path: &Path) -> Option<String> {
+    path.file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+}
+
+/// 指定したディレクトリから上位へ `.git` を探し、作業ツリーとgitディレクトリを返す
+///
+/// worktree等で `.git` がファイルの場合は `gitdir:` の参照先を辿る
+fn find_git_dir(start: &Path) -> Option<(PathBuf, PathBuf)> {
+    for dir in start.ancestors() {
+        let dot_git = dir.join(".git");
+        if dot_git.is_dir() {
+            return Some((dir.to_path_buf(), dot_git));
+        }
+        if dot_git.is_file() {
+            let content = std::fs::read_to_string(&dot_git).ok()?;
+            let git_dir = content.trim().strip_prefix("gitdir:")?.trim();
+            return Some((dir.to_path_buf(), dir.join(git_dir)));
+        }
+    }
+
+    None
+}
@@ -0,0 +1,53 @@
+//! ファイルの変更検出
+
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// 変更検出に使うファイルの状態（更新日時とサイズ、存在しない場合は `None`）
+type Fingerprint = Option<(SystemTime, u64)>;
+
+/// 複数のファイルの変更をポーリングで検出する
+///
+/// ディレクトリも指定でき、その場合は中のファイルの追加・削除・名前の変更を検出する。
+/// 呼び出し側のループから定期的に [`poll`](Self::poll) を呼ぶ
+#[derive(Debug, Clone, Default)]
+pub struct FileWatcher {
+    files: Vec<(PathBuf, Fingerprint)>,
+}
+
+impl FileWatcher {
+    /// 監視対象のファイルを指定して作成（現在の状態を基準にする）
+    pub fn new(paths: impl IntoIterator<Item = PathBuf>) -> Self {
+        let mut files: Vec<(PathBuf, Fingerprint)> = Vec::new();
+        for path in paths {
+            if !files.iter().any(|(p, _)| *p == path) {
+                let fingerprint = fingerprint(&path);
+                files.push((path, fingerprint));
+            }
+        }
+        Self { files }
+    }
+
+    /// 監視対象のファイル一覧
+    pub fn paths(&self) -> impl Iterator<Item = &Path> {
+        self.files.iter().map(|(path, _)| path.as_path())
+    }
+
+    /// 前回の呼び出し以降に変更（作成・削除を含む）されたファイルを取得
+    pub fn poll(&mut self) -> Vec<PathBuf> {
+        let mut changed = Vec::new();
+        for (path, last) in &mut self.files {
+            let current = fingerprint(path);
+            if current != *last {
+                *last = current;
+                changed.push(path.clone());
+            }
+        }
+        changed
+    }
+}
+
+fn fingerprint(path: &Path) -> Fingerprint {
+    let metadata = std::fs::metadata(path).ok()?;
+    Some((metadata.modified().ok()?, metadata.len()))
+}